@@ -0,0 +1,327 @@
+// SPDX-FileCopyrightText: 2025 Famedly GmbH (info@famedly.com)
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Audit logs
+//!
+//! Module containing the [`audit!`](crate::audit!) macro and the layer
+//! delivering the audit events to the destination of the [`AuditConfig`],
+//! waiting until each record is written to disk or exported.
+use std::{
+	fmt,
+	fs::{File, OpenOptions},
+	io::{self, Write},
+	path::Path,
+	sync::{
+		Mutex, MutexGuard, PoisonError,
+		mpsc::{self, Sender, SyncSender},
+	},
+	thread,
+};
+
+use opentelemetry::InstrumentationScope;
+use opentelemetry_appender_tracing::layer::OpenTelemetryTracingBridge;
+use opentelemetry_otlp::{LogExporter, WithExportConfig as _};
+use opentelemetry_sdk::{
+	Resource,
+	error::{OTelSdkError, OTelSdkResult},
+	logs::{LogBatch, LogExporter as _, LogProcessor, SdkLogRecord, SdkLoggerProvider},
+};
+use tokio::runtime::Runtime;
+use tracing_subscriber::{Layer as _, filter::filter_fn, fmt::MakeWriter};
+use url::Url;
+
+use crate::{
+	OtelInitError,
+	builder::BoxedLayer,
+	config::{AuditConfig, AuditDestination, LogFormat, StdoutLogsConfig},
+	stdout,
+};
+
+/// Target of the audit events
+pub const TARGET: &str = "audit";
+
+/// Emits an audit event, delivered to the audit logs whatever the levels of
+/// the other outputs. Takes the same arguments as [`tracing::info!`].
+///
+/// The call returns once the record is delivered, see
+/// [`AuditConfig`](crate::config::AuditConfig).
+///
+/// example
+/// ```rust
+/// use rust_telemetry::audit;
+///
+/// audit!(user = "alice", room = "!abc:example.org", "Deleted the room");
+/// ```
+#[macro_export]
+macro_rules! audit {
+	($($arg:tt)+) => {
+		$crate::reexport::tracing::event!(
+			target: $crate::audit::TARGET,
+			$crate::reexport::tracing::Level::INFO,
+			$($arg)+
+		)
+	};
+}
+
+/// Builds the layer delivering the audit events to the configured destination
+pub(crate) fn layer(config: &AuditConfig, resource: Resource) -> Result<BoxedLayer, OtelInitError> {
+	let filter = filter_fn(|metadata| metadata.target() == TARGET);
+	Ok(match &config.destination {
+		AuditDestination::File(path) => {
+			let file = SyncedFile::open(path).map_err(OtelInitError::AuditError)?;
+			let fmt_config =
+				StdoutLogsConfig { format: LogFormat::OtlpJson, ansi: false, ..Default::default() };
+			Box::new(stdout::layer(&fmt_config, file).with_filter(filter))
+		}
+		AuditDestination::Otlp(endpoint) => {
+			let processor = BlockingProcessor::spawn(endpoint.url.clone(), resource.clone())?;
+			let logger_provider = SdkLoggerProvider::builder()
+				.with_resource(resource)
+				.with_log_processor(processor)
+				.build();
+			Box::new(OpenTelemetryTracingBridge::new(&logger_provider).with_filter(filter))
+		}
+	})
+}
+
+/// Reports on stderr a record that could not be delivered, so that it is not
+/// lost
+fn report_undelivered(error: &dyn fmt::Display, record: &dyn fmt::Display) {
+	let _ = writeln!(io::stderr(), "Audit record not delivered ({error}): {record}");
+}
+
+/// File the audit records are appended to
+#[derive(Debug)]
+struct SyncedFile(Mutex<File>);
+
+impl SyncedFile {
+	/// Opens the file for appending, creating it if needed
+	fn open(path: &Path) -> io::Result<Self> {
+		Ok(Self(Mutex::new(OpenOptions::new().create(true).append(true).open(path)?)))
+	}
+}
+
+impl<'a> MakeWriter<'a> for SyncedFile {
+	type Writer = SyncedRecord<'a>;
+
+	fn make_writer(&'a self) -> Self::Writer {
+		SyncedRecord(self.0.lock().unwrap_or_else(PoisonError::into_inner))
+	}
+}
+
+/// Writer of a single record, syncing the file to disk when dropped
+struct SyncedRecord<'a>(MutexGuard<'a, File>);
+
+impl Write for SyncedRecord<'_> {
+	fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+		self.0.write_all(buf).inspect_err(|err| {
+			report_undelivered(err, &String::from_utf8_lossy(buf).trim_end());
+		})?;
+		Ok(buf.len())
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		self.0.flush()
+	}
+}
+
+impl Drop for SyncedRecord<'_> {
+	fn drop(&mut self) {
+		if let Err(err) = self.0.sync_data() {
+			let _ = writeln!(io::stderr(), "Audit record possibly not synced to disk: {err}");
+		}
+	}
+}
+
+/// Record sent to the exporting thread, with the channel receiving the result
+/// of the export
+type Export = (SdkLogRecord, InstrumentationScope, SyncSender<OTelSdkResult>);
+
+/// Log processor exporting each record in its own request from a dedicated
+/// thread, and waiting for the result
+#[derive(Debug)]
+struct BlockingProcessor {
+	/// Sends the records to the exporting thread
+	exports: Sender<Export>,
+}
+
+impl BlockingProcessor {
+	/// Starts the thread exporting the records to the endpoint, with its own
+	/// runtime so that the callers can wait for the exports from any thread
+	fn spawn(endpoint: Url, resource: Resource) -> Result<Self, OtelInitError> {
+		let (exports, received) = mpsc::channel::<Export>();
+		let (ready, started) = mpsc::sync_channel(1);
+		thread::Builder::new()
+			.name("telemetry-audit".to_owned())
+			.spawn(move || {
+				let (runtime, exporter) = match start_exporter(endpoint, &resource) {
+					Ok(started) => {
+						let _ = ready.send(Ok(()));
+						started
+					}
+					Err(err) => {
+						let _ = ready.send(Err(err));
+						return;
+					}
+				};
+				for (record, scope, result) in received {
+					let batch = [(&record, &scope)];
+					let _ = result.send(runtime.block_on(exporter.export(LogBatch::new(&batch))));
+				}
+				let _ = exporter.shutdown();
+			})
+			.map_err(OtelInitError::AuditError)?;
+		started.recv().map_err(|_| {
+			OtelInitError::AuditError(io::Error::other("The audit exporting thread stopped"))
+		})??;
+		Ok(Self { exports })
+	}
+}
+
+/// Builds the runtime of the exporting thread and the exporter, which must be
+/// created in the runtime
+fn start_exporter(
+	endpoint: Url,
+	resource: &Resource,
+) -> Result<(Runtime, LogExporter), OtelInitError> {
+	let runtime = tokio::runtime::Builder::new_current_thread()
+		.enable_all()
+		.build()
+		.map_err(OtelInitError::AuditError)?;
+	let mut exporter = runtime
+		.block_on(async { LogExporter::builder().with_tonic().with_endpoint(endpoint).build() })?;
+	exporter.set_resource(resource);
+	Ok((runtime, exporter))
+}
+
+impl LogProcessor for BlockingProcessor {
+	fn emit(&self, record: &mut SdkLogRecord, scope: &InstrumentationScope) {
+		let (result, exported) = mpsc::sync_channel(1);
+		let delivered = match self.exports.send((record.clone(), scope.clone(), result)) {
+			Ok(()) => exported.recv().unwrap_or(Err(OTelSdkError::AlreadyShutdown)),
+			Err(_) => Err(OTelSdkError::AlreadyShutdown),
+		};
+		if let Err(err) = delivered {
+			report_undelivered(&err, &format_args!("{record:?}"));
+		}
+	}
+
+	fn force_flush(&self) -> OTelSdkResult {
+		Ok(())
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	#![allow(clippy::expect_used)]
+	use std::{env, process::Command};
+
+	use tracing::level_filters::LevelFilter;
+
+	use crate::{
+		build_otel,
+		config::{AuditConfig, AuditDestination, OtelConfig, StdoutLogsConfig},
+	};
+
+	/// Config sending the audit logs to the destination, with the stdout logs
+	/// filtering everything out
+	fn audit_config(destination: AuditDestination) -> OtelConfig {
+		OtelConfig {
+			stdout: Some(StdoutLogsConfig {
+				level: LevelFilter::OFF.into(),
+				general_level: LevelFilter::OFF.into(),
+				..Default::default()
+			}),
+			audit: Some(AuditConfig { enabled: true, destination }),
+			..Default::default()
+		}
+	}
+
+	#[test]
+	fn test_audit_file() {
+		let directory = tempfile::tempdir().expect("Error creating directory");
+		let path = directory.path().join("audit.log");
+		let config = audit_config(AuditDestination::File(path.clone()));
+		let (subscriber, _guard) = build_otel!(&config).expect("Error initializing Otel");
+		tracing::subscriber::with_default(subscriber, || {
+			tracing::info!("Not audited");
+			audit!(user = "alice", "Deleted the room");
+
+			let contents = std::fs::read_to_string(&path).expect("Error reading the audit logs");
+			assert_eq!(contents.lines().count(), 1, "{contents}");
+			let record: serde_json::Value =
+				serde_json::from_str(&contents).expect("Invalid audit record");
+			assert_eq!(record["body"]["stringValue"], "Deleted the room");
+			let attributes = record["attributes"].as_array().expect("Missing attributes");
+			assert!(attributes.contains(&serde_json::json!({
+				"key": "user",
+				"value": { "stringValue": "alice" }
+			})));
+		});
+	}
+
+	#[test]
+	fn test_audit_not_on_stdout() {
+		// The stdout logs are written to the real stdout, so they are checked from
+		// a run of this test in a child process
+		if env::var_os("AUDIT_STDOUT_CHILD").is_some() {
+			let directory = tempfile::tempdir().expect("Error creating directory");
+			let config = OtelConfig {
+				audit: Some(AuditConfig {
+					enabled: true,
+					destination: AuditDestination::File(directory.path().join("audit.log")),
+				}),
+				..Default::default()
+			};
+			let (subscriber, _guard) = build_otel!(&config).expect("Error initializing Otel");
+			tracing::subscriber::with_default(subscriber, || {
+				tracing::info!("Not audited");
+				audit!(user = "alice", "Deleted the room");
+			});
+			return;
+		}
+
+		let output = Command::new(env::current_exe().expect("Missing test binary"))
+			.args(["--exact", "audit::tests::test_audit_not_on_stdout", "--nocapture"])
+			.env("AUDIT_STDOUT_CHILD", "1")
+			.output()
+			.expect("Error running the test");
+		assert!(output.status.success(), "{output:?}");
+		let stdout = String::from_utf8_lossy(&output.stdout);
+		assert!(stdout.contains("Not audited"), "{stdout}");
+		assert!(!stdout.contains("Deleted the room"), "{stdout}");
+	}
+
+	#[cfg(feature = "testing")]
+	#[tokio::test(flavor = "multi_thread")]
+	async fn test_audit_otlp() {
+		use opentelemetry_proto::tonic::common::v1::any_value::Value;
+
+		let collector =
+			crate::testing::MockCollector::start().await.expect("Error starting collector");
+		let config = audit_config(AuditDestination::Otlp(collector.endpoint().into()));
+		let (subscriber, _guard) = build_otel!(&config).expect("Error initializing Otel");
+		tracing::subscriber::with_default(subscriber, || {
+			tracing::info!("Not audited");
+			audit!(user = "alice", "Deleted the room");
+		});
+
+		// Delivered before the macro returns, without flushing
+		let logs = collector.logs();
+		assert_eq!(logs.len(), 1);
+		let records: Vec<_> = logs[0]
+			.request
+			.resource_logs
+			.iter()
+			.flat_map(|l| &l.scope_logs)
+			.flat_map(|l| &l.log_records)
+			.collect();
+		assert_eq!(records.len(), 1);
+		assert!(matches!(
+			records[0].body.as_ref().and_then(|body| body.value.as_ref()),
+			Some(Value::StringValue(body)) if body == "Deleted the room"
+		));
+		assert_eq!(logs[0].resource_attribute("service.name"), Some(env!("CARGO_PKG_NAME")));
+	}
+}
@@ -2,11 +2,65 @@
 //
 // SPDX-License-Identifier: Apache-2.0
 
-//! Module containing the function to add a metrics layer to axum
+//! Module containing the layers tracing the requests received by axum and
+//! their WebSocket and SSE connections, recording their metrics and panics and
+//! writing their trace ids and server timings to the responses, and the
+//! extractors of their trace context
+//!
+//! # Layer order
+//!
+//! The [`CatchPanicLayer`], [`ConnectionTelemetryLayer`], [`DebugHeaderLayer`]
+//! and [`TraceResponseLayer`] work on the span of the request, so add them
+//! inside the [`ServerTraceLayer`], that is before it with
+//! [`Router::layer`](axum::Router::layer).
+//!
+//! # Global meter provider
+//!
+//! The [`HttpMetricsLayer`], [`CatchPanicLayer`] and
+//! [`ConnectionTelemetryLayer`] record their metrics with the global meter
+//! provider when they are created, so create them after initializing the
+//! OpenTelemetry.
+//!
+//! # Trusted headers
+//!
+//! The headers of the [`DebugHeaderLayer`] and [`ServerTimingLayer`] are
+//! trusted if their value is signed by [`sign_trusted_header`], or if the
+//! request comes from one of the `allowed_sources` of their config, which
+//! requires serving the router with
+//! [`into_make_service_with_connect_info`](axum::Router::into_make_service_with_connect_info).
+mod catch_panic;
+mod connection;
+mod debug_header;
+mod extract;
+mod metrics;
+mod server_timing;
+mod trace;
+mod trace_response;
+mod trusted_header;
+
 use axum::routing::Router;
-use famedly_rust_utils::GenericCombinators;
 
-use super::config::OtelConfig;
+#[cfg(feature = "axum-ws")]
+pub use self::connection::TracedWebSocket;
+pub(crate) use self::server_timing::layer as server_timing_layer;
+pub use self::{
+	catch_panic::{CatchPanicLayer, CatchPanicService},
+	connection::{
+		ConnectionTelemetry, ConnectionTelemetryLayer, ConnectionTelemetryService,
+		MissingConnectionTelemetryLayer,
+	},
+	debug_header::{DebugHeaderLayer, DebugHeaderService},
+	extract::{Baggage, MissingServerTraceLayer, SpanContext, TraceId},
+	metrics::{HttpMetricsLayer, HttpMetricsService},
+	server_timing::{ServerTimingLayer, ServerTimingService},
+	trace::{ServerTraceLayer, ServerTraceService},
+	trace_response::{TraceResponseLayer, TraceResponseService},
+	trusted_header::sign_trusted_header,
+};
+use super::{
+	OtelInitError,
+	config::{HttpMetricsConfig, OtelConfig},
+};
 
 /// Adds a layer to create metrics if the metrics exporting is enabled
 ///
@@ -23,7 +77,8 @@ use super::config::OtelConfig;
 /// async fn main() {
 /// 	let config = Some(rust_telemetry::config::OtelConfig::default());
 /// 	let app = Router::new().route("/", get("Test"));
-/// 	let app = rust_telemetry::add_axum_metrics_layer!(app, config.as_ref());
+/// 	let app = rust_telemetry::add_axum_metrics_layer!(app, config.as_ref())
+/// 		.unwrap();
 ///
 /// 	let listener =
 /// 		tokio::net::TcpListener::bind("127.0.0.1:8000").await.unwrap();
@@ -37,18 +92,25 @@ macro_rules! add_axum_metrics_layer {
 	};
 }
 
-#[allow(missing_docs)]
-pub fn add_metrics_layer(router: Router, config: Option<&OtelConfig>) -> Router {
+/// Adds a [`HttpMetricsLayer`] configured by the `http.metrics` section if the
+/// metrics exporting is enabled. Call it after initializing the
+/// OpenTelemetry, as the layer records the metrics with the global meter
+/// provider.
+pub fn add_metrics_layer(
+	router: Router,
+	config: Option<&OtelConfig>,
+) -> Result<Router, OtelInitError> {
 	let enabled = config
 		.and_then(|config| config.exporter.as_ref())
 		.and_then(|exporter| exporter.metrics.as_ref())
 		.is_some_and(|metrics| metrics.enabled);
+	if !enabled {
+		return Ok(router);
+	}
 
-	router.chain_if(enabled, |router| {
-		let layer = opentelemetry_instrumentation_tower::HTTPLayerBuilder::builder()
-			.build()
-			.inspect_err(|e| tracing::warn!("Error creating metrics layer: {e:?}"))
-			.ok();
-		router.chain_opt(layer, Router::layer)
-	})
+	let default_config = HttpMetricsConfig::default();
+	let metrics_config = config
+		.and_then(|config| config.http.as_ref())
+		.map_or(&default_config, |http| &http.metrics);
+	Ok(router.layer(HttpMetricsLayer::new(metrics_config)?))
 }
@@ -0,0 +1,264 @@
+// SPDX-FileCopyrightText: 2025 Famedly GmbH (info@famedly.com)
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Panics
+//!
+//! Module containing the tower layer catching the panics of the handlers,
+//! responding with a 500 status and recording them as exceptions.
+use std::{
+	any::Any,
+	backtrace::{Backtrace, BacktraceStatus},
+	cell::RefCell,
+	future::{Future, poll_fn},
+	panic::{self, AssertUnwindSafe},
+	pin::Pin,
+	sync::Once,
+	task::{Context, Poll},
+};
+
+use axum::extract::MatchedPath;
+use http::{Request, Response, StatusCode};
+use opentelemetry::{
+	KeyValue,
+	metrics::{Counter, Meter},
+	trace::Status,
+};
+use opentelemetry_semantic_conventions::attribute::{HTTP_REQUEST_METHOD, HTTP_ROUTE};
+use tower_layer::Layer;
+use tower_service::Service;
+use tracing::Span;
+use tracing_opentelemetry::OpenTelemetrySpanExt as _;
+
+use crate::redaction::redact_span_value;
+
+thread_local! {
+	/// Backtrace of the last panic of the thread, if captured
+	static BACKTRACE: RefCell<Option<String>> = const { RefCell::new(None) };
+}
+
+/// Installs the panic hook capturing the backtraces, once
+static BACKTRACE_HOOK: Once = Once::new();
+
+/// Chains a panic hook capturing the backtrace of the panics to the current
+/// one. The backtraces are captured as configured by the `RUST_BACKTRACE` and
+/// `RUST_LIB_BACKTRACE` environment variables.
+fn install_backtrace_hook() {
+	BACKTRACE_HOOK.call_once(|| {
+		let previous = panic::take_hook();
+		panic::set_hook(Box::new(move |info| {
+			let backtrace = Backtrace::capture();
+			BACKTRACE.set(
+				(backtrace.status() == BacktraceStatus::Captured).then(|| backtrace.to_string()),
+			);
+			previous(info);
+		}));
+	});
+}
+
+/// Layer catching the panics of the handlers and responding with a 500 status
+/// instead of closing the connection
+///
+/// A panic is recorded as an `exception` event of the current span, with the
+/// `exception.type`, `exception.message` and, if captured, the
+/// `exception.stacktrace` attributes, the status of the span is set to an
+/// error and the `http.server.panics` counter is incremented, see the
+/// [layer order](super#layer-order). Creating the layer chains a panic hook
+/// capturing the backtraces to the current one.
+///
+/// Example
+///
+/// ```rust
+/// use axum::routing::{Router, get};
+/// use rust_telemetry::axum::{CatchPanicLayer, ServerTraceLayer};
+///
+/// let app: Router = Router::new()
+/// 	.route("/", get("Test"))
+/// 	.layer(CatchPanicLayer::new())
+/// 	.layer(ServerTraceLayer::default());
+/// ```
+#[derive(Debug, Clone)]
+pub struct CatchPanicLayer {
+	/// `http.server.panics`
+	panics: Counter<u64>,
+}
+
+impl CatchPanicLayer {
+	/// Creates the layer with the [global meter
+	/// provider](super#global-meter-provider)
+	#[must_use]
+	pub fn new() -> Self {
+		Self::with_meter(&opentelemetry::global::meter(env!("CARGO_PKG_NAME")))
+	}
+
+	/// Creates the layer with the given meter
+	pub(crate) fn with_meter(meter: &Meter) -> Self {
+		install_backtrace_hook();
+		Self {
+			panics: meter
+				.u64_counter("http.server.panics")
+				.with_description("Number of panics of the HTTP server handlers.")
+				.with_unit("{panic}")
+				.build(),
+		}
+	}
+}
+
+impl Default for CatchPanicLayer {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl<S> Layer<S> for CatchPanicLayer {
+	type Service = CatchPanicService<S>;
+
+	fn layer(&self, inner: S) -> Self::Service {
+		CatchPanicService { inner, panics: self.panics.clone() }
+	}
+}
+
+/// Service catching the panics of the handlers, see [`CatchPanicLayer`]
+#[derive(Debug, Clone)]
+pub struct CatchPanicService<S> {
+	/// Service handling the requests
+	inner: S,
+	/// `http.server.panics`
+	panics: Counter<u64>,
+}
+
+impl<S, B, ResBody> Service<Request<B>> for CatchPanicService<S>
+where
+	S: Service<Request<B>, Response = Response<ResBody>>,
+	S::Future: Send + 'static,
+	ResBody: Default + Send + 'static,
+{
+	type Response = S::Response;
+	type Error = S::Error;
+	type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+	fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+		self.inner.poll_ready(cx)
+	}
+
+	fn call(&mut self, request: Request<B>) -> Self::Future {
+		let mut attributes =
+			vec![KeyValue::new(HTTP_REQUEST_METHOD, request.method().as_str().to_owned())];
+		if let Some(route) = request.extensions().get::<MatchedPath>() {
+			attributes.push(KeyValue::new(HTTP_ROUTE, route.as_str().to_owned()));
+		}
+		let panics = self.panics.clone();
+		let mut future = match panic::catch_unwind(AssertUnwindSafe(|| self.inner.call(request))) {
+			Ok(future) => Box::pin(future),
+			Err(payload) => {
+				record_panic(payload.as_ref(), &panics, &attributes);
+				return Box::pin(async { Ok(internal_server_error()) });
+			}
+		};
+		Box::pin(async move {
+			let caught = poll_fn(|cx| {
+				panic::catch_unwind(AssertUnwindSafe(|| future.as_mut().poll(cx)))
+					.map_or_else(|payload| Poll::Ready(Err(payload)), |poll| poll.map(Ok))
+			})
+			.await;
+			caught.unwrap_or_else(|payload| {
+				record_panic(payload.as_ref(), &panics, &attributes);
+				Ok(internal_server_error())
+			})
+		})
+	}
+}
+
+/// Empty response with a 500 status
+fn internal_server_error<B: Default>() -> Response<B> {
+	let mut response = Response::new(B::default());
+	*response.status_mut() = StatusCode::INTERNAL_SERVER_ERROR;
+	response
+}
+
+/// Records a panic as an exception of the current span, and counts it
+fn record_panic(payload: &(dyn Any + Send), panics: &Counter<u64>, attributes: &[KeyValue]) {
+	let message = payload
+		.downcast_ref::<&str>()
+		.copied()
+		.or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+		.unwrap_or("Box<dyn Any>");
+	let stacktrace = BACKTRACE.take();
+	tracing::error!(
+		exception.r#type = "panic",
+		exception.message = message,
+		exception.stacktrace = stacktrace.as_deref(),
+		"exception"
+	);
+	let span = Span::current();
+	let message = redact_span_value(&span, "otel.status_message", message.to_owned());
+	span.set_status(Status::error(message));
+	panics.add(1, attributes);
+}
+
+#[cfg(test)]
+mod tests {
+	#![allow(clippy::expect_used)]
+	use axum::{Router, body::Body, routing::get};
+	use http::{Request, StatusCode};
+	use opentelemetry::{KeyValue, Value, trace::Status};
+	use opentelemetry_sdk::metrics::data::{AggregatedMetrics, MetricData};
+	use tower::ServiceExt as _;
+
+	use super::CatchPanicLayer;
+	use crate::{
+		axum::ServerTraceLayer,
+		fixtures::{TestMetrics, TestSpans, flat_metrics},
+	};
+
+	#[tokio::test]
+	async fn test_catch_panic() {
+		let spans = TestSpans::new();
+		let _default = spans.set_default();
+		let metrics = TestMetrics::new();
+
+		let app = Router::new()
+			.route("/ok", get(async || "OK"))
+			.route(
+				"/panic",
+				get(async || -> &'static str {
+					panic!("Handler failed");
+				}),
+			)
+			.layer(CatchPanicLayer::with_meter(&metrics.meter()))
+			.layer(ServerTraceLayer::default());
+		let request = Request::get("/ok").body(Body::empty()).expect("Invalid request");
+		let response = app.clone().oneshot(request).await.expect("Error sending request");
+		assert_eq!(response.status(), StatusCode::OK);
+		let request = Request::get("/panic").body(Body::empty()).expect("Invalid request");
+		let response = app.oneshot(request).await.expect("Error sending request");
+		assert_eq!(response.status(), StatusCode::INTERNAL_SERVER_ERROR);
+
+		let spans = spans.finished();
+		assert_eq!(spans.len(), 2);
+		assert!(spans[0].events.is_empty());
+		let span = &spans[1];
+		assert!(matches!(span.status, Status::Error { .. }));
+		let events: Vec<_> = span.events.iter().collect();
+		assert_eq!(events.len(), 1);
+		assert_eq!(events[0].name, "exception");
+		for attribute in [
+			KeyValue::new("exception.type", "panic"),
+			KeyValue::new("exception.message", Value::from("Handler failed")),
+		] {
+			assert!(events[0].attributes.contains(&attribute), "{attribute:?} missing");
+		}
+
+		let exported = metrics.flushed();
+		let metrics = flat_metrics(&exported);
+		assert_eq!(metrics.len(), 1);
+		assert_eq!(metrics[0].name(), "http.server.panics");
+		let AggregatedMetrics::U64(MetricData::Sum(panics)) = metrics[0].data() else {
+			panic!("Invalid panics metric");
+		};
+		let points: Vec<_> = panics.data_points().collect();
+		assert_eq!(points.len(), 1);
+		assert_eq!(points[0].value(), 1);
+		assert!(points[0].attributes().any(|kv| *kv == KeyValue::new("http.route", "/panic")));
+	}
+}
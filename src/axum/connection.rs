@@ -0,0 +1,779 @@
+// SPDX-FileCopyrightText: 2025 Famedly GmbH (info@famedly.com)
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Long-lived connections
+//!
+//! Module containing the tower layer and the extractor tracing the WebSocket
+//! and SSE connections, with a span per connection, an event per message and
+//! the metrics of the connections and their messages.
+use std::{
+	pin::Pin,
+	sync::Arc,
+	task::{Context, Poll},
+	time::Instant,
+};
+
+#[cfg(feature = "axum-ws")]
+use axum::extract::ws::{CloseFrame, Message, WebSocket};
+use axum::{
+	body::{Body, Bytes},
+	extract::{FromRequestParts, MatchedPath},
+	response::{IntoResponse, Response, sse::Sse},
+};
+use http::{Request, StatusCode, request::Parts};
+use http_body::{Frame, SizeHint};
+use opentelemetry::{
+	KeyValue,
+	metrics::{Counter, Histogram, Meter, UpDownCounter},
+	trace::{SpanContext, TraceContextExt as _},
+};
+use opentelemetry_semantic_conventions::attribute::HTTP_ROUTE;
+use tower_layer::Layer;
+use tower_service::Service;
+use tracing::{Span, field::Empty};
+use tracing_opentelemetry::OpenTelemetrySpanExt as _;
+
+/// Boundaries of the buckets of the `http.server.connection.duration`
+/// histogram, in seconds, as the connections last up to hours
+const DURATION_BUCKETS: [f64; 10] =
+	[1.0, 5.0, 15.0, 30.0, 60.0, 300.0, 900.0, 1800.0, 3600.0, 14400.0];
+
+/// Instruments recording the metrics of the connections
+#[derive(Debug)]
+struct Instruments {
+	/// `http.server.active_connections`
+	active_connections: UpDownCounter<i64>,
+	/// `http.server.connection.duration`
+	duration: Histogram<f64>,
+	/// `http.server.connection.messages`
+	messages: Counter<u64>,
+	/// `http.server.connection.message.size`
+	message_size: Histogram<u64>,
+}
+
+/// Layer making the [`ConnectionTelemetry`] extractor available to the
+/// handlers, to trace their WebSocket and SSE connections
+///
+/// The handlers upgrading a request to a long-lived connection wrap it with
+/// the extractor, so that the connection gets its own span, the root of a new
+/// trace linking to the span of the request, with an event at the debug level
+/// per message, and records the `http.server.active_connections` counter, the
+/// `http.server.connection.duration` histogram with the
+/// `connection.close_reason` attribute, and the
+/// `http.server.connection.messages` counter and
+/// `http.server.connection.message.size` histogram with the
+/// `message.direction` and `message.type` attributes. All of them have the
+/// `connection.kind` attribute, `websocket` or `sse`, and the `http.route`
+/// attribute, see the [layer order](super#layer-order).
+///
+/// Example
+///
+/// ```rust
+/// use std::convert::Infallible;
+///
+/// use axum::{
+/// 	response::{
+/// 		Response,
+/// 		sse::{Event, Sse},
+/// 	},
+/// 	routing::{Router, get},
+/// };
+/// use rust_telemetry::axum::{
+/// 	ConnectionTelemetry, ConnectionTelemetryLayer, ServerTraceLayer,
+/// };
+///
+/// async fn events(telemetry: ConnectionTelemetry) -> Response {
+/// 	let event: Result<_, Infallible> = Ok(Event::default().data("Test"));
+/// 	telemetry.sse(Sse::new(futures_util::stream::iter([event])))
+/// }
+///
+/// let app: Router = Router::new()
+/// 	.route("/events", get(events))
+/// 	.layer(ConnectionTelemetryLayer::new())
+/// 	.layer(ServerTraceLayer::default());
+/// ```
+#[derive(Debug, Clone)]
+pub struct ConnectionTelemetryLayer {
+	/// Instruments shared by the connections
+	instruments: Arc<Instruments>,
+}
+
+impl ConnectionTelemetryLayer {
+	/// Creates the layer with the [global meter
+	/// provider](super#global-meter-provider)
+	#[must_use]
+	pub fn new() -> Self {
+		Self::with_meter(&opentelemetry::global::meter(env!("CARGO_PKG_NAME")))
+	}
+
+	/// Creates the layer with the given meter
+	pub(crate) fn with_meter(meter: &Meter) -> Self {
+		let instruments = Instruments {
+			active_connections: meter
+				.i64_up_down_counter("http.server.active_connections")
+				.with_description("Number of active WebSocket and SSE connections.")
+				.with_unit("{connection}")
+				.build(),
+			duration: meter
+				.f64_histogram("http.server.connection.duration")
+				.with_description("Duration of WebSocket and SSE connections.")
+				.with_unit("s")
+				.with_boundaries(DURATION_BUCKETS.to_vec())
+				.build(),
+			messages: meter
+				.u64_counter("http.server.connection.messages")
+				.with_description("Number of messages of WebSocket and SSE connections.")
+				.with_unit("{message}")
+				.build(),
+			message_size: meter
+				.u64_histogram("http.server.connection.message.size")
+				.with_description("Size of the messages of WebSocket and SSE connections.")
+				.with_unit("By")
+				.build(),
+		};
+		Self { instruments: Arc::new(instruments) }
+	}
+}
+
+impl Default for ConnectionTelemetryLayer {
+	fn default() -> Self {
+		Self::new()
+	}
+}
+
+impl<S> Layer<S> for ConnectionTelemetryLayer {
+	type Service = ConnectionTelemetryService<S>;
+
+	fn layer(&self, inner: S) -> Self::Service {
+		ConnectionTelemetryService { inner, instruments: self.instruments.clone() }
+	}
+}
+
+/// Service adding the instruments of the connections to the extensions of the
+/// requests, see [`ConnectionTelemetryLayer`]
+#[derive(Debug, Clone)]
+pub struct ConnectionTelemetryService<S> {
+	/// Service handling the requests
+	inner: S,
+	/// Instruments shared by the connections
+	instruments: Arc<Instruments>,
+}
+
+impl<S, B> Service<Request<B>> for ConnectionTelemetryService<S>
+where
+	S: Service<Request<B>>,
+{
+	type Response = S::Response;
+	type Error = S::Error;
+	type Future = S::Future;
+
+	fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+		self.inner.poll_ready(cx)
+	}
+
+	fn call(&mut self, mut request: Request<B>) -> Self::Future {
+		request.extensions_mut().insert(self.instruments.clone());
+		self.inner.call(request)
+	}
+}
+
+/// Rejection of the [`ConnectionTelemetry`] extractor when the request did
+/// not go through the [`ConnectionTelemetryLayer`], responding with a 500
+/// status as it is a mistake in the setup of the router
+#[derive(Debug, Clone, Copy, thiserror::Error)]
+#[error("Missing the ConnectionTelemetryLayer, required to trace the connections")]
+pub struct MissingConnectionTelemetryLayer;
+
+impl IntoResponse for MissingConnectionTelemetryLayer {
+	fn into_response(self) -> Response {
+		tracing::error!("{self}");
+		(StatusCode::INTERNAL_SERVER_ERROR, self.to_string()).into_response()
+	}
+}
+
+/// Extractor tracing the WebSocket or SSE connection opened by the handler,
+/// see [`ConnectionTelemetryLayer`]
+///
+/// It only keeps the context of the span of the request, which the span of the
+/// connection links to, as the connections outlive the handlers and the
+/// request would otherwise stay open until they are closed.
+#[derive(Debug, Clone)]
+pub struct ConnectionTelemetry {
+	/// Instruments shared by the connections
+	instruments: Arc<Instruments>,
+	/// Context of the span of the request
+	request_span: SpanContext,
+	/// Route of the request
+	route: Option<String>,
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for ConnectionTelemetry {
+	type Rejection = MissingConnectionTelemetryLayer;
+
+	async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+		let instruments =
+			parts.extensions.get::<Arc<Instruments>>().ok_or(MissingConnectionTelemetryLayer)?;
+		Ok(Self {
+			instruments: instruments.clone(),
+			request_span: Span::current().context().span().span_context().clone(),
+			route: parts.extensions.get::<MatchedPath>().map(|path| path.as_str().to_owned()),
+		})
+	}
+}
+
+impl ConnectionTelemetry {
+	/// Traces a WebSocket, to be called in the
+	/// [`on_upgrade`](axum::extract::ws::WebSocketUpgrade::on_upgrade)
+	/// callback
+	///
+	/// The connection is closed by the client when it sends a close frame, by
+	/// the server when it sends one, and is considered disconnected if the
+	/// socket is dropped without any.
+	#[cfg(feature = "axum-ws")]
+	#[must_use]
+	pub fn websocket(self, socket: WebSocket) -> TracedWebSocket {
+		let span = tracing::info_span!(
+			parent: None,
+			"websocket connection",
+			connection.kind = "websocket",
+			http.route = self.route.as_deref(),
+			connection.close_reason = Empty,
+			websocket.close.code = Empty,
+			websocket.close.reason = Empty,
+		);
+		TracedWebSocket { socket, connection: Connection::open(self, "websocket", span) }
+	}
+
+	/// Traces the events of an SSE response
+	///
+	/// The connection is closed by the server when the stream of events ends,
+	/// and is considered disconnected if the client goes away before. The keep
+	/// alive comments are not counted as messages.
+	pub fn sse<S>(self, sse: Sse<S>) -> Response
+	where
+		Sse<S>: IntoResponse,
+	{
+		let span = tracing::info_span!(
+			parent: None,
+			"sse connection",
+			connection.kind = "sse",
+			http.route = self.route.as_deref(),
+			connection.close_reason = Empty,
+		);
+		let connection = Connection::open(self, "sse", span);
+		sse.into_response().map(|body| Body::new(TracedSseBody { body, connection }))
+	}
+}
+
+/// Reason of the closing of a connection
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum CloseReason {
+	/// The client closed the connection
+	#[cfg(feature = "axum-ws")]
+	Client,
+	/// The server closed the connection
+	Server,
+	/// The connection failed
+	Error,
+	/// The connection went away without being closed
+	Disconnected,
+}
+
+impl CloseReason {
+	/// Value of the `connection.close_reason` attribute
+	const fn as_str(self) -> &'static str {
+		match self {
+			#[cfg(feature = "axum-ws")]
+			Self::Client => "client",
+			Self::Server => "server",
+			Self::Error => "error",
+			Self::Disconnected => "disconnected",
+		}
+	}
+}
+
+/// Direction of a message
+#[derive(Debug, Clone, Copy)]
+enum Direction {
+	/// Received from the client
+	#[cfg(feature = "axum-ws")]
+	Received,
+	/// Sent to the client
+	Sent,
+}
+
+impl Direction {
+	/// Value of the `message.direction` attribute
+	const fn as_str(self) -> &'static str {
+		match self {
+			#[cfg(feature = "axum-ws")]
+			Self::Received => "received",
+			Self::Sent => "sent",
+		}
+	}
+}
+
+/// State of a traced connection, recording its metrics when dropped
+#[derive(Debug)]
+struct Connection {
+	/// Instruments shared by the connections
+	instruments: Arc<Instruments>,
+	/// Span of the connection
+	span: Span,
+	/// `connection.kind` and `http.route` attributes
+	attributes: Vec<KeyValue>,
+	/// When the connection was opened
+	opened: Instant,
+	/// Why the connection was closed, if it was
+	close_reason: Option<CloseReason>,
+}
+
+impl Connection {
+	/// Opens a connection, linking its span to the span of the request and
+	/// counting it as active
+	fn open(telemetry: ConnectionTelemetry, kind: &'static str, span: Span) -> Self {
+		if telemetry.request_span.is_valid() {
+			span.add_link(telemetry.request_span);
+		}
+		let mut attributes = vec![KeyValue::new("connection.kind", kind)];
+		if let Some(route) = telemetry.route {
+			attributes.push(KeyValue::new(HTTP_ROUTE, route));
+		}
+		telemetry.instruments.active_connections.add(1, &attributes);
+		tracing::debug!(parent: &span, "connection opened");
+		Self {
+			instruments: telemetry.instruments,
+			span,
+			attributes,
+			opened: Instant::now(),
+			close_reason: None,
+		}
+	}
+
+	/// Records a message as an event of the span and in the metrics
+	fn record_message(&self, direction: Direction, message_type: &'static str, size: usize) {
+		tracing::debug!(
+			parent: &self.span,
+			message.direction = direction.as_str(),
+			message.r#type = message_type,
+			message.size = size,
+			"message"
+		);
+		let mut attributes = self.attributes.clone();
+		attributes.push(KeyValue::new("message.direction", direction.as_str()));
+		attributes.push(KeyValue::new("message.type", message_type));
+		self.instruments.messages.add(1, &attributes);
+		self.instruments.message_size.record(u64::try_from(size).unwrap_or(u64::MAX), &attributes);
+	}
+
+	/// Records why the connection was closed, keeping the first reason, which
+	/// is returned
+	fn close(&mut self, reason: CloseReason) -> CloseReason {
+		if let Some(first) = self.close_reason {
+			return first;
+		}
+		self.close_reason = Some(reason);
+		self.span.record("connection.close_reason", reason.as_str());
+		reason
+	}
+}
+
+impl Drop for Connection {
+	fn drop(&mut self) {
+		let reason = self.close(CloseReason::Disconnected);
+		tracing::debug!(parent: &self.span, connection.close_reason = reason.as_str(), "connection closed");
+		self.instruments.active_connections.add(-1, &self.attributes);
+		let mut attributes = self.attributes.clone();
+		attributes.push(KeyValue::new("connection.close_reason", reason.as_str()));
+		self.instruments.duration.record(self.opened.elapsed().as_secs_f64(), &attributes);
+	}
+}
+
+/// Body of an SSE response, recording its events
+#[derive(Debug)]
+struct TracedSseBody {
+	/// Body of the response
+	body: Body,
+	/// Traced connection
+	connection: Connection,
+}
+
+impl http_body::Body for TracedSseBody {
+	type Data = Bytes;
+	type Error = axum::Error;
+
+	fn poll_frame(
+		self: Pin<&mut Self>,
+		cx: &mut Context<'_>,
+	) -> Poll<Option<Result<Frame<Self::Data>, Self::Error>>> {
+		let this = self.get_mut();
+		let polled = Pin::new(&mut this.body).poll_frame(cx);
+		match &polled {
+			Poll::Ready(Some(Ok(frame))) => {
+				// The keep alive comments start with a colon
+				if let Some(data) = frame.data_ref().filter(|data| !data.starts_with(b":")) {
+					this.connection.record_message(Direction::Sent, "event", data.len());
+				}
+			}
+			Poll::Ready(Some(Err(_))) => {
+				this.connection.close(CloseReason::Error);
+			}
+			Poll::Ready(None) => {
+				this.connection.close(CloseReason::Server);
+			}
+			Poll::Pending => {}
+		}
+		polled
+	}
+
+	fn is_end_stream(&self) -> bool {
+		self.body.is_end_stream()
+	}
+
+	fn size_hint(&self) -> SizeHint {
+		self.body.size_hint()
+	}
+}
+
+/// WebSocket recording its messages and closing, see
+/// [`ConnectionTelemetry::websocket`]
+///
+/// It can be used like the [`WebSocket`], through its `recv` and `send`
+/// methods or as a stream and a sink.
+#[cfg(feature = "axum-ws")]
+#[derive(Debug)]
+pub struct TracedWebSocket {
+	/// Traced socket
+	socket: WebSocket,
+	/// Traced connection
+	connection: Connection,
+}
+
+#[cfg(feature = "axum-ws")]
+impl TracedWebSocket {
+	/// Receives the next message, returning `None` if the connection is closed
+	pub async fn recv(&mut self) -> Option<Result<Message, axum::Error>> {
+		let received = self.socket.recv().await;
+		self.record_received(received.as_ref());
+		received
+	}
+
+	/// Sends a message
+	pub async fn send(&mut self, message: Message) -> Result<(), axum::Error> {
+		self.record_sent(&message);
+		self.socket.send(message).await
+	}
+
+	/// Records a received message, or the closing of the connection
+	fn record_received(&mut self, received: Option<&Result<Message, axum::Error>>) {
+		match received {
+			Some(Ok(message)) => {
+				let (message_type, size) = message_type_and_size(message);
+				self.connection.record_message(Direction::Received, message_type, size);
+				if let Message::Close(frame) = message {
+					self.record_close(CloseReason::Client, frame.as_ref());
+				}
+			}
+			Some(Err(_)) => {
+				self.connection.close(CloseReason::Error);
+			}
+			None => {
+				self.connection.close(CloseReason::Disconnected);
+			}
+		}
+	}
+
+	/// Records a sent message, and the closing of the connection if it is a
+	/// close frame
+	fn record_sent(&mut self, message: &Message) {
+		let (message_type, size) = message_type_and_size(message);
+		self.connection.record_message(Direction::Sent, message_type, size);
+		if let Message::Close(frame) = message {
+			self.record_close(CloseReason::Server, frame.as_ref());
+		}
+	}
+
+	/// Records the closing of the connection with the code and the reason of
+	/// the close frame
+	fn record_close(&mut self, reason: CloseReason, frame: Option<&CloseFrame>) {
+		if self.connection.close_reason.is_some() {
+			return;
+		}
+		if let Some(frame) = frame {
+			self.connection.span.record("websocket.close.code", i64::from(frame.code));
+			self.connection.span.record("websocket.close.reason", frame.reason.as_str());
+		}
+		self.connection.close(reason);
+	}
+}
+
+/// Value of the `message.type` attribute and size of the payload of a
+/// WebSocket message
+#[cfg(feature = "axum-ws")]
+fn message_type_and_size(message: &Message) -> (&'static str, usize) {
+	match message {
+		Message::Text(text) => ("text", text.len()),
+		Message::Binary(data) => ("binary", data.len()),
+		Message::Ping(data) => ("ping", data.len()),
+		Message::Pong(data) => ("pong", data.len()),
+		Message::Close(frame) => {
+			("close", frame.as_ref().map_or(0, |frame| 2 + frame.reason.len()))
+		}
+	}
+}
+
+#[cfg(feature = "axum-ws")]
+impl futures_core::Stream for TracedWebSocket {
+	type Item = Result<Message, axum::Error>;
+
+	fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+		let this = self.get_mut();
+		let polled = Pin::new(&mut this.socket).poll_next(cx);
+		if let Poll::Ready(received) = &polled {
+			this.record_received(received.as_ref());
+		}
+		polled
+	}
+}
+
+#[cfg(feature = "axum-ws")]
+impl futures_sink::Sink<Message> for TracedWebSocket {
+	type Error = axum::Error;
+
+	fn poll_ready(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+		Pin::new(&mut self.get_mut().socket).poll_ready(cx)
+	}
+
+	fn start_send(self: Pin<&mut Self>, message: Message) -> Result<(), Self::Error> {
+		let this = self.get_mut();
+		this.record_sent(&message);
+		Pin::new(&mut this.socket).start_send(message)
+	}
+
+	fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+		Pin::new(&mut self.get_mut().socket).poll_flush(cx)
+	}
+
+	fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+		Pin::new(&mut self.get_mut().socket).poll_close(cx)
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	#![allow(clippy::expect_used)]
+	use std::convert::Infallible;
+
+	use axum::{
+		Router,
+		body::{Body, to_bytes},
+		response::{
+			Response,
+			sse::{Event, Sse},
+		},
+		routing::get,
+	};
+	use http::Request;
+	use opentelemetry::{
+		KeyValue,
+		trace::{SpanId, SpanKind},
+	};
+	use opentelemetry_sdk::{
+		metrics::data::{AggregatedMetrics, Metric, MetricData, ResourceMetrics},
+		trace::SpanData,
+	};
+	use tower::ServiceExt as _;
+
+	use super::{ConnectionTelemetry, ConnectionTelemetryLayer};
+	use crate::{
+		axum::ServerTraceLayer,
+		fixtures::{TestMetrics, TestSpans, attribute, flat_metrics},
+	};
+
+	/// Exporters of the spans and metrics of a test, with the subscriber set
+	/// as the default of the thread
+	struct Exporters {
+		/// Exported spans
+		spans: TestSpans,
+		/// Exported metrics
+		metrics: TestMetrics,
+		/// Guard of the default subscriber
+		_default: tracing::subscriber::DefaultGuard,
+	}
+
+	impl Exporters {
+		/// Sets up the exporters and the subscriber
+		fn new() -> Self {
+			let spans = TestSpans::new();
+			let default = spans.set_default();
+			Self { spans, metrics: TestMetrics::new(), _default: default }
+		}
+
+		/// Router tracing its connections with the meter provider
+		fn router(&self, router: Router) -> Router {
+			router
+				.layer(ConnectionTelemetryLayer::with_meter(&self.metrics.meter()))
+				.layer(ServerTraceLayer::default())
+		}
+
+		/// Span of the connection and span of its request
+		fn spans(&self, name: &str) -> (SpanData, SpanData) {
+			let spans = self.spans.finished();
+			let connection =
+				spans.iter().find(|span| span.name == name).expect("Missing connection span");
+			let request = spans
+				.iter()
+				.find(|span| span.span_kind == SpanKind::Server)
+				.expect("Missing request span");
+			(connection.clone(), request.clone())
+		}
+
+		/// Exported metrics
+		fn metrics(&self) -> Vec<ResourceMetrics> {
+			self.metrics.flushed()
+		}
+	}
+
+	/// Checks that the span of a connection is the root of a new trace linking
+	/// to the span of its request, which ended before it
+	fn assert_linked(connection: &SpanData, request: &SpanData) {
+		assert_eq!(connection.parent_span_id, SpanId::INVALID);
+		assert_ne!(connection.span_context.trace_id(), request.span_context.trace_id());
+		let links: Vec<_> = connection.links.iter().collect();
+		assert_eq!(links.len(), 1);
+		assert_eq!(links[0].span_context, request.span_context);
+		assert!(request.end_time <= connection.end_time);
+	}
+
+	/// Data of the metric of the given name
+	fn metric<'a>(metrics: &'a [ResourceMetrics], name: &str) -> &'a AggregatedMetrics {
+		flat_metrics(metrics)
+			.into_iter()
+			.find(|metric| metric.name() == name)
+			.map(Metric::data)
+			.expect("Missing metric")
+	}
+
+	#[tokio::test]
+	async fn test_sse() {
+		let exporters = Exporters::new();
+		let app = exporters.router(Router::new().route(
+			"/events",
+			get(async |telemetry: ConnectionTelemetry| -> Response {
+				let events: [Result<_, Infallible>; 2] =
+					[Ok(Event::default().data("first")), Ok(Event::default().data("second"))];
+				telemetry.sse(Sse::new(futures_util::stream::iter(events)))
+			}),
+		));
+		let request = Request::get("/events").body(Body::empty()).expect("Invalid request");
+		let response = app.oneshot(request).await.expect("Error sending request");
+		let body = to_bytes(response.into_body(), usize::MAX).await.expect("Error reading body");
+		assert_eq!(body, "data: first\n\ndata: second\n\n");
+
+		let (connection, request) = exporters.spans("sse connection");
+		assert_linked(&connection, &request);
+		assert_eq!(attribute(&connection, "connection.close_reason"), Some("server".into()));
+		let messages: Vec<_> =
+			connection.events.iter().filter(|event| event.name == "message").collect();
+		assert_eq!(messages.len(), 2);
+
+		let metrics = exporters.metrics();
+		let AggregatedMetrics::U64(MetricData::Sum(sum)) =
+			metric(&metrics, "http.server.connection.messages")
+		else {
+			panic!("Invalid messages metric");
+		};
+		let point = sum.data_points().next().expect("Missing data point");
+		assert_eq!(point.value(), 2);
+		for attribute in [
+			KeyValue::new("connection.kind", "sse"),
+			KeyValue::new("http.route", "/events"),
+			KeyValue::new("message.direction", "sent"),
+		] {
+			assert!(point.attributes().any(|kv| *kv == attribute), "{attribute:?} missing");
+		}
+		let AggregatedMetrics::I64(MetricData::Sum(active)) =
+			metric(&metrics, "http.server.active_connections")
+		else {
+			panic!("Invalid active connections metric");
+		};
+		assert_eq!(active.data_points().next().expect("Missing data point").value(), 0);
+		let AggregatedMetrics::F64(MetricData::Histogram(duration)) =
+			metric(&metrics, "http.server.connection.duration")
+		else {
+			panic!("Invalid duration metric");
+		};
+		let point = duration.data_points().next().expect("Missing data point");
+		let reason = KeyValue::new("connection.close_reason", "server");
+		assert!(point.attributes().any(|kv| *kv == reason));
+	}
+
+	#[cfg(feature = "axum-ws")]
+	#[tokio::test]
+	async fn test_websocket() {
+		use std::sync::{Arc, Mutex};
+
+		use axum::extract::ws::{Message, WebSocketUpgrade};
+		use futures_util::{SinkExt as _, StreamExt as _};
+		use tokio_tungstenite::tungstenite::{
+			self,
+			protocol::{CloseFrame, frame::coding::CloseCode},
+		};
+
+		use crate::axum::TracedWebSocket;
+
+		let exporters = Exporters::new();
+		let (closed_tx, closed_rx) = tokio::sync::oneshot::channel();
+		let closed_tx = Arc::new(Mutex::new(Some(closed_tx)));
+		let app = exporters.router(Router::new().route(
+			"/ws",
+			get(async move |upgrade: WebSocketUpgrade, telemetry: ConnectionTelemetry| {
+				let closed_tx = closed_tx.lock().expect("Poisoned lock").take();
+				upgrade.on_upgrade(async move |socket| {
+					let mut socket: TracedWebSocket = telemetry.websocket(socket);
+					while let Some(Ok(message)) = socket.recv().await {
+						if let Message::Text(text) = message {
+							socket.send(Message::Text(text)).await.expect("Error sending");
+						}
+					}
+					drop(socket);
+					closed_tx.map(|closed_tx| closed_tx.send(()));
+				})
+			}),
+		));
+		let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.expect("Error binding");
+		let address = listener.local_addr().expect("Missing address");
+		tokio::spawn(async move { axum::serve(listener, app).await });
+
+		let (mut client, _) = tokio_tungstenite::connect_async(format!("ws://{address}/ws"))
+			.await
+			.expect("Error connecting");
+		client.send(tungstenite::Message::text("hello")).await.expect("Error sending");
+		let echo = client.next().await.expect("Missing echo").expect("Error receiving");
+		assert_eq!(echo, tungstenite::Message::text("hello"));
+		let frame = CloseFrame { code: CloseCode::Normal, reason: "done".into() };
+		client.close(Some(frame)).await.expect("Error closing");
+		closed_rx.await.expect("Handler not finished");
+
+		let (connection, request) = exporters.spans("websocket connection");
+		assert_linked(&connection, &request);
+		assert_eq!(attribute(&connection, "connection.close_reason"), Some("client".into()));
+		assert_eq!(attribute(&connection, "websocket.close.code"), Some(1000_i64.into()));
+		assert_eq!(attribute(&connection, "websocket.close.reason"), Some("done".into()));
+		let messages: Vec<_> =
+			connection.events.iter().filter(|event| event.name == "message").collect();
+		assert_eq!(messages.len(), 3);
+
+		let metrics = exporters.metrics();
+		let AggregatedMetrics::U64(MetricData::Histogram(sizes)) =
+			metric(&metrics, "http.server.connection.message.size")
+		else {
+			panic!("Invalid message size metric");
+		};
+		let text = KeyValue::new("message.type", "text");
+		let mut points =
+			sizes.data_points().filter(|point| point.attributes().any(|kv| *kv == text));
+		assert!(points.all(|point| point.count() == 1 && point.sum() == 5));
+	}
+}
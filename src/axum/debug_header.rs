@@ -0,0 +1,212 @@
+// SPDX-FileCopyrightText: 2025 Famedly GmbH (info@famedly.com)
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Debug header
+//!
+//! Module containing the tower layer marking the requests with a trusted debug
+//! header from the [`DebugHeaderConfig`], so that their logs and traces are
+//! recorded at the debug level.
+use std::{
+	future::Future,
+	pin::Pin,
+	task::{Context, Poll},
+};
+
+use http::Request;
+use tower_layer::Layer;
+use tower_service::Service;
+use tracing::Instrument as _;
+
+use super::trusted_header::TrustedHeader;
+use crate::{OtelInitError, config::DebugHeaderConfig, debug_requests::TARGET};
+
+/// Layer recording the logs and traces of the requests with a trusted debug
+/// header at the level of the [`DebugHeaderConfig`], whatever the levels of
+/// the outputs
+///
+/// See the [trusted headers](super#trusted-headers), and the
+/// [layer order](super#layer-order) so that the span of the request keeps its
+/// level.
+///
+/// Example
+///
+/// ```rust
+/// use axum::routing::{Router, get};
+/// use rust_telemetry::{
+/// 	axum::{DebugHeaderLayer, ServerTraceLayer},
+/// 	config::DebugHeaderConfig,
+/// };
+///
+/// let config = DebugHeaderConfig {
+/// 	hmac_key: Some("secret".to_owned()),
+/// 	..Default::default()
+/// };
+/// let app: Router = Router::new()
+/// 	.route("/", get("Test"))
+/// 	.layer(DebugHeaderLayer::new(&config).unwrap())
+/// 	.layer(ServerTraceLayer::default());
+/// ```
+#[derive(Debug, Clone)]
+pub struct DebugHeaderLayer {
+	/// Header marking the requests for debugging
+	header: TrustedHeader,
+}
+
+impl DebugHeaderLayer {
+	/// Creates the layer, failing if the header is not a valid header name
+	pub fn new(config: &DebugHeaderConfig) -> Result<Self, OtelInitError> {
+		Ok(Self {
+			header: TrustedHeader::new(
+				&config.header,
+				config.hmac_key.as_deref(),
+				&config.allowed_sources,
+			)?,
+		})
+	}
+}
+
+impl<S> Layer<S> for DebugHeaderLayer {
+	type Service = DebugHeaderService<S>;
+
+	fn layer(&self, inner: S) -> Self::Service {
+		DebugHeaderService { inner, layer: self.clone() }
+	}
+}
+
+/// Service recording the requests with a trusted debug header at the debug
+/// level, see [`DebugHeaderLayer`]
+#[derive(Debug, Clone)]
+pub struct DebugHeaderService<S> {
+	/// Service handling the requests
+	inner: S,
+	/// Options of the layer
+	layer: DebugHeaderLayer,
+}
+
+impl<S, B> Service<Request<B>> for DebugHeaderService<S>
+where
+	S: Service<Request<B>>,
+	S::Future: Send + 'static,
+{
+	type Response = S::Response;
+	type Error = S::Error;
+	type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+	fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+		self.inner.poll_ready(cx)
+	}
+
+	fn call(&mut self, request: Request<B>) -> Self::Future {
+		if !self.layer.header.is_trusted(&request) {
+			return Box::pin(self.inner.call(request));
+		}
+		let span = tracing::info_span!(target: TARGET, "debug request");
+		let future = span.in_scope(|| self.inner.call(request));
+		Box::pin(future.instrument(span))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	#![allow(clippy::expect_used)]
+	use std::{
+		net::SocketAddr,
+		str::FromStr as _,
+		time::{Duration, SystemTime},
+	};
+
+	use axum::{Router, body::Body, extract::ConnectInfo, routing::get};
+	use http::Request;
+	use tower::ServiceExt as _;
+	use tracing_subscriber::{
+		EnvFilter, Layer as _, filter::LevelFilter, layer::SubscriberExt as _,
+	};
+
+	use super::DebugHeaderLayer;
+	use crate::{
+		axum::sign_trusted_header,
+		config::{DebugHeaderConfig, LogFormat, StdoutLogsConfig, Timestamp},
+		debug_requests::DebugScoped,
+		stdout::{self, tests::CapturedOutput},
+	};
+
+	/// Sends a request to a router logging at the debug level, returning the
+	/// stdout logs
+	async fn send(request: Request<Body>) -> String {
+		let output = CapturedOutput::default();
+		let fmt_config = StdoutLogsConfig {
+			format: LogFormat::Logfmt,
+			timestamp: Timestamp::None,
+			..Default::default()
+		};
+		let filter = EnvFilter::from_str("info").expect("Invalid filter");
+		let subscriber = tracing_subscriber::registry().with(
+			stdout::layer(&fmt_config, output.clone())
+				.with_filter(DebugScoped::new(filter, Some(LevelFilter::DEBUG))),
+		);
+		let _default = tracing::subscriber::set_default(subscriber);
+
+		let config = DebugHeaderConfig {
+			hmac_key: Some("secret".to_owned()),
+			allowed_sources: vec!["10.0.0.1".parse().expect("Invalid address")],
+			..Default::default()
+		};
+		let app = Router::new()
+			.route(
+				"/",
+				get(async || {
+					tracing::debug!("Handled");
+				}),
+			)
+			.layer(DebugHeaderLayer::new(&config).expect("Error creating layer"));
+		app.oneshot(request).await.expect("Error sending request");
+		output.contents()
+	}
+
+	/// Request with a debug header, from a source
+	fn request(value: &str, source: &str) -> Request<Body> {
+		let mut request = Request::get("/")
+			.header("x-debug-log", value)
+			.body(Body::empty())
+			.expect("Invalid request");
+		let source: SocketAddr = source.parse().expect("Invalid address");
+		request.extensions_mut().insert(ConnectInfo(source));
+		request
+	}
+
+	#[tokio::test]
+	async fn test_signed_header() {
+		let value = sign_trusted_header(
+			"secret",
+			"x-debug-log",
+			SystemTime::now() + Duration::from_secs(60),
+		);
+		let logs = send(request(&value, "192.0.2.1:1234")).await;
+		assert!(logs.contains("Handled"), "{logs}");
+	}
+
+	#[tokio::test]
+	async fn test_untrusted_header() {
+		let valid_until = SystemTime::now() + Duration::from_secs(60);
+		let expired = sign_trusted_header(
+			"secret",
+			"x-debug-log",
+			SystemTime::now() - Duration::from_secs(60),
+		);
+		let wrong_key = sign_trusted_header("other", "x-debug-log", valid_until);
+		let wrong_header = sign_trusted_header("secret", "x-server-timing", valid_until);
+		for value in ["1", expired.as_str(), wrong_key.as_str(), wrong_header.as_str()] {
+			let logs = send(request(value, "192.0.2.1:1234")).await;
+			assert!(logs.is_empty(), "{value}: {logs}");
+		}
+		let logs = send(Request::get("/").body(Body::empty()).expect("Invalid request")).await;
+		assert!(logs.is_empty(), "{logs}");
+	}
+
+	#[tokio::test]
+	async fn test_allowed_source() {
+		let logs = send(request("1", "10.0.0.1:1234")).await;
+		assert!(logs.contains("Handled"), "{logs}");
+	}
+}
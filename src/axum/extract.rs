@@ -0,0 +1,218 @@
+// SPDX-FileCopyrightText: 2025 Famedly GmbH (info@famedly.com)
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Extractors
+//!
+//! Module containing the axum extractors of the trace context and baggage of
+//! the requests traced by the [`ServerTraceLayer`](super::ServerTraceLayer).
+use std::ops::Deref;
+
+use axum::{
+	extract::FromRequestParts,
+	response::{IntoResponse, Response},
+};
+use http::{StatusCode, request::Parts};
+use opentelemetry::{baggage::BaggageExt as _, trace};
+
+/// Context of a request traced by the
+/// [`ServerTraceLayer`](super::ServerTraceLayer), added to its extensions
+#[derive(Debug, Clone)]
+pub(crate) struct RequestContext {
+	/// Context of the server span of the request
+	pub(crate) span_context: trace::SpanContext,
+	/// Context holding the baggage of the request
+	pub(crate) baggage: opentelemetry::Context,
+}
+
+/// Rejection of the extractors of this module when the request was not traced
+/// by the [`ServerTraceLayer`](super::ServerTraceLayer), responding with a
+/// 500 status as it is a mistake in the setup of the router
+#[derive(Debug, Clone, Copy, thiserror::Error)]
+#[error("Missing the ServerTraceLayer, required to extract the trace context of the request")]
+pub struct MissingServerTraceLayer;
+
+impl IntoResponse for MissingServerTraceLayer {
+	fn into_response(self) -> Response {
+		tracing::error!("{self}");
+		(StatusCode::INTERNAL_SERVER_ERROR, self.to_string()).into_response()
+	}
+}
+
+/// Context of the request from its extensions
+fn request_context(parts: &Parts) -> Result<&RequestContext, MissingServerTraceLayer> {
+	parts.extensions.get::<RequestContext>().ok_or(MissingServerTraceLayer)
+}
+
+/// Extractor of the trace id of the request, for example to add it to the
+/// error bodies
+///
+/// The id is [invalid](trace::TraceId::INVALID) if the traces are not
+/// exported.
+///
+/// Example
+///
+/// ```rust
+/// use axum::routing::{Router, get};
+/// use rust_telemetry::axum::{ServerTraceLayer, TraceId};
+///
+/// let app: Router = Router::new()
+/// 	.route(
+/// 		"/",
+/// 		get(async |TraceId(trace_id): TraceId| trace_id.to_string()),
+/// 	)
+/// 	.layer(ServerTraceLayer::default());
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TraceId(pub trace::TraceId);
+
+impl<S: Send + Sync> FromRequestParts<S> for TraceId {
+	type Rejection = MissingServerTraceLayer;
+
+	async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+		Ok(Self(request_context(parts)?.span_context.trace_id()))
+	}
+}
+
+/// Extractor of the context of the server span of the request
+///
+/// The context is [invalid](trace::SpanContext::empty_context) if the traces
+/// are not exported.
+#[derive(Debug, Clone, PartialEq)]
+pub struct SpanContext(pub trace::SpanContext);
+
+impl<S: Send + Sync> FromRequestParts<S> for SpanContext {
+	type Rejection = MissingServerTraceLayer;
+
+	async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+		Ok(Self(request_context(parts)?.span_context.clone()))
+	}
+}
+
+/// Extractor of the baggage propagated with the request in the W3C `baggage`
+/// headers, capped if the caller is not trusted, dereferencing to the
+/// [`Baggage`](opentelemetry::baggage::Baggage)
+///
+/// The baggage is read from the headers by the layer whatever the global
+/// propagator, which doesn't propagate it by default.
+///
+/// Example
+///
+/// ```rust
+/// use axum::routing::{Router, get};
+/// use rust_telemetry::axum::{Baggage, ServerTraceLayer};
+///
+/// let app: Router = Router::new()
+/// 	.route(
+/// 		"/",
+/// 		get(async |baggage: Baggage| {
+/// 			baggage
+/// 				.get("tenant")
+/// 				.map(ToString::to_string)
+/// 				.unwrap_or_default()
+/// 		}),
+/// 	)
+/// 	.layer(ServerTraceLayer::default());
+/// ```
+#[derive(Debug, Clone)]
+pub struct Baggage(opentelemetry::Context);
+
+impl Deref for Baggage {
+	type Target = opentelemetry::baggage::Baggage;
+
+	fn deref(&self) -> &Self::Target {
+		self.0.baggage()
+	}
+}
+
+impl<S: Send + Sync> FromRequestParts<S> for Baggage {
+	type Rejection = MissingServerTraceLayer;
+
+	async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+		Ok(Self(request_context(parts)?.baggage.clone()))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	#![allow(clippy::expect_used)]
+	use axum::{
+		Router,
+		body::{Body, to_bytes},
+		http::StatusCode,
+		routing::get,
+	};
+	use http::Request;
+	use opentelemetry_sdk::propagation::TraceContextPropagator;
+	use tower::ServiceExt as _;
+
+	use super::{Baggage, SpanContext, TraceId};
+	use crate::{axum::ServerTraceLayer, config::IncomingContextConfig, fixtures::TestSpans};
+
+	/// Router answering with the extracted trace context and baggage
+	fn router() -> Router {
+		Router::new()
+			.route("/trace", get(async |TraceId(trace_id): TraceId| trace_id.to_string()))
+			.route(
+				"/span",
+				get(async |SpanContext(context): SpanContext| context.span_id().to_string()),
+			)
+			.route(
+				"/baggage",
+				get(async |baggage: Baggage| {
+					baggage.get("tenant").map(ToString::to_string).unwrap_or_default()
+				}),
+			)
+	}
+
+	/// Sends a request, returning the status and the body of the response
+	async fn send(app: Router, path: &str) -> (StatusCode, String) {
+		let request = Request::get(path)
+			.header("traceparent", "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01")
+			.header("baggage", "tenant=famedly")
+			.body(Body::empty())
+			.expect("Invalid request");
+		let response = app.oneshot(request).await.expect("Error sending request");
+		let status = response.status();
+		let body = to_bytes(response.into_body(), usize::MAX).await.expect("Error reading body");
+		(status, String::from_utf8(body.to_vec()).expect("Invalid body"))
+	}
+
+	#[tokio::test]
+	async fn test_extractors() {
+		opentelemetry::global::set_text_map_propagator(TraceContextPropagator::new());
+		let spans = TestSpans::new();
+		let _default = spans.set_default();
+		let app = router().layer(ServerTraceLayer::default());
+
+		let (status, trace_id) = send(app.clone(), "/trace").await;
+		assert_eq!(status, StatusCode::OK);
+		assert_eq!(trace_id, "4bf92f3577b34da6a3ce929d0e0e4736");
+
+		let (_, span_id) = send(app.clone(), "/span").await;
+		let spans = spans.finished();
+		assert_eq!(span_id, spans[1].span_context.span_id().to_string());
+
+		let (_, tenant) = send(app, "/baggage").await;
+		assert_eq!(tenant, "famedly");
+	}
+
+	#[tokio::test]
+	async fn test_untrusted_baggage() {
+		let config = IncomingContextConfig { trusted: false, ..Default::default() };
+		let app = router().layer(ServerTraceLayer::new(&config));
+
+		let (status, tenant) = send(app, "/baggage").await;
+		assert_eq!(status, StatusCode::OK);
+		assert_eq!(tenant, "");
+	}
+
+	#[tokio::test]
+	async fn test_missing_layer() {
+		for path in ["/trace", "/span", "/baggage"] {
+			let (status, body) = send(router(), path).await;
+			assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+			assert!(body.contains("ServerTraceLayer"), "{body}");
+		}
+	}
+}
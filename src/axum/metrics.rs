@@ -0,0 +1,365 @@
+// SPDX-FileCopyrightText: 2025 Famedly GmbH (info@famedly.com)
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Server metrics
+//!
+//! Module containing the tower layer recording the metrics of the requests
+//! from the [`HttpMetricsConfig`], following the [HTTP semantic conventions](https://opentelemetry.io/docs/specs/semconv/http/http-metrics/).
+use std::{
+	collections::HashSet,
+	fmt,
+	future::Future,
+	pin::Pin,
+	sync::Arc,
+	task::{Context, Poll},
+	time::Instant,
+};
+
+use axum::extract::MatchedPath;
+use http::{Extensions, Request, Response, StatusCode, header::CONTENT_LENGTH};
+use http_body::Body;
+use opentelemetry::{
+	KeyValue,
+	metrics::{Histogram, Meter, UpDownCounter},
+};
+use opentelemetry_semantic_conventions::attribute::{
+	ERROR_TYPE, HTTP_REQUEST_METHOD, HTTP_RESPONSE_STATUS_CODE, HTTP_ROUTE, NETWORK_PROTOCOL_NAME,
+	NETWORK_PROTOCOL_VERSION,
+};
+use tower_layer::Layer;
+use tower_service::Service;
+
+use super::trace::protocol_version;
+use crate::{OtelInitError, config::HttpMetricsConfig};
+
+/// Extracts additional attributes of the metrics from the extensions of the
+/// requests
+type AttributesFn = dyn Fn(&Extensions) -> Vec<KeyValue> + Send + Sync;
+
+/// Instruments recording the metrics of the requests
+#[derive(Debug)]
+struct Instruments {
+	/// `http.server.request.duration`
+	duration: Histogram<f64>,
+	/// `http.server.active_requests`
+	active_requests: UpDownCounter<i64>,
+	/// `http.server.request.body.size`, if enabled
+	request_body_size: Option<Histogram<u64>>,
+	/// `http.server.response.body.size`, if enabled
+	response_body_size: Option<Histogram<u64>>,
+}
+
+/// Layer recording the metrics of the requests
+///
+/// It records the `http.server.request.duration` histogram, the
+/// `http.server.active_requests` counter and, if enabled, the
+/// `http.server.request.body.size` and `http.server.response.body.size`
+/// histograms. The `http.route` attribute is the [`MatchedPath`] of the
+/// request, which is only known when the layer is added with
+/// [`Router::layer`](axum::Router::layer) or
+/// [`Router::route_layer`](axum::Router::route_layer).
+///
+/// The requests switching to another protocol, like the upgrades to
+/// WebSockets, are left out of the `http.server.request.duration` histogram,
+/// as their duration is the one of the handshake and not of the connection,
+/// which the [`ConnectionTelemetryLayer`](super::ConnectionTelemetryLayer)
+/// records.
+///
+/// Example
+///
+/// ```rust
+/// use axum::routing::{Router, get};
+/// use opentelemetry::KeyValue;
+/// use rust_telemetry::{axum::HttpMetricsLayer, config::HttpMetricsConfig};
+///
+/// #[derive(Clone)]
+/// struct Tenant(String);
+///
+/// let layer = HttpMetricsLayer::new(&HttpMetricsConfig::default())
+/// 	.unwrap()
+/// 	.with_attributes(|extensions| {
+/// 		extensions
+/// 			.get::<Tenant>()
+/// 			.map(|tenant| KeyValue::new("tenant", tenant.0.clone()))
+/// 			.into_iter()
+/// 			.collect()
+/// 	});
+/// let app: Router = Router::new().route("/", get("Test")).layer(layer);
+/// ```
+#[derive(Clone)]
+pub struct HttpMetricsLayer {
+	/// Instruments shared by the services
+	instruments: Arc<Instruments>,
+	/// Paths of the requests not recorded
+	excluded_paths: Arc<HashSet<String>>,
+	/// Extracts additional attributes from the extensions of the requests
+	attributes: Option<Arc<AttributesFn>>,
+}
+
+impl fmt::Debug for HttpMetricsLayer {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_struct("HttpMetricsLayer")
+			.field("instruments", &self.instruments)
+			.field("excluded_paths", &self.excluded_paths)
+			.field("attributes", &self.attributes.is_some())
+			.finish()
+	}
+}
+
+impl HttpMetricsLayer {
+	/// Creates the layer with the [global meter
+	/// provider](super#global-meter-provider). Fails if the boundaries
+	/// of the duration buckets are not finite and increasing.
+	pub fn new(config: &HttpMetricsConfig) -> Result<Self, OtelInitError> {
+		Self::with_meter(config, &opentelemetry::global::meter(env!("CARGO_PKG_NAME")))
+	}
+
+	/// Creates the layer with the given meter
+	pub(crate) fn with_meter(
+		config: &HttpMetricsConfig,
+		meter: &Meter,
+	) -> Result<Self, OtelInitError> {
+		let buckets = &config.duration_buckets;
+		if !buckets.iter().all(|bound| bound.is_finite()) || !buckets.is_sorted_by(|a, b| a < b) {
+			return Err(OtelInitError::HttpMetricsConfigError(format!(
+				"The duration buckets must be finite and increasing: {buckets:?}"
+			)));
+		}
+		let body_size = |name: &'static str, description: &'static str| {
+			meter.u64_histogram(name).with_description(description).with_unit("By").build()
+		};
+		let instruments = Instruments {
+			duration: meter
+				.f64_histogram("http.server.request.duration")
+				.with_description("Duration of HTTP server requests.")
+				.with_unit("s")
+				.with_boundaries(buckets.clone())
+				.build(),
+			active_requests: meter
+				.i64_up_down_counter("http.server.active_requests")
+				.with_description("Number of active HTTP server requests.")
+				.with_unit("{request}")
+				.build(),
+			request_body_size: config.body_sizes.then(|| {
+				body_size("http.server.request.body.size", "Size of HTTP server request bodies.")
+			}),
+			response_body_size: config.body_sizes.then(|| {
+				body_size("http.server.response.body.size", "Size of HTTP server response bodies.")
+			}),
+		};
+		Ok(Self {
+			instruments: Arc::new(instruments),
+			excluded_paths: Arc::new(config.excluded_paths.iter().cloned().collect()),
+			attributes: None,
+		})
+	}
+
+	/// Adds the attributes extracted from the extensions of the requests to
+	/// the metrics, like the tenant set by an authentication middleware. The
+	/// middleware must run before this layer.
+	#[must_use]
+	pub fn with_attributes<F>(mut self, attributes: F) -> Self
+	where
+		F: Fn(&Extensions) -> Vec<KeyValue> + Send + Sync + 'static,
+	{
+		self.attributes = Some(Arc::new(attributes));
+		self
+	}
+}
+
+impl<S> Layer<S> for HttpMetricsLayer {
+	type Service = HttpMetricsService<S>;
+
+	fn layer(&self, inner: S) -> Self::Service {
+		HttpMetricsService { inner, layer: self.clone() }
+	}
+}
+
+/// Service recording the metrics of the requests, see [`HttpMetricsLayer`]
+#[derive(Debug, Clone)]
+pub struct HttpMetricsService<S> {
+	/// Service handling the requests
+	inner: S,
+	/// Instruments and options of the layer
+	layer: HttpMetricsLayer,
+}
+
+impl<S, B, ResBody> Service<Request<B>> for HttpMetricsService<S>
+where
+	S: Service<Request<B>, Response = Response<ResBody>>,
+	S::Future: Send + 'static,
+	ResBody: Body,
+{
+	type Response = S::Response;
+	type Error = S::Error;
+	type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+	fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+		self.inner.poll_ready(cx)
+	}
+
+	fn call(&mut self, request: Request<B>) -> Self::Future {
+		if self.layer.excluded_paths.contains(request.uri().path()) {
+			return Box::pin(self.inner.call(request));
+		}
+		let started = Instant::now();
+		let mut attributes =
+			vec![KeyValue::new(HTTP_REQUEST_METHOD, request.method().as_str().to_owned())];
+		let active = ActiveRequest::new(&self.layer.instruments.active_requests, &attributes);
+		if let Some(route) = request.extensions().get::<MatchedPath>() {
+			attributes.push(KeyValue::new(HTTP_ROUTE, route.as_str().to_owned()));
+		}
+		attributes.push(KeyValue::new(NETWORK_PROTOCOL_NAME, "http"));
+		if let Some(version) = protocol_version(request.version()) {
+			attributes.push(KeyValue::new(NETWORK_PROTOCOL_VERSION, version));
+		}
+		if let Some(extract) = &self.layer.attributes {
+			attributes.extend(extract(request.extensions()));
+		}
+		let request_body_size = request
+			.headers()
+			.get(CONTENT_LENGTH)
+			.and_then(|value| value.to_str().ok()?.parse::<u64>().ok());
+
+		let instruments = self.layer.instruments.clone();
+		let future = self.inner.call(request);
+		Box::pin(async move {
+			let _active = active;
+			let result = future.await;
+			let mut upgraded = false;
+			match &result {
+				Ok(response) => {
+					upgraded = response.status() == StatusCode::SWITCHING_PROTOCOLS;
+					let status = response.status().as_u16();
+					attributes.push(KeyValue::new(HTTP_RESPONSE_STATUS_CODE, i64::from(status)));
+					if status >= 500 {
+						attributes.push(KeyValue::new(ERROR_TYPE, status.to_string()));
+					}
+					if let (Some(histogram), Some(size)) =
+						(&instruments.request_body_size, request_body_size)
+					{
+						histogram.record(size, &attributes);
+					}
+					if let (Some(histogram), Some(size)) =
+						(&instruments.response_body_size, response.body().size_hint().exact())
+					{
+						histogram.record(size, &attributes);
+					}
+				}
+				Err(_) => attributes.push(KeyValue::new(ERROR_TYPE, "_OTHER")),
+			}
+			if !upgraded {
+				instruments.duration.record(started.elapsed().as_secs_f64(), &attributes);
+			}
+			result
+		})
+	}
+}
+
+/// Counts a request as active until dropped, including when the request is
+/// cancelled
+struct ActiveRequest {
+	/// `http.server.active_requests`
+	counter: UpDownCounter<i64>,
+	/// Attributes the request is counted with
+	attributes: Vec<KeyValue>,
+}
+
+impl ActiveRequest {
+	/// Counts a request as active
+	fn new(counter: &UpDownCounter<i64>, attributes: &[KeyValue]) -> Self {
+		counter.add(1, attributes);
+		Self { counter: counter.clone(), attributes: attributes.to_vec() }
+	}
+}
+
+impl Drop for ActiveRequest {
+	fn drop(&mut self) {
+		self.counter.add(-1, &self.attributes);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	#![allow(clippy::expect_used)]
+	use axum::{Router, body::Body, routing::get};
+	use http::{Request, StatusCode};
+	use opentelemetry::KeyValue;
+	use opentelemetry_sdk::metrics::data::{AggregatedMetrics, MetricData};
+	use tower::ServiceExt as _;
+
+	use super::HttpMetricsLayer;
+	use crate::{
+		OtelInitError,
+		config::HttpMetricsConfig,
+		fixtures::{TestMetrics, flat_metrics},
+	};
+
+	/// Tenant of a request, set by the tests
+	#[derive(Debug, Clone)]
+	struct Tenant(&'static str);
+
+	#[tokio::test]
+	async fn test_http_metrics() {
+		let metrics = TestMetrics::new();
+		let config = HttpMetricsConfig {
+			duration_buckets: vec![0.1, 1.0],
+			excluded_paths: vec!["/health".to_owned()],
+			body_sizes: false,
+		};
+		let layer = HttpMetricsLayer::with_meter(&config, &metrics.meter())
+			.expect("Error creating layer")
+			.with_attributes(|extensions| {
+				extensions
+					.get::<Tenant>()
+					.map(|tenant| KeyValue::new("tenant", tenant.0))
+					.into_iter()
+					.collect()
+			});
+		let app = Router::new()
+			.route("/users/{id}", get(async || "Found"))
+			.route("/health", get(async || "OK"))
+			.route("/ws", get(async || StatusCode::SWITCHING_PROTOCOLS))
+			.layer(layer);
+
+		let mut request = Request::get("/users/42").body(Body::empty()).expect("Invalid request");
+		request.extensions_mut().insert(Tenant("famedly"));
+		app.clone().oneshot(request).await.expect("Error sending request");
+		for path in ["/health", "/ws"] {
+			let request = Request::get(path).body(Body::empty()).expect("Invalid request");
+			app.clone().oneshot(request).await.expect("Error sending request");
+		}
+
+		let exported = metrics.flushed();
+		let metrics = flat_metrics(&exported);
+		let names: Vec<_> = metrics.iter().map(|metric| metric.name()).collect();
+		assert_eq!(names, ["http.server.request.duration", "http.server.active_requests"]);
+
+		let AggregatedMetrics::F64(MetricData::Histogram(duration)) = metrics[0].data() else {
+			panic!("Invalid duration metric");
+		};
+		let points: Vec<_> = duration.data_points().collect();
+		assert_eq!(points.len(), 1);
+		assert_eq!(points[0].count(), 1);
+		assert_eq!(points[0].bounds().collect::<Vec<_>>(), [0.1, 1.0]);
+		let attributes: Vec<_> = points[0].attributes().cloned().collect();
+		for attribute in [
+			KeyValue::new("http.request.method", "GET"),
+			KeyValue::new("http.route", "/users/{id}"),
+			KeyValue::new("http.response.status_code", 200),
+			KeyValue::new("tenant", "famedly"),
+		] {
+			assert!(attributes.contains(&attribute), "{attribute:?} missing in {attributes:?}");
+		}
+	}
+
+	#[test]
+	fn test_invalid_buckets() {
+		let config = HttpMetricsConfig { duration_buckets: vec![1.0, 0.5], ..Default::default() };
+		assert!(matches!(
+			HttpMetricsLayer::new(&config),
+			Err(OtelInitError::HttpMetricsConfigError(_))
+		));
+	}
+}
@@ -0,0 +1,288 @@
+// SPDX-FileCopyrightText: 2025 Famedly GmbH (info@famedly.com)
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Server timing
+//!
+//! Module containing the tower layer writing the durations of the selected
+//! spans of the requests to the `Server-Timing` header from the
+//! [`ServerTimingConfig`], and the subscriber layer measuring them.
+use std::{
+	future::Future,
+	pin::Pin,
+	sync::{Arc, Mutex, PoisonError},
+	task::{Context, Poll},
+	time::{Duration, Instant},
+};
+
+use http::{HeaderName, HeaderValue, Request, Response};
+use tower_layer::Layer;
+use tower_service::Service;
+use tracing::{
+	Metadata, Subscriber,
+	span::{Attributes, Id},
+};
+use tracing_subscriber::{Layer as _, filter::filter_fn, layer, registry::LookupSpan};
+
+use super::trusted_header::TrustedHeader;
+use crate::{OtelInitError, builder::BoxedLayer, config::ServerTimingConfig};
+
+/// Header of the [Server Timing](https://www.w3.org/TR/server-timing/)
+const SERVER_TIMING: HeaderName = HeaderName::from_static("server-timing");
+
+/// Durations of the spans closed so far, by span name
+type Timings = Arc<Mutex<Vec<(&'static str, Duration)>>>;
+
+tokio::task_local! {
+	/// Durations of the spans of the request handled by the task, if it has a
+	/// trusted header
+	static TIMINGS: Timings;
+}
+
+/// Builds the layer measuring the spans selected by name or target
+pub(crate) fn layer(config: &ServerTimingConfig) -> BoxedLayer {
+	let names = config.span_names.clone();
+	let targets = config.span_targets.clone();
+	let selected = move |metadata: &Metadata<'_>| {
+		metadata.is_span()
+			&& (names.iter().any(|name| name == metadata.name())
+				|| targets.iter().any(|target| within_target(metadata.target(), target)))
+	};
+	Box::new(TimingLayer.with_filter(filter_fn(selected)))
+}
+
+/// Whether a target is the given one or a module within it
+fn within_target(target: &str, parent: &str) -> bool {
+	target.strip_prefix(parent).is_some_and(|rest| rest.is_empty() || rest.starts_with("::"))
+}
+
+/// Start of a measured span, with the timings of its request
+struct Started {
+	/// When the span was created
+	at: Instant,
+	/// Timings of the request the span belongs to
+	timings: Timings,
+}
+
+/// Subscriber layer measuring the spans created while handling a request with
+/// a trusted header, from their creation to their closing
+struct TimingLayer;
+
+impl<S> tracing_subscriber::Layer<S> for TimingLayer
+where
+	S: Subscriber + for<'a> LookupSpan<'a>,
+{
+	fn on_new_span(&self, _attrs: &Attributes<'_>, id: &Id, ctx: layer::Context<'_, S>) {
+		let (Ok(timings), Some(span)) = (TIMINGS.try_with(Arc::clone), ctx.span(id)) else {
+			return;
+		};
+		span.extensions_mut().insert(Started { at: Instant::now(), timings });
+	}
+
+	fn on_close(&self, id: Id, ctx: layer::Context<'_, S>) {
+		let Some(span) = ctx.span(&id) else {
+			return;
+		};
+		if let Some(started) = span.extensions_mut().remove::<Started>() {
+			let mut timings = started.timings.lock().unwrap_or_else(PoisonError::into_inner);
+			timings.push((span.name(), started.at.elapsed()));
+		}
+	}
+}
+
+/// Value of the `Server-Timing` header, with the total duration of the spans
+/// of each name in milliseconds
+fn server_timing(timings: &Timings) -> Option<HeaderValue> {
+	let timings = timings.lock().unwrap_or_else(PoisonError::into_inner);
+	let mut totals: Vec<(&str, Duration)> = Vec::new();
+	for (name, duration) in timings.iter() {
+		match totals.iter_mut().find(|(total_name, _)| total_name == name) {
+			Some((_, total)) => *total += *duration,
+			None => totals.push((name, *duration)),
+		}
+	}
+	let metrics: Vec<String> = totals
+		.into_iter()
+		.map(|(name, total)| {
+			let name: String = name
+				.chars()
+				.map(|c| {
+					if c.is_ascii_alphanumeric() || "!#$%&'*+-.^_`|~".contains(c) { c } else { '_' }
+				})
+				.collect();
+			format!("{name};dur={:.1}", total.as_secs_f64() * 1000.0)
+		})
+		.collect();
+	if metrics.is_empty() {
+		return None;
+	}
+	HeaderValue::from_str(&metrics.join(", ")).ok()
+}
+
+/// Layer writing the durations of the selected spans of the requests with a
+/// trusted header to the `Server-Timing` header of their responses
+///
+/// The spans are selected by the [`ServerTimingConfig`] of the `http` section
+/// of the config the subscriber is built from, which measures them from their
+/// creation to their closing. Only the spans created by the task handling the
+/// request are measured, not the ones of the tasks it spawns, and the
+/// durations of the spans with the same name are added up. See the
+/// [trusted headers](super#trusted-headers).
+///
+/// Example
+///
+/// ```rust
+/// use axum::routing::{Router, get};
+/// use rust_telemetry::{axum::ServerTimingLayer, config::ServerTimingConfig};
+///
+/// let config = ServerTimingConfig {
+/// 	hmac_key: Some("secret".to_owned()),
+/// 	span_names: vec!["database query".to_owned()],
+/// 	..Default::default()
+/// };
+/// let app: Router = Router::new()
+/// 	.route("/", get("Test"))
+/// 	.layer(ServerTimingLayer::new(&config).unwrap());
+/// ```
+#[derive(Debug, Clone)]
+pub struct ServerTimingLayer {
+	/// Header requesting the timings
+	header: TrustedHeader,
+}
+
+impl ServerTimingLayer {
+	/// Creates the layer, failing if the header is not a valid header name
+	pub fn new(config: &ServerTimingConfig) -> Result<Self, OtelInitError> {
+		Ok(Self {
+			header: TrustedHeader::new(
+				&config.header,
+				config.hmac_key.as_deref(),
+				&config.allowed_sources,
+			)?,
+		})
+	}
+}
+
+impl<S> Layer<S> for ServerTimingLayer {
+	type Service = ServerTimingService<S>;
+
+	fn layer(&self, inner: S) -> Self::Service {
+		ServerTimingService { inner, layer: self.clone() }
+	}
+}
+
+/// Service writing the durations of the selected spans of the requests with a
+/// trusted header to their responses, see [`ServerTimingLayer`]
+#[derive(Debug, Clone)]
+pub struct ServerTimingService<S> {
+	/// Service handling the requests
+	inner: S,
+	/// Options of the layer
+	layer: ServerTimingLayer,
+}
+
+impl<S, B, ResBody> Service<Request<B>> for ServerTimingService<S>
+where
+	S: Service<Request<B>, Response = Response<ResBody>>,
+	S::Future: Send + 'static,
+{
+	type Response = S::Response;
+	type Error = S::Error;
+	type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+	fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+		self.inner.poll_ready(cx)
+	}
+
+	fn call(&mut self, request: Request<B>) -> Self::Future {
+		if !self.layer.header.is_trusted(&request) {
+			return Box::pin(self.inner.call(request));
+		}
+		let timings = Timings::default();
+		let future = TIMINGS.sync_scope(timings.clone(), || self.inner.call(request));
+		let future = TIMINGS.scope(timings.clone(), future);
+		Box::pin(async move {
+			let mut response = future.await?;
+			if let Some(value) = server_timing(&timings) {
+				response.headers_mut().append(SERVER_TIMING, value);
+			}
+			Ok(response)
+		})
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	#![allow(clippy::expect_used)]
+	use std::{net::SocketAddr, time::Duration};
+
+	use axum::{Router, body::Body, extract::ConnectInfo, routing::get};
+	use http::Request;
+	use tower::ServiceExt as _;
+	use tracing::Instrument as _;
+	use tracing_subscriber::layer::SubscriberExt as _;
+
+	use super::{ServerTimingLayer, layer, within_target};
+	use crate::config::ServerTimingConfig;
+
+	/// Sends a request from a source, returning the `Server-Timing` header
+	async fn send(source: &str) -> Option<String> {
+		let config = ServerTimingConfig {
+			allowed_sources: vec!["10.0.0.1".parse().expect("Invalid address")],
+			span_names: vec!["database query".to_owned()],
+			span_targets: vec!["cache".to_owned()],
+			..Default::default()
+		};
+		let subscriber = tracing_subscriber::registry().with(layer(&config));
+		let _default = tracing::subscriber::set_default(subscriber);
+
+		let app = Router::new()
+			.route(
+				"/",
+				get(async || {
+					for _ in 0..2 {
+						tokio::time::sleep(Duration::from_millis(5))
+							.instrument(tracing::info_span!("database query"))
+							.await;
+					}
+					tracing::info_span!(target: "cache::redis", "lookup").in_scope(|| {});
+					tracing::info_span!("ignored").in_scope(|| {});
+				}),
+			)
+			.layer(ServerTimingLayer::new(&config).expect("Error creating layer"));
+		let mut request = Request::get("/")
+			.header("x-server-timing", "1")
+			.body(Body::empty())
+			.expect("Invalid request");
+		let source: SocketAddr = source.parse().expect("Invalid address");
+		request.extensions_mut().insert(ConnectInfo(source));
+		let response = app.oneshot(request).await.expect("Error sending request");
+		response
+			.headers()
+			.get("server-timing")
+			.map(|value| value.to_str().expect("Invalid header").to_owned())
+	}
+
+	#[tokio::test]
+	async fn test_server_timing() {
+		let header = send("10.0.0.1:1234").await.expect("Missing header");
+		let metrics: Vec<_> = header.split(", ").collect();
+		assert_eq!(metrics.len(), 2, "{header}");
+		let duration = metrics[0].strip_prefix("database_query;dur=").expect("Missing query");
+		let duration: f64 = duration.parse().expect("Invalid duration");
+		assert!(duration >= 10.0, "{header}");
+		assert!(metrics[1].starts_with("lookup;dur="), "{header}");
+	}
+
+	#[tokio::test]
+	async fn test_untrusted_source() {
+		assert_eq!(send("192.0.2.1:1234").await, None);
+	}
+
+	#[test]
+	fn test_within_target() {
+		assert!(within_target("cache", "cache"));
+		assert!(within_target("cache::redis", "cache"));
+		assert!(!within_target("cachet", "cache"));
+	}
+}
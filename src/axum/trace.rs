@@ -0,0 +1,436 @@
+// SPDX-FileCopyrightText: 2025 Famedly GmbH (info@famedly.com)
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Server spans
+//!
+//! Module containing the tower layer opening a server span per request, as a
+//! child of the context propagated by the caller.
+use std::{
+	future::Future,
+	net::SocketAddr,
+	pin::Pin,
+	sync::Arc,
+	task::{Context, Poll},
+};
+
+use axum::extract::{ConnectInfo, MatchedPath};
+use http::{HeaderMap, HeaderName, HeaderValue, Request, Response, Version, header::USER_AGENT};
+use opentelemetry::{propagation::TextMapPropagator as _, trace::TraceContextExt as _};
+use opentelemetry_http::HeaderExtractor;
+use opentelemetry_sdk::propagation::BaggagePropagator;
+use tower_layer::Layer;
+use tower_service::Service;
+use tracing::{Instrument as _, Span, field::Empty};
+use tracing_opentelemetry::OpenTelemetrySpanExt as _;
+
+use super::extract::RequestContext;
+use crate::{
+	OtelInitError,
+	config::{ClientAddressMode, HttpCaptureConfig, IncomingContextConfig},
+	http_capture::{HeaderCapture, client_address},
+};
+
+/// Header of the [W3C baggage](https://www.w3.org/TR/baggage/)
+const BAGGAGE: HeaderName = HeaderName::from_static("baggage");
+
+/// Layer opening a server span per request
+///
+/// The context of the caller is extracted from the headers with the global
+/// text map propagator. If the [`IncomingContextConfig`] doesn't trust it,
+/// the span starts a new trace linking to it instead, and the `baggage` header
+/// is capped before the request is handled. The spans are named `{method}
+/// {route}` after the [`MatchedPath`] of the request, which is only known when
+/// the layer is added with [`Router::layer`](axum::Router::layer) or
+/// [`Router::route_layer`](axum::Router::route_layer), and carry the
+/// attributes of the [HTTP semantic conventions](https://opentelemetry.io/docs/specs/semconv/http/http-spans/#http-server).
+/// Their status is an error for 5xx responses.
+///
+/// Example
+///
+/// ```rust
+/// use axum::routing::{Router, get};
+/// use rust_telemetry::axum::ServerTraceLayer;
+///
+/// let app: Router = Router::new()
+/// 	.route("/users/{id}", get("Test"))
+/// 	.layer(ServerTraceLayer::default());
+/// ```
+#[derive(Debug, Clone, Default)]
+pub struct ServerTraceLayer {
+	/// Trust in the context of the callers
+	incoming_context: Arc<IncomingContextConfig>,
+	/// Headers recorded in the spans
+	capture: Arc<HeaderCapture>,
+	/// How the address of the client is recorded
+	client_address: Arc<ClientAddressMode>,
+}
+
+impl ServerTraceLayer {
+	/// Creates the layer with the given trust in the context of the callers.
+	/// The default layer trusts all of them.
+	#[must_use]
+	pub fn new(incoming_context: &IncomingContextConfig) -> Self {
+		Self { incoming_context: Arc::new(incoming_context.clone()), ..Default::default() }
+	}
+
+	/// Records the allowed headers and the client address in the spans,
+	/// failing if a header is invalid or holds credentials. The
+	/// `user_agent.original` attribute is only recorded if the `user-agent`
+	/// header is allowed. The client address
+	/// is taken from the [`ConnectInfo`] of the request, which requires
+	/// serving the router with
+	/// [`into_make_service_with_connect_info`](axum::Router::into_make_service_with_connect_info).
+	pub fn with_capture(mut self, capture: &HttpCaptureConfig) -> Result<Self, OtelInitError> {
+		self.capture = Arc::new(HeaderCapture::new(capture)?);
+		self.client_address = Arc::new(capture.client_address.clone());
+		Ok(self)
+	}
+}
+
+impl<S> Layer<S> for ServerTraceLayer {
+	type Service = ServerTraceService<S>;
+
+	fn layer(&self, inner: S) -> Self::Service {
+		ServerTraceService { inner, layer: self.clone() }
+	}
+}
+
+/// Service opening a server span per request, see [`ServerTraceLayer`]
+#[derive(Debug, Clone)]
+pub struct ServerTraceService<S> {
+	/// Service handling the requests
+	inner: S,
+	/// Options of the layer
+	layer: ServerTraceLayer,
+}
+
+impl<S, B, ResBody> Service<Request<B>> for ServerTraceService<S>
+where
+	S: Service<Request<B>, Response = Response<ResBody>>,
+	S::Future: Send + 'static,
+{
+	type Response = S::Response;
+	type Error = S::Error;
+	type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+	fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+		self.inner.poll_ready(cx)
+	}
+
+	fn call(&mut self, mut request: Request<B>) -> Self::Future {
+		let route = request.extensions().get::<MatchedPath>().map(MatchedPath::as_str);
+		let trusted = route
+			.and_then(|route| self.layer.incoming_context.routes.get(route))
+			.copied()
+			.unwrap_or(self.layer.incoming_context.trusted);
+		if !trusted {
+			let max_size = self.layer.incoming_context.untrusted_baggage_max_size;
+			cap_baggage(request.headers_mut(), max_size);
+		}
+		let span = server_span(&mut request, trusted);
+		self.layer.capture.record_request(&span, request.headers());
+		if self.layer.capture.captures_request(&USER_AGENT) {
+			let user_agent =
+				request.headers().get(USER_AGENT).and_then(|value| value.to_str().ok());
+			if let Some(user_agent) = user_agent {
+				span.record("user_agent.original", user_agent);
+			}
+		}
+		let source = request.extensions().get::<ConnectInfo<SocketAddr>>();
+		if let Some(address) = source
+			.and_then(|ConnectInfo(source)| client_address(&self.layer.client_address, source.ip()))
+		{
+			span.record("client.address", address);
+		}
+		let capture = self.layer.capture.clone();
+		let future = span.in_scope(|| self.inner.call(request));
+		Box::pin(
+			async move {
+				let response = future.await?;
+				let span = Span::current();
+				capture.record_response(&span, response.headers());
+				record_status(&span, response.status().as_u16());
+				Ok(response)
+			}
+			.instrument(span),
+		)
+	}
+}
+
+/// Opens the span of a request, as a child of the propagated context if
+/// trusted, or as a new root linking to it, and adds its [`RequestContext`]
+/// with the baggage of the headers, capped if untrusted, to the request for
+/// the extractors
+pub(crate) fn server_span<B>(request: &mut Request<B>, trusted: bool) -> Span {
+	let method = request.method().as_str();
+	let route = request.extensions().get::<MatchedPath>().map(MatchedPath::as_str);
+	let name = route.map_or_else(|| method.to_owned(), |route| format!("{method} {route}"));
+	let span = tracing::info_span!(
+		"HTTP request",
+		otel.name = name,
+		otel.kind = "server",
+		otel.status_code = Empty,
+		http.request.method = method,
+		http.route = route,
+		http.response.status_code = Empty,
+		url.path = request.uri().path(),
+		network.protocol.version = protocol_version(request.version()),
+		user_agent.original = Empty,
+		client.address = Empty,
+		error.type = Empty,
+	);
+	let caller = opentelemetry::global::get_text_map_propagator(|propagator| {
+		propagator.extract(&HeaderExtractor(request.headers()))
+	});
+	let parent = if trusted {
+		caller
+	} else {
+		let caller_span = caller.span().span_context().clone();
+		if caller_span.is_valid() {
+			span.add_link(caller_span);
+		}
+		opentelemetry::Context::new()
+	};
+	// Without an OpenTelemetry layer, there is no context to set
+	let _ = span.set_parent(parent);
+	let span_context = span.context().span().span_context().clone();
+	let baggage = BaggagePropagator::new().extract(&HeaderExtractor(request.headers()));
+	request.extensions_mut().insert(RequestContext { span_context, baggage });
+	span
+}
+
+/// Caps the `baggage` headers of an untrusted request to the given size,
+/// dropping the list members from the first one that doesn't fit
+fn cap_baggage(headers: &mut HeaderMap, max_size: usize) {
+	let members: Vec<String> = headers
+		.get_all(BAGGAGE)
+		.iter()
+		.filter_map(|value| value.to_str().ok())
+		.flat_map(|value| value.split(','))
+		.map(str::trim)
+		.filter(|member| !member.is_empty())
+		.map(ToOwned::to_owned)
+		.collect();
+	headers.remove(BAGGAGE);
+
+	let mut kept = String::new();
+	for member in members {
+		let separator = usize::from(!kept.is_empty());
+		if kept.len() + separator + member.len() > max_size {
+			break;
+		}
+		if separator > 0 {
+			kept.push(',');
+		}
+		kept.push_str(&member);
+	}
+	if let Ok(value) = HeaderValue::from_str(&kept)
+		&& !kept.is_empty()
+	{
+		headers.insert(BAGGAGE, value);
+	}
+}
+
+/// Records the response status of a request, setting the span status to an
+/// error for server errors
+pub(crate) fn record_status(span: &Span, status: u16) {
+	span.record("http.response.status_code", i64::from(status));
+	if status >= 500 {
+		span.record("otel.status_code", "ERROR");
+		span.record("error.type", status.to_string());
+	}
+}
+
+/// Value of the `network.protocol.version` attribute
+pub(crate) const fn protocol_version(version: Version) -> Option<&'static str> {
+	match version {
+		Version::HTTP_09 => Some("0.9"),
+		Version::HTTP_10 => Some("1.0"),
+		Version::HTTP_11 => Some("1.1"),
+		Version::HTTP_2 => Some("2"),
+		Version::HTTP_3 => Some("3"),
+		_ => None,
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	#![allow(clippy::expect_used)]
+	use std::{collections::HashMap, net::SocketAddr};
+
+	use axum::{
+		Router,
+		body::{Body, to_bytes},
+		extract::ConnectInfo,
+		http::{HeaderMap, StatusCode},
+		routing::get,
+	};
+	use http::Request;
+	use opentelemetry::{
+		StringValue, Value,
+		trace::{SpanId, SpanKind, Status},
+	};
+	use opentelemetry_sdk::{propagation::TraceContextPropagator, trace::SpanData};
+	use tower::ServiceExt as _;
+
+	use super::ServerTraceLayer;
+	use crate::{
+		config::{ClientAddressMode, HttpCaptureConfig, IncomingContextConfig},
+		fixtures::{TestSpans, attribute},
+	};
+
+	/// Context propagated by the callers in the tests
+	const TRACEPARENT: &str = "00-4bf92f3577b34da6a3ce929d0e0e4736-00f067aa0ba902b7-01";
+
+	/// Sends the requests to a router traced by the layer, returning the
+	/// exported spans and the response bodies
+	async fn traced_requests(
+		layer: ServerTraceLayer,
+		requests: Vec<Request<Body>>,
+	) -> (Vec<SpanData>, Vec<String>) {
+		opentelemetry::global::set_text_map_propagator(TraceContextPropagator::new());
+		let spans = TestSpans::new();
+		let _default = spans.set_default();
+
+		let app = Router::new()
+			.route("/users/{id}", get(async || "Found"))
+			.route("/fail", get(async || StatusCode::INTERNAL_SERVER_ERROR))
+			.route(
+				"/public",
+				get(async |headers: HeaderMap| {
+					headers
+						.get("baggage")
+						.and_then(|baggage| baggage.to_str().ok())
+						.unwrap_or_default()
+						.to_owned()
+				}),
+			)
+			.layer(layer);
+		let mut bodies = Vec::new();
+		for request in requests {
+			let response = app.clone().oneshot(request).await.expect("Error sending request");
+			let body =
+				to_bytes(response.into_body(), usize::MAX).await.expect("Error reading body");
+			bodies.push(String::from_utf8(body.to_vec()).expect("Invalid body"));
+		}
+		(spans.finished(), bodies)
+	}
+
+	#[tokio::test]
+	async fn test_server_span() {
+		let request = Request::get("/users/42")
+			.header("traceparent", TRACEPARENT)
+			.header("user-agent", "test")
+			.body(Body::empty())
+			.expect("Invalid request");
+		let (spans, _) = traced_requests(ServerTraceLayer::default(), vec![request]).await;
+
+		assert_eq!(spans.len(), 1);
+		let span = &spans[0];
+		assert_eq!(span.name, "GET /users/{id}");
+		assert_eq!(span.span_kind, SpanKind::Server);
+		assert_eq!(span.span_context.trace_id().to_string(), "4bf92f3577b34da6a3ce929d0e0e4736");
+		assert_eq!(span.parent_span_id.to_string(), "00f067aa0ba902b7");
+		assert_eq!(span.status, Status::Unset);
+		assert_eq!(attribute(span, "http.request.method"), Some(Value::from("GET")));
+		assert_eq!(attribute(span, "http.route"), Some(Value::from("/users/{id}")));
+		assert_eq!(attribute(span, "url.path"), Some(Value::from("/users/42")));
+		assert_eq!(attribute(span, "user_agent.original"), None);
+		assert_eq!(attribute(span, "http.response.status_code"), Some(Value::I64(200)));
+	}
+
+	#[tokio::test]
+	async fn test_server_error_status() {
+		let request = Request::get("/fail").body(Body::empty()).expect("Invalid request");
+		let (spans, _) = traced_requests(ServerTraceLayer::default(), vec![request]).await;
+
+		assert_eq!(spans.len(), 1);
+		assert!(matches!(spans[0].status, Status::Error { .. }));
+		assert_eq!(attribute(&spans[0], "http.response.status_code"), Some(Value::I64(500)));
+		assert_eq!(attribute(&spans[0], "error.type"), Some(Value::from("500")));
+	}
+
+	#[tokio::test]
+	async fn test_untrusted_context() {
+		let layer = ServerTraceLayer::new(&IncomingContextConfig {
+			routes: HashMap::from([("/public".to_owned(), false)]),
+			untrusted_baggage_max_size: 9,
+			..Default::default()
+		});
+		let request = |path: &str| {
+			Request::get(path)
+				.header("traceparent", TRACEPARENT)
+				.header("baggage", "a=1, bb=22")
+				.header("baggage", "ccc=333")
+				.body(Body::empty())
+				.expect("Invalid request")
+		};
+		let (spans, bodies) =
+			traced_requests(layer, vec![request("/public"), request("/users/42")]).await;
+
+		assert_eq!(spans.len(), 2);
+		let untrusted = &spans[0];
+		assert_ne!(untrusted.span_context.trace_id().to_string(), &TRACEPARENT[3..35]);
+		assert_eq!(untrusted.parent_span_id, SpanId::INVALID);
+		let links: Vec<_> = untrusted.links.iter().collect();
+		assert_eq!(links.len(), 1);
+		assert_eq!(links[0].span_context.trace_id().to_string(), &TRACEPARENT[3..35]);
+		assert_eq!(links[0].span_context.span_id().to_string(), &TRACEPARENT[36..52]);
+		assert_eq!(bodies[0], "a=1,bb=22");
+
+		let trusted = &spans[1];
+		assert_eq!(trusted.span_context.trace_id().to_string(), &TRACEPARENT[3..35]);
+		assert!(trusted.links.is_empty());
+	}
+
+	#[tokio::test]
+	async fn test_capture() {
+		let layer = ServerTraceLayer::default()
+			.with_capture(&HttpCaptureConfig {
+				request_headers: vec!["X-Matrix-Client".to_owned(), "user-agent".to_owned()],
+				response_headers: vec!["content-type".to_owned()],
+				client_address: ClientAddressMode::Truncate,
+			})
+			.expect("Error creating layer");
+		let mut request = Request::get("/users/42")
+			.header("x-matrix-client", "element")
+			.header("x-matrix-client", "web")
+			.header("user-agent", "test")
+			.header("authorization", "Bearer secret")
+			.body(Body::empty())
+			.expect("Invalid request");
+		let source: SocketAddr = "192.0.2.42:1234".parse().expect("Invalid address");
+		request.extensions_mut().insert(ConnectInfo(source));
+		let (spans, _) = traced_requests(layer, vec![request]).await;
+
+		assert_eq!(spans.len(), 1);
+		let span = &spans[0];
+		assert_eq!(
+			attribute(span, "http.request.header.x-matrix-client"),
+			Some(Value::Array(vec![StringValue::from("element"), StringValue::from("web")].into()))
+		);
+		assert_eq!(
+			attribute(span, "http.response.header.content-type"),
+			Some(Value::Array(vec![StringValue::from("text/plain; charset=utf-8")].into()))
+		);
+		assert_eq!(attribute(span, "user_agent.original"), Some(Value::from("test")));
+		assert_eq!(attribute(span, "client.address"), Some(Value::from("192.0.2.0")));
+		assert!(!span.attributes.iter().any(|kv| kv.key.as_str().contains("authorization")));
+	}
+
+	#[tokio::test]
+	async fn test_untrusted_baggage_dropped() {
+		let layer =
+			ServerTraceLayer::new(&IncomingContextConfig { trusted: false, ..Default::default() });
+		let request = Request::get("/public")
+			.header("baggage", "a=1")
+			.body(Body::empty())
+			.expect("Invalid request");
+		let (spans, bodies) = traced_requests(layer, vec![request]).await;
+
+		assert_eq!(spans.len(), 1);
+		assert!(spans[0].links.is_empty());
+		assert_eq!(bodies[0], "");
+	}
+}
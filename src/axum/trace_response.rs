@@ -0,0 +1,296 @@
+// SPDX-FileCopyrightText: 2025 Famedly GmbH (info@famedly.com)
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Trace response headers
+//!
+//! Module containing the tower layer writing the trace id and the request id
+//! of the requests to their responses from the [`TraceResponseConfig`].
+use std::{
+	future::Future,
+	pin::Pin,
+	str::FromStr as _,
+	task::{Context, Poll},
+};
+
+use http::{HeaderName, HeaderValue, Request, Response};
+use opentelemetry::trace::TraceContextExt as _;
+use opentelemetry_sdk::trace::{IdGenerator as _, RandomIdGenerator};
+use tower_layer::Layer;
+use tower_service::Service;
+use tracing::Span;
+use tracing_opentelemetry::OpenTelemetrySpanExt as _;
+
+use crate::{OtelInitError, config::TraceResponseConfig, redaction::redact_span_value};
+
+/// Header of [Trace Context Level 2](https://www.w3.org/TR/trace-context-2/#traceresponse-header)
+/// holding the context of the span of the request
+const TRACERESPONSE: HeaderName = HeaderName::from_static("traceresponse");
+
+/// Maximum length of the request ids accepted from the clients
+const MAX_REQUEST_ID_LEN: usize = 128;
+
+/// Layer writing the trace id and the request id of the requests to their
+/// responses
+///
+/// The `traceresponse` header holds the context of the span of the request,
+/// see the [layer order](super#layer-order). The incoming request id is
+/// recorded in the current span as the `http.request.header.<name>` attribute.
+/// If the request doesn't have one, or if it isn't made of 1 to 128 visible
+/// ASCII characters, a random id is generated and set in the request for the
+/// handlers.
+///
+/// Example
+///
+/// ```rust
+/// use axum::routing::{Router, get};
+/// use rust_telemetry::{
+/// 	axum::{ServerTraceLayer, TraceResponseLayer},
+/// 	config::TraceResponseConfig,
+/// };
+///
+/// let app: Router = Router::new()
+/// 	.route("/", get("Test"))
+/// 	.layer(
+/// 		TraceResponseLayer::new(&TraceResponseConfig::default()).unwrap(),
+/// 	)
+/// 	.layer(ServerTraceLayer::default());
+/// ```
+#[derive(Debug, Clone)]
+pub struct TraceResponseLayer {
+	/// Writes the `traceresponse` header
+	traceresponse: bool,
+	/// Header holding the id of the request
+	request_id_header: Option<HeaderName>,
+}
+
+impl TraceResponseLayer {
+	/// Creates the layer, failing if the request id header is not a valid
+	/// header name
+	pub fn new(config: &TraceResponseConfig) -> Result<Self, OtelInitError> {
+		Ok(Self {
+			traceresponse: config.traceresponse,
+			request_id_header: config
+				.request_id_header
+				.as_deref()
+				.map(HeaderName::from_str)
+				.transpose()?,
+		})
+	}
+}
+
+impl<S> Layer<S> for TraceResponseLayer {
+	type Service = TraceResponseService<S>;
+
+	fn layer(&self, inner: S) -> Self::Service {
+		TraceResponseService { inner, layer: self.clone() }
+	}
+}
+
+/// Service writing the trace id and the request id of the requests to their
+/// responses, see [`TraceResponseLayer`]
+#[derive(Debug, Clone)]
+pub struct TraceResponseService<S> {
+	/// Service handling the requests
+	inner: S,
+	/// Options of the layer
+	layer: TraceResponseLayer,
+}
+
+impl<S, B, ResBody> Service<Request<B>> for TraceResponseService<S>
+where
+	S: Service<Request<B>, Response = Response<ResBody>>,
+	S::Future: Send + 'static,
+{
+	type Response = S::Response;
+	type Error = S::Error;
+	type Future = Pin<Box<dyn Future<Output = Result<Self::Response, Self::Error>> + Send>>;
+
+	fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+		self.inner.poll_ready(cx)
+	}
+
+	fn call(&mut self, mut request: Request<B>) -> Self::Future {
+		let span = Span::current();
+		let request_id = self.layer.request_id_header.clone().map(|header| {
+			let incoming = request.headers().get(&header).filter(|id| is_valid_request_id(id));
+			let id = incoming.cloned().unwrap_or_else(|| {
+				let id = generate_request_id();
+				request.headers_mut().insert(header.clone(), id.clone());
+				id
+			});
+			if let Ok(value) = id.to_str() {
+				let key = format!("http.request.header.{header}");
+				let value = redact_span_value(&span, &key, value.to_owned());
+				span.set_attribute(key, value);
+			}
+			(header, id)
+		});
+		let traceresponse = self.layer.traceresponse.then(|| traceresponse(&span)).flatten();
+
+		let future = self.inner.call(request);
+		Box::pin(async move {
+			let mut response = future.await?;
+			let headers = response.headers_mut();
+			if let Some(traceresponse) = traceresponse {
+				headers.insert(TRACERESPONSE, traceresponse);
+			}
+			if let Some((header, id)) = request_id {
+				headers.entry(header).or_insert(id);
+			}
+			Ok(response)
+		})
+	}
+}
+
+/// Whether a request id from a client is kept, that is made of 1 to
+/// [`MAX_REQUEST_ID_LEN`] visible ASCII characters
+fn is_valid_request_id(id: &HeaderValue) -> bool {
+	let id = id.as_bytes();
+	!id.is_empty() && id.len() <= MAX_REQUEST_ID_LEN && id.iter().all(u8::is_ascii_graphic)
+}
+
+/// Generates a random request id of 32 hexadecimal digits
+fn generate_request_id() -> HeaderValue {
+	let id = RandomIdGenerator::default().new_trace_id().to_string();
+	HeaderValue::from_str(&id).unwrap_or_else(|_| HeaderValue::from_static("unknown"))
+}
+
+/// Value of the `traceresponse` header for the context of a span, if it is
+/// exported over OpenTelemetry
+fn traceresponse(span: &Span) -> Option<HeaderValue> {
+	let context = span.context();
+	let span_context = context.span().span_context().clone();
+	if !span_context.is_valid() {
+		return None;
+	}
+	let value = format!(
+		"00-{}-{}-{:02x}",
+		span_context.trace_id(),
+		span_context.span_id(),
+		span_context.trace_flags().to_u8()
+	);
+	HeaderValue::from_str(&value).ok()
+}
+
+#[cfg(test)]
+mod tests {
+	#![allow(clippy::expect_used)]
+	use axum::{
+		Router,
+		body::{Body, to_bytes},
+		http::HeaderMap,
+		routing::get,
+	};
+	use http::{Request, Response};
+	use opentelemetry::Value;
+	use opentelemetry_sdk::trace::SpanData;
+	use tower::ServiceExt as _;
+
+	use super::TraceResponseLayer;
+	use crate::{
+		axum::ServerTraceLayer,
+		config::TraceResponseConfig,
+		fixtures::{TestSpans, attribute},
+	};
+
+	/// Sends a request to a router echoing its request id, returning the
+	/// response, its body and the exported span
+	async fn send(request: Request<Body>) -> (Response<Body>, String, SpanData) {
+		let spans = TestSpans::new();
+		let _default = spans.set_default();
+
+		let layer =
+			TraceResponseLayer::new(&TraceResponseConfig::default()).expect("Error creating layer");
+		let app = Router::new()
+			.route(
+				"/",
+				get(async |headers: HeaderMap| {
+					headers
+						.get("x-request-id")
+						.and_then(|id| id.to_str().ok())
+						.unwrap_or_default()
+						.to_owned()
+				}),
+			)
+			.layer(layer)
+			.layer(ServerTraceLayer::default());
+		let response = app.oneshot(request).await.expect("Error sending request");
+		let (parts, body) = response.into_parts();
+		let body = to_bytes(body, usize::MAX).await.expect("Error reading body");
+		let body = String::from_utf8(body.to_vec()).expect("Invalid body");
+		let mut spans = spans.finished();
+		assert_eq!(spans.len(), 1);
+		(Response::from_parts(parts, Body::empty()), body, spans.remove(0))
+	}
+
+	/// Value of a header of a response
+	fn header<'a>(response: &'a Response<Body>, name: &str) -> Option<&'a str> {
+		response.headers().get(name).and_then(|value| value.to_str().ok())
+	}
+
+	#[tokio::test]
+	async fn test_incoming_request_id() {
+		let request = Request::get("/")
+			.header("x-request-id", "abc-123")
+			.body(Body::empty())
+			.expect("Invalid request");
+		let (response, body, span) = send(request).await;
+
+		assert_eq!(header(&response, "x-request-id"), Some("abc-123"));
+		assert_eq!(body, "abc-123");
+		assert_eq!(
+			header(&response, "traceresponse"),
+			Some(
+				format!("00-{}-{}-01", span.span_context.trace_id(), span.span_context.span_id())
+					.as_str()
+			)
+		);
+		assert_eq!(
+			attribute(&span, "http.request.header.x-request-id"),
+			Some(Value::from("abc-123"))
+		);
+	}
+
+	#[tokio::test]
+	async fn test_generated_request_id() {
+		let request = Request::get("/").body(Body::empty()).expect("Invalid request");
+		let (response, body, _span) = send(request).await;
+
+		let id = header(&response, "x-request-id").expect("Missing request id");
+		assert_eq!(id.len(), 32);
+		assert!(id.chars().all(|c| c.is_ascii_hexdigit()));
+		assert_eq!(body, id);
+	}
+
+	#[tokio::test]
+	async fn test_invalid_request_id() {
+		for id in ["a".repeat(129).as_str(), "abc 123", ""] {
+			let request = Request::get("/")
+				.header("x-request-id", id)
+				.body(Body::empty())
+				.expect("Invalid request");
+			let (response, body, span) = send(request).await;
+
+			let generated = header(&response, "x-request-id").expect("Missing request id");
+			assert_eq!(generated.len(), 32, "{id}");
+			assert_eq!(body, generated);
+			assert_eq!(
+				attribute(&span, "http.request.header.x-request-id"),
+				Some(Value::from(generated.to_owned()))
+			);
+		}
+	}
+
+	#[test]
+	fn test_invalid_header() {
+		let config = TraceResponseConfig {
+			request_id_header: Some("invalid header".to_owned()),
+			..Default::default()
+		};
+		assert!(matches!(
+			TraceResponseLayer::new(&config),
+			Err(crate::OtelInitError::InvalidHeaderName(_))
+		));
+	}
+}
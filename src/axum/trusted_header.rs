@@ -0,0 +1,137 @@
+// SPDX-FileCopyrightText: 2025 Famedly GmbH (info@famedly.com)
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Trusted headers
+//!
+//! Module checking the headers enabling a diagnostic for a request, trusted if
+//! their value is signed with a key or if the request comes from an allowed
+//! source.
+use std::{
+	net::{IpAddr, SocketAddr},
+	str::FromStr as _,
+	sync::Arc,
+	time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use axum::extract::ConnectInfo;
+use http::{HeaderName, HeaderValue, Request};
+
+use crate::{OtelInitError, redaction::hmac_hex};
+
+/// Number of bytes of the HMAC in the signatures, all of them
+const SIGNATURE_LEN: usize = 32;
+
+/// Signs a value of a trusted header valid until the expiry, with the
+/// `hmac_key` of its config, like the
+/// [`DebugHeaderConfig`](crate::config::DebugHeaderConfig)
+///
+/// The signature covers the name of the header, so that a value signed for a
+/// header is not trusted in another one sharing the same key.
+#[must_use]
+pub fn sign_trusted_header(key: &str, header: &str, expires: SystemTime) -> String {
+	let expiry = expires.duration_since(UNIX_EPOCH).unwrap_or_default().as_secs().to_string();
+	let signature = signature(key, &header.to_ascii_lowercase(), &expiry);
+	format!("{expiry}.{signature}")
+}
+
+/// Hexadecimal HMAC of the header name and the expiry of a value of a trusted
+/// header
+fn signature(key: &str, header: &str, expiry: &str) -> String {
+	hmac_hex(key, &format!("{header}.{expiry}"), SIGNATURE_LEN)
+}
+
+/// Whether a value of a trusted header is signed for the header with the key
+/// and not expired
+fn verify_signature(key: &str, header: &HeaderName, value: &HeaderValue, now: SystemTime) -> bool {
+	let Some((expiry, signature)) = value.to_str().ok().and_then(|value| value.split_once('.'))
+	else {
+		return false;
+	};
+	let not_expired = expiry
+		.parse()
+		.ok()
+		.and_then(|secs| UNIX_EPOCH.checked_add(Duration::from_secs(secs)))
+		.is_some_and(|expires| expires > now);
+	not_expired
+		&& constant_time_eq(
+			signature.to_ascii_lowercase().as_bytes(),
+			self::signature(key, header.as_str(), expiry).as_bytes(),
+		)
+}
+
+/// Whether two byte strings are equal, in a time not depending on their first
+/// difference so that the signatures can't be guessed byte by byte
+fn constant_time_eq(a: &[u8], b: &[u8]) -> bool {
+	a.len() == b.len() && a.iter().zip(b).fold(0, |diff, (a, b)| diff | (a ^ b)) == 0
+}
+
+/// Header trusted if its value is signed by [`sign_trusted_header`], or if the
+/// request comes from one of the allowed sources. Without any of them, the
+/// header is never trusted.
+#[derive(Debug, Clone)]
+pub(crate) struct TrustedHeader {
+	/// Name of the header
+	header: HeaderName,
+	/// Key of the HMAC signing the values of the header
+	hmac_key: Option<Arc<str>>,
+	/// Addresses of the callers trusted to send the header with any value
+	allowed_sources: Arc<[IpAddr]>,
+}
+
+impl TrustedHeader {
+	/// Parses the name of the header, failing if it is not a valid header name
+	pub(crate) fn new(
+		header: &str,
+		hmac_key: Option<&str>,
+		allowed_sources: &[IpAddr],
+	) -> Result<Self, OtelInitError> {
+		Ok(Self {
+			header: HeaderName::from_str(header)?,
+			hmac_key: hmac_key.map(Arc::from),
+			allowed_sources: allowed_sources.into(),
+		})
+	}
+
+	/// Whether the request has the header, and it is trusted
+	pub(crate) fn is_trusted<B>(&self, request: &Request<B>) -> bool {
+		let Some(value) = request.headers().get(&self.header) else {
+			return false;
+		};
+		let from_allowed_source = request
+			.extensions()
+			.get::<ConnectInfo<SocketAddr>>()
+			.is_some_and(|ConnectInfo(source)| self.allowed_sources.contains(&source.ip()));
+		from_allowed_source
+			|| self
+				.hmac_key
+				.as_deref()
+				.is_some_and(|key| verify_signature(key, &self.header, value, SystemTime::now()))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	#![allow(clippy::expect_used)]
+	use std::time::{Duration, SystemTime};
+
+	use http::{HeaderName, HeaderValue};
+
+	use super::{sign_trusted_header, verify_signature};
+
+	#[test]
+	fn test_verify_signature() {
+		let now = SystemTime::now();
+		let header = HeaderName::from_static("x-debug-log");
+		let value = sign_trusted_header("secret", "X-Debug-Log", now + Duration::from_secs(1));
+		let value = HeaderValue::from_str(&value).expect("Invalid value");
+		assert!(verify_signature("secret", &header, &value, now));
+		assert!(!verify_signature("secret", &header, &value, now + Duration::from_secs(1)));
+		assert!(!verify_signature("other", &header, &value, now));
+		let other_header = HeaderName::from_static("x-other");
+		assert!(!verify_signature("secret", &other_header, &value, now));
+		let truncated = HeaderValue::from_str(&value.to_str().expect("Invalid value")[..20])
+			.expect("Invalid value");
+		assert!(!verify_signature("secret", &header, &truncated, now));
+	}
+}
@@ -0,0 +1,710 @@
+// SPDX-FileCopyrightText: 2025 Famedly GmbH (info@famedly.com)
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Telemetry builder
+//!
+//! Module containing the [`TelemetryBuilder`], which builds the subscriber and
+//! the OpenTelemetry providers from the [`OtelConfig`] and allows adding custom
+//! layers and customizing each signal.
+use std::{collections::BTreeMap as Map, fmt, str::FromStr as _};
+
+use opentelemetry::{KeyValue, trace::TracerProvider as _};
+use opentelemetry_appender_tracing::layer::OpenTelemetryTracingBridge;
+use opentelemetry_otlp::{ExporterBuildError, LogExporter, SpanExporter, WithExportConfig as _};
+use opentelemetry_resource_detectors::{K8sResourceDetector, ProcessResourceDetector};
+use opentelemetry_sdk::{
+	Resource,
+	logs::{LogProcessor, LoggerProviderBuilder, SdkLoggerProvider},
+	metrics::{MeterProviderBuilder, PeriodicReader, SdkMeterProvider, reader::MetricReader},
+	propagation::TraceContextPropagator,
+	trace::{RandomIdGenerator, SdkTracerProvider, SpanProcessor, TracerProviderBuilder},
+};
+use opentelemetry_semantic_conventions::resource::SERVICE_VERSION;
+use tracing::Subscriber;
+use tracing_appender::non_blocking::WorkerGuard;
+use tracing_opentelemetry::{MetricsLayer, OpenTelemetryLayer};
+use tracing_subscriber::{
+	EnvFilter, Layer, Registry, layer::SubscriberExt as _, util::SubscriberInitExt as _,
+};
+
+use crate::{
+	OtelInitError, ProvidersGuard, audit,
+	config::{OtelConfig, OtelUrl, StdoutLogsConfig},
+	debug_requests::DebugScoped,
+	file,
+	journald::JournaldLayer,
+	non_blocking::{self, DispatchCapture, StdoutGuard},
+	redaction::{Redacted, Redactor},
+	stdout,
+	syslog::SyslogLayer,
+};
+
+/// Type-erased layer of the subscriber
+pub(crate) type BoxedLayer = Box<dyn Layer<Registry> + Send + Sync>;
+
+/// Customization applied to the builder of an OpenTelemetry provider
+type ProviderHook<B> = Box<dyn FnOnce(B) -> B + Send>;
+
+/// Crates a resource for the Otel providers
+fn mk_resource(
+	service_name: &'static str,
+	version: &'static str,
+	resource_metadata: Map<String, String>,
+	resource_attributes: Vec<KeyValue>,
+) -> Resource {
+	Resource::builder()
+		.with_attributes(
+			resource_metadata.into_iter().map(|(key, value)| KeyValue::new(key, value)),
+		)
+		.with_attributes(resource_attributes)
+		.with_detector(Box::new(K8sResourceDetector {}))
+		.with_detector(Box::new(ProcessResourceDetector {}))
+		.with_attribute(KeyValue::new(SERVICE_VERSION, version))
+		.with_service_name(service_name)
+		.build()
+}
+
+/// Setup a Otel exporter and a provider for traces, optionally setting it as
+/// the global tracer provider
+fn init_traces(
+	endpoint: OtelUrl,
+	resource: Resource,
+	set_global: bool,
+	hooks: Vec<ProviderHook<TracerProviderBuilder>>,
+) -> Result<SdkTracerProvider, ExporterBuildError> {
+	let exporter = SpanExporter::builder().with_tonic().with_endpoint(endpoint.url).build()?;
+	let builder = SdkTracerProvider::builder()
+		.with_id_generator(RandomIdGenerator::default())
+		.with_resource(resource)
+		.with_batch_exporter(exporter);
+	let tracer_provider = hooks.into_iter().fold(builder, |builder, hook| hook(builder)).build();
+
+	if set_global {
+		opentelemetry::global::set_tracer_provider(tracer_provider.clone());
+	}
+	Ok(tracer_provider)
+}
+
+/// Setup a Otel exporter and a provider for metrics, optionally setting it as
+/// the global meter provider
+fn init_metrics(
+	endpoint: OtelUrl,
+	resource: Resource,
+	set_global: bool,
+	hooks: Vec<ProviderHook<MeterProviderBuilder>>,
+) -> Result<SdkMeterProvider, ExporterBuildError> {
+	let exporter = opentelemetry_otlp::MetricExporter::builder()
+		.with_tonic()
+		.with_endpoint(endpoint.url)
+		.with_temporality(opentelemetry_sdk::metrics::Temporality::default())
+		.build()?;
+
+	let reader = PeriodicReader::builder(exporter).build();
+
+	let builder = MeterProviderBuilder::default().with_resource(resource).with_reader(reader);
+	let meter_provider = hooks.into_iter().fold(builder, |builder, hook| hook(builder)).build();
+
+	if set_global {
+		opentelemetry::global::set_meter_provider(meter_provider.clone());
+	}
+	Ok(meter_provider)
+}
+
+/// Parses the filter of a layer other than the audit one, keeping the audit
+/// events out of it when they are delivered to the audit logs
+fn env_filter(config: &OtelConfig, filter: &str) -> Result<EnvFilter, OtelInitError> {
+	let filter = EnvFilter::from_str(filter)?;
+	if !config.audit.as_ref().is_some_and(|audit| audit.enabled) {
+		return Ok(filter);
+	}
+	Ok(filter.add_directive(format!("{}=off", audit::TARGET).parse()?))
+}
+
+/// Parses the filter of a layer, raised to the level of the debug header
+/// within the requests with a trusted one if configured
+fn debug_filter(
+	config: &OtelConfig,
+	filter: &str,
+) -> Result<DebugScoped<EnvFilter>, OtelInitError> {
+	let debug_level =
+		config.http.as_ref().and_then(|http| http.debug_header.as_ref()).map(|debug| debug.level.0);
+	Ok(DebugScoped::new(env_filter(config, filter)?, debug_level))
+}
+
+/// Wraps the layers in the redaction of the sensitive data, if configured
+fn redact(layers: Vec<BoxedLayer>, config: &OtelConfig) -> Result<Vec<BoxedLayer>, OtelInitError> {
+	let Some(redaction_config) = config.redaction.as_ref() else {
+		return Ok(layers);
+	};
+	Ok(vec![Box::new(Redacted::new(layers, Redactor::new(redaction_config)?))])
+}
+
+/// Setup the layer measuring the spans written to the `Server-Timing` header,
+/// if configured
+#[cfg_attr(not(feature = "axum"), allow(unused_variables))]
+fn init_server_timing(config: &OtelConfig) -> Option<BoxedLayer> {
+	#[cfg(feature = "axum")]
+	return config
+		.http
+		.as_ref()
+		.and_then(|http| http.server_timing.as_ref())
+		.map(crate::axum::server_timing_layer);
+	#[cfg(not(feature = "axum"))]
+	None
+}
+
+/// Setup the layer writing the logs to stdout and stderr, enabled by default,
+/// from background threads if configured
+fn init_stdout_logs(
+	otel_config: &OtelConfig,
+	main_crate: &'static str,
+) -> Result<(Option<BoxedLayer>, Option<StdoutGuard>), OtelInitError> {
+	let default_config = StdoutLogsConfig::default();
+	let config = otel_config.stdout.as_ref().unwrap_or(&default_config);
+	if !config.enabled {
+		return Ok((None, None));
+	}
+	let filter = debug_filter(otel_config, &config.get_filter(main_crate))?;
+	Ok(match config.non_blocking.as_ref() {
+		Some(non_blocking_config) => {
+			let stderr = config.stderr_level.is_some().then(std::io::stderr);
+			let (stdout, stderr, guard) =
+				non_blocking::writers(non_blocking_config, std::io::stdout(), stderr);
+			let layer = match stderr {
+				Some(stderr) => stdout::routed_layer(config, stdout, stderr),
+				None => stdout::layer(config, stdout),
+			};
+			(Some(Box::new(layer.with_filter(filter))), Some(guard))
+		}
+		None => {
+			let layer = stdout::routed_layer(config, std::io::stdout, std::io::stderr);
+			(Some(Box::new(layer.with_filter(filter))), None)
+		}
+	})
+}
+
+/// Setup the layer writing the logs to rotating files, if enabled
+fn init_file_logs(
+	otel_config: &OtelConfig,
+	main_crate: &'static str,
+	service_name: &'static str,
+) -> Result<(Option<BoxedLayer>, Option<WorkerGuard>), OtelInitError> {
+	let Some(config) = otel_config.file.as_ref().filter(|file| file.enabled) else {
+		return Ok((None, None));
+	};
+	let filter = env_filter(otel_config, &config.get_filter(main_crate))?;
+	let (writer, guard) = file::writer(config, service_name)?;
+	Ok((
+		Some(Box::new(stdout::layer(&config.fmt_config(), writer).with_filter(filter))),
+		Some(guard),
+	))
+}
+
+/// Setup the layers sending the logs to the systemd journal and to syslog
+fn init_system_logs(
+	config: &OtelConfig,
+	main_crate: &'static str,
+	service_name: &'static str,
+) -> Result<Vec<BoxedLayer>, OtelInitError> {
+	let mut layers: Vec<BoxedLayer> = Vec::new();
+	if let Some(journald_config) = config.journald.as_ref().filter(|journald| journald.enabled) {
+		let filter = env_filter(config, &journald_config.get_filter(main_crate))?;
+		let layer = JournaldLayer::new(journald_config, service_name)
+			.map_err(OtelInitError::JournaldError)?;
+		layers.push(Box::new(layer.with_filter(filter)));
+	}
+	if let Some(syslog_config) = config.syslog.as_ref().filter(|syslog| syslog.enabled) {
+		let filter = env_filter(config, &syslog_config.get_filter(main_crate))?;
+		let layer =
+			SyslogLayer::new(syslog_config, service_name).map_err(OtelInitError::SyslogError)?;
+		layers.push(Box::new(layer.with_filter(filter)));
+	}
+	Ok(layers)
+}
+
+/// Setup the layer delivering the audit logs, with the resource of the other
+/// signals
+fn init_audit_logs(
+	config: &OtelConfig,
+	service_name: &'static str,
+	pkg_version: &'static str,
+	resource_attributes: &[KeyValue],
+) -> Result<Option<BoxedLayer>, OtelInitError> {
+	let Some(audit_config) = config.audit.as_ref().filter(|audit| audit.enabled) else {
+		return Ok(None);
+	};
+	let resource_metadata =
+		config.exporter.as_ref().map(|exporter| exporter.resource_metadata.clone());
+	let resource = mk_resource(
+		service_name,
+		pkg_version,
+		resource_metadata.unwrap_or_default(),
+		resource_attributes.to_vec(),
+	);
+	Ok(Some(audit::layer(audit_config, resource)?))
+}
+
+/// Setup a Otel exporter and a provider for logs
+fn init_logs(
+	endpoint: OtelUrl,
+	resource: Resource,
+	hooks: Vec<ProviderHook<LoggerProviderBuilder>>,
+) -> Result<SdkLoggerProvider, ExporterBuildError> {
+	let exporter = LogExporter::builder().with_tonic().with_endpoint(endpoint.url).build()?;
+
+	let builder =
+		SdkLoggerProvider::builder().with_resource(resource).with_batch_exporter(exporter);
+	Ok(hooks.into_iter().fold(builder, |builder, hook| hook(builder)).build())
+}
+
+/// Builds the subscriber and the OpenTelemetry providers, allowing to add
+/// custom layers and to customize each signal
+///
+/// The span processors, log processors and metric readers are only used if
+/// exporting the corresponding signal is enabled in the config.
+///
+/// Example
+///
+/// ```rust
+/// use opentelemetry::KeyValue;
+/// use rust_telemetry::{config::OtelConfig, telemetry_builder};
+/// use tracing_subscriber::{Layer as _, filter::LevelFilter};
+///
+/// #[tokio::main]
+/// async fn main() {
+/// 	let audit_layer = tracing_subscriber::fmt::layer()
+/// 		.with_target(true)
+/// 		.with_filter(LevelFilter::WARN);
+/// 	let _guard = telemetry_builder!(OtelConfig::default())
+/// 		.with_layer(audit_layer)
+/// 		.with_resource_attribute(KeyValue::new(
+/// 			"deployment.environment.name",
+/// 			"staging",
+/// 		))
+/// 		.init();
+///
+/// 	// ...
+/// }
+/// ```
+pub struct TelemetryBuilder {
+	/// Telemetry configuration
+	config: OtelConfig,
+	/// Crate whose level is set by the `level` config options
+	main_crate: &'static str,
+	/// Name of the service in the OpenTelemetry resource
+	service_name: &'static str,
+	/// Version of the service in the OpenTelemetry resource
+	pkg_version: &'static str,
+	/// Custom layers added to the subscriber
+	layers: Vec<BoxedLayer>,
+	/// Customizations of the tracer provider
+	tracer_hooks: Vec<ProviderHook<TracerProviderBuilder>>,
+	/// Customizations of the logger provider
+	logger_hooks: Vec<ProviderHook<LoggerProviderBuilder>>,
+	/// Customizations of the meter provider
+	meter_hooks: Vec<ProviderHook<MeterProviderBuilder>>,
+	/// Additional attributes of the OpenTelemetry resource
+	resource_attributes: Vec<KeyValue>,
+}
+
+impl fmt::Debug for TelemetryBuilder {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.debug_struct("TelemetryBuilder")
+			.field("config", &self.config)
+			.field("main_crate", &self.main_crate)
+			.field("service_name", &self.service_name)
+			.field("pkg_version", &self.pkg_version)
+			.field("layers", &self.layers.len())
+			.field("tracer_hooks", &self.tracer_hooks.len())
+			.field("logger_hooks", &self.logger_hooks.len())
+			.field("meter_hooks", &self.meter_hooks.len())
+			.field("resource_attributes", &self.resource_attributes)
+			.finish()
+	}
+}
+
+/// Creates a [`TelemetryBuilder`] for the calling crate
+///
+/// example
+/// ```rust
+/// use rust_telemetry::{config::OtelConfig, telemetry_builder};
+///
+/// #[tokio::main]
+/// async fn main() {
+/// 	let _guard = telemetry_builder!(OtelConfig::default()).init();
+///
+/// 	// ...
+/// }
+/// ```
+#[macro_export]
+macro_rules! telemetry_builder {
+	($config:expr) => {
+		$crate::builder::TelemetryBuilder::new(
+			$config,
+			env!("CARGO_CRATE_NAME"),
+			env!("CARGO_PKG_NAME"),
+			env!("CARGO_PKG_VERSION"),
+		)
+	};
+}
+
+impl TelemetryBuilder {
+	/// Creates a builder for the given config. See [`crate::init_otel`] for
+	/// the meaning of the other arguments.
+	#[must_use]
+	pub fn new(
+		config: OtelConfig,
+		main_crate: &'static str,
+		service_name: &'static str,
+		pkg_version: &'static str,
+	) -> Self {
+		Self {
+			config,
+			main_crate,
+			service_name,
+			pkg_version,
+			layers: Vec::new(),
+			tracer_hooks: Vec::new(),
+			logger_hooks: Vec::new(),
+			meter_hooks: Vec::new(),
+			resource_attributes: Vec::new(),
+		}
+	}
+
+	/// Adds a custom layer to the subscriber, after the layers built from the
+	/// config
+	#[must_use]
+	pub fn with_layer<L>(mut self, layer: L) -> Self
+	where
+		L: Layer<Registry> + Send + Sync + 'static,
+	{
+		self.layers.push(Box::new(layer));
+		self
+	}
+
+	/// Adds a span processor to the tracer provider, after the one exporting
+	/// over OTLP
+	#[must_use]
+	pub fn with_span_processor<P: SpanProcessor + 'static>(mut self, processor: P) -> Self {
+		self.tracer_hooks.push(Box::new(|builder| builder.with_span_processor(processor)));
+		self
+	}
+
+	/// Adds a log processor to the logger provider, after the one exporting
+	/// over OTLP
+	#[must_use]
+	pub fn with_log_processor<P: LogProcessor + 'static>(mut self, processor: P) -> Self {
+		self.logger_hooks.push(Box::new(|builder| builder.with_log_processor(processor)));
+		self
+	}
+
+	/// Adds a metric reader to the meter provider, next to the one exporting
+	/// over OTLP
+	#[must_use]
+	pub fn with_metric_reader<R: MetricReader>(mut self, reader: R) -> Self {
+		self.meter_hooks.push(Box::new(|builder| builder.with_reader(reader)));
+		self
+	}
+
+	/// Adds an attribute to the OpenTelemetry resource of all the signals
+	#[must_use]
+	pub fn with_resource_attribute(mut self, attribute: KeyValue) -> Self {
+		self.resource_attributes.push(attribute);
+		self
+	}
+
+	/// Builds the subscriber without installing it, for use with
+	/// [`tracing::subscriber::set_default`] or
+	/// [`tracing::subscriber::with_default`]
+	///
+	/// Neither the subscriber, the tracer and meter providers nor the text map
+	/// propagator are set globally.
+	pub fn build(
+		self,
+	) -> Result<(impl Subscriber + Send + Sync + 'static, ProvidersGuard), OtelInitError> {
+		self.build_subscriber(false)
+	}
+
+	/// Installs the subscriber and the providers globally
+	///
+	/// # Panics
+	///
+	/// Panics if a global subscriber is already set
+	#[must_use = "The return is a guard for the providers and it need to be kept to properly shutdown them"]
+	pub fn init(self) -> Result<ProvidersGuard, OtelInitError> {
+		opentelemetry::global::set_text_map_propagator(TraceContextPropagator::default());
+		let (subscriber, guard) = self.build_subscriber(true)?;
+		subscriber.init();
+		Ok(guard)
+	}
+
+	/// Installs the subscriber and the providers globally, returning
+	/// [`OtelInitError::AlreadyInitialized`] if a global subscriber is already
+	/// set. In that case, neither the subscriber nor the global providers are
+	/// touched.
+	#[must_use = "The return is a guard for the providers and it need to be kept to properly shutdown them"]
+	pub fn try_init(self) -> Result<ProvidersGuard, OtelInitError> {
+		if tracing::dispatcher::has_been_set() {
+			return Err(OtelInitError::AlreadyInitialized);
+		}
+		let (subscriber, guard) = self.build_subscriber(false)?;
+		subscriber.try_init().map_err(|_| OtelInitError::AlreadyInitialized)?;
+
+		opentelemetry::global::set_text_map_propagator(TraceContextPropagator::default());
+		if let Some(tracer_provider) = guard.tracer_provider.as_ref() {
+			opentelemetry::global::set_tracer_provider(tracer_provider.clone());
+		}
+		if let Some(meter_provider) = guard.meter_provider.as_ref() {
+			opentelemetry::global::set_meter_provider(meter_provider.clone());
+		}
+		Ok(guard)
+	}
+
+	/// Builds the subscriber with the stdout layer, the layers exporting over
+	/// OpenTelemetry and the custom layers, optionally setting the tracer and
+	/// meter providers globally
+	fn build_subscriber(
+		self,
+		set_global_providers: bool,
+	) -> Result<(impl Subscriber + Send + Sync + 'static, ProvidersGuard), OtelInitError> {
+		let Self {
+			config,
+			main_crate,
+			service_name,
+			pkg_version,
+			layers: custom_layers,
+			tracer_hooks,
+			logger_hooks,
+			meter_hooks,
+			resource_attributes,
+		} = self;
+		let mut layers: Vec<BoxedLayer> = Vec::new();
+
+		let audit_layer =
+			init_audit_logs(&config, service_name, pkg_version, &resource_attributes)?;
+
+		let exporter_with_resource = config.exporter.as_ref().map(|exporter| {
+			let resource = mk_resource(
+				service_name,
+				pkg_version,
+				exporter.resource_metadata.clone(),
+				resource_attributes,
+			);
+			(exporter, resource)
+		});
+
+		let logger_provider = exporter_with_resource
+			.as_ref()
+			.and_then(|(exporter, resource)| {
+				exporter.logs.as_ref().and_then(|c| c.enabled.then_some(c)).map(|logger_config| {
+					let filter_otel = debug_filter(&config, &logger_config.get_filter(main_crate))?;
+					let logger_provider =
+						init_logs(exporter.endpoint.clone(), resource.clone(), logger_hooks)?;
+
+					// Create a new OpenTelemetryTracingBridge using the above LoggerProvider.
+					layers.push(Box::new(
+						OpenTelemetryTracingBridge::new(&logger_provider).with_filter(filter_otel),
+					));
+
+					Ok::<_, OtelInitError>(logger_provider)
+				})
+			})
+			.transpose()?;
+
+		let (stdout_layer, stdout_guard) = init_stdout_logs(&config, main_crate)?;
+		layers.extend(stdout_layer);
+		let dispatch_capture = stdout_guard
+			.as_ref()
+			.map_or_else(DispatchCapture::default, StdoutGuard::dispatch_capture);
+
+		let (file_layer, file_guard) = init_file_logs(&config, main_crate, service_name)?;
+		layers.extend(file_layer);
+		layers.extend(init_system_logs(&config, main_crate, service_name)?);
+
+		let meter_provider = exporter_with_resource
+			.as_ref()
+			.and_then(|(exporter, resource)| {
+				exporter.metrics.as_ref().and_then(|c| c.enabled.then_some(c)).map(|meter_config| {
+					let metrics_filter = env_filter(&config, &meter_config.get_filter(main_crate))?;
+					let meter_provider = init_metrics(
+						exporter.endpoint.clone(),
+						resource.clone(),
+						set_global_providers,
+						meter_hooks,
+					)?;
+					layers.push(Box::new(
+						MetricsLayer::new(meter_provider.clone()).with_filter(metrics_filter),
+					));
+
+					Ok::<_, OtelInitError>(meter_provider)
+				})
+			})
+			.transpose()?;
+
+		let tracer_provider = exporter_with_resource
+			.as_ref()
+			.and_then(|(exporter, resource)| {
+				exporter.traces.as_ref().and_then(|c| c.enabled.then_some(c)).map(|tracer_config| {
+					let filter = debug_filter(&config, &tracer_config.get_filter(main_crate))?;
+					let tracer_provider = init_traces(
+						exporter.endpoint.clone(),
+						resource.clone(),
+						set_global_providers,
+						tracer_hooks,
+					)?;
+					let tracer = tracer_provider.tracer(service_name);
+					layers.push(Box::new(OpenTelemetryLayer::new(tracer).with_filter(filter)));
+					Ok::<_, OtelInitError>(tracer_provider)
+				})
+			})
+			.transpose()?;
+
+		let mut layers = redact(layers, &config)?;
+		layers.extend(audit_layer);
+		layers.extend(init_server_timing(&config));
+
+		#[cfg(feature = "tracing-error")]
+		layers.push(Box::new(tracing_error::ErrorLayer::default()));
+
+		layers.extend(custom_layers);
+
+		// Initialize the tracing subscriber with the stdout layer,
+		// layers for exporting over OpenTelemetry the logs, traces and metrics,
+		// redacted if configured, the audit logs and the custom layers.
+		let subscriber = tracing_subscriber::registry().with(layers).with(dispatch_capture);
+
+		Ok((
+			subscriber,
+			ProvidersGuard {
+				logger_provider,
+				tracer_provider,
+				meter_provider,
+				stdout_guard,
+				file_guard,
+			},
+		))
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	#![allow(clippy::expect_used)]
+	use std::sync::{
+		Arc,
+		atomic::{AtomicUsize, Ordering},
+	};
+
+	use opentelemetry_sdk::{
+		error::OTelSdkResult,
+		trace::{SpanData, SpanProcessor},
+	};
+	use tracing::Subscriber;
+	use tracing_subscriber::{Layer, layer::Context};
+
+	use crate::config::{ExporterConfig, OtelConfig, ProviderConfig};
+
+	/// Layer counting the events it sees
+	#[derive(Debug, Clone, Default)]
+	struct CountingLayer(Arc<AtomicUsize>);
+
+	impl<S: Subscriber> Layer<S> for CountingLayer {
+		fn on_event(&self, _event: &tracing::Event<'_>, _ctx: Context<'_, S>) {
+			self.0.fetch_add(1, Ordering::SeqCst);
+		}
+	}
+
+	/// Span processor counting the ended spans
+	#[derive(Debug, Clone, Default)]
+	struct CountingProcessor(Arc<AtomicUsize>);
+
+	impl SpanProcessor for CountingProcessor {
+		fn on_start(
+			&self,
+			_span: &mut opentelemetry_sdk::trace::Span,
+			_cx: &opentelemetry::Context,
+		) {
+		}
+
+		fn on_end(&self, _span: SpanData) {
+			self.0.fetch_add(1, Ordering::SeqCst);
+		}
+
+		fn force_flush(&self) -> OTelSdkResult {
+			Ok(())
+		}
+
+		fn shutdown_with_timeout(&self, _timeout: std::time::Duration) -> OTelSdkResult {
+			Ok(())
+		}
+	}
+
+	#[tokio::test]
+	async fn test_custom_layer_receives_events() {
+		let layer = CountingLayer::default();
+		let (subscriber, _guard) = telemetry_builder!(OtelConfig::for_tests())
+			.with_layer(layer.clone())
+			.build()
+			.expect("Error building subscriber");
+
+		tracing::subscriber::with_default(subscriber, || tracing::info!("Counted"));
+		assert_eq!(layer.0.load(Ordering::SeqCst), 1);
+	}
+
+	#[tokio::test(flavor = "multi_thread")]
+	async fn test_custom_span_processor_receives_spans() {
+		let processor = CountingProcessor::default();
+		let config = OtelConfig {
+			exporter: Some(ExporterConfig {
+				traces: Some(ProviderConfig { enabled: true, ..Default::default() }),
+				..Default::default()
+			}),
+			..Default::default()
+		};
+		let (subscriber, _guard) = telemetry_builder!(config)
+			.with_span_processor(processor.clone())
+			.build()
+			.expect("Error building subscriber");
+
+		tracing::subscriber::with_default(subscriber, || {
+			tracing::info_span!("processed").in_scope(|| {});
+		});
+		assert_eq!(processor.0.load(Ordering::SeqCst), 1);
+	}
+
+	#[cfg(feature = "testing")]
+	#[tokio::test(flavor = "multi_thread")]
+	async fn test_resource_attribute_is_exported() {
+		let collector =
+			crate::testing::MockCollector::start().await.expect("Error starting collector");
+		let config = OtelConfig {
+			exporter: Some(ExporterConfig {
+				endpoint: collector.endpoint().into(),
+				traces: Some(ProviderConfig { enabled: true, ..Default::default() }),
+				..Default::default()
+			}),
+			..Default::default()
+		};
+		let (subscriber, guard) = telemetry_builder!(config)
+			.with_resource_attribute(opentelemetry::KeyValue::new("tenant", "famedly"))
+			.build()
+			.expect("Error building subscriber");
+
+		tracing::subscriber::with_default(subscriber, || {
+			tracing::info_span!("exported").in_scope(|| {});
+		});
+		guard
+			.tracer_provider
+			.as_ref()
+			.expect("Missing tracer provider")
+			.force_flush()
+			.expect("Error flushing traces");
+
+		assert!(
+			collector
+				.wait_until(std::time::Duration::from_secs(10), |c| !c.traces().is_empty())
+				.await
+		);
+		assert_eq!(collector.traces()[0].resource_attribute("tenant"), Some("famedly"));
+	}
+}
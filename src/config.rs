@@ -6,9 +6,14 @@
 //!
 //! Module containing the configuration struct for the OpenTelemetry
 
-use std::collections::{BTreeMap as Map, HashMap};
+use std::{
+	collections::{BTreeMap as Map, HashMap},
+	net::IpAddr,
+	path::PathBuf,
+	time::Duration,
+};
 
-use famedly_rust_utils::LevelFilter;
+use famedly_rust_utils::{LevelFilter, duration::Seconds};
 use serde::Deserialize;
 use url::Url;
 
@@ -44,6 +49,18 @@ impl Default for OtelUrl {
 pub struct OtelConfig {
 	/// Enables logs on stdout
 	pub stdout: Option<StdoutLogsConfig>,
+	/// Enables logs in rotating files
+	pub file: Option<FileLogsConfig>,
+	/// Enables logs in the systemd journal
+	pub journald: Option<JournaldConfig>,
+	/// Enables logs sent to a syslog server
+	pub syslog: Option<SyslogConfig>,
+	/// Redacts sensitive fields from the logs, spans and metrics
+	pub redaction: Option<RedactionConfig>,
+	/// Enables the audit logs, delivered separately from the other logs
+	pub audit: Option<AuditConfig>,
+	/// Instrumentation of the HTTP servers
+	pub http: Option<HttpConfig>,
 	/// Configurations for exporting traces, metrics and logs
 	pub exporter: Option<ExporterConfig>,
 }
@@ -57,10 +74,296 @@ impl OtelConfig {
 				enabled: true,
 				level: tracing_subscriber::filter::LevelFilter::TRACE.into(),
 				general_level: tracing_subscriber::filter::LevelFilter::INFO.into(),
-				dependencies_levels: HashMap::new(),
-				json_output: false,
+				..Default::default()
 			}),
-			exporter: None,
+			..Default::default()
+		}
+	}
+}
+
+/// Redaction of sensitive values
+///
+/// It applies to all the outputs built from the config: the stdout, file,
+/// journal and syslog logs, and the logs, span attributes and metric
+/// attributes exported over OTLP. The custom layers of the
+/// [`TelemetryBuilder`](crate::builder::TelemetryBuilder) see the original
+/// values.
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct RedactionConfig {
+	/// Names of the fields whose values are always redacted. They are compared
+	/// case-insensitively to the whole name of the fields and to the part after
+	/// the last `.`
+	#[serde(default)]
+	pub fields: Vec<String>,
+	/// Regular expressions whose matches are redacted in the text values of all
+	/// the fields, including the messages
+	#[serde(default)]
+	pub patterns: Vec<String>,
+	/// How the values are redacted
+	#[serde(default)]
+	pub mode: RedactionMode,
+}
+
+/// How the sensitive values are redacted
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum RedactionMode {
+	/// Replaced by the given text
+	Mask(String),
+	/// Replaced by `hash:` and the start of their HMAC-SHA256 with the given
+	/// key, so that the records about the same value can still be correlated.
+	/// Use a secret key, otherwise guessable values like email addresses can
+	/// be recovered from their hash
+	Hash(String),
+}
+
+/// Audit logs configuration
+///
+/// The events with the [`audit`](crate::audit) target are written to the
+/// destination regardless of the levels of the other outputs, which don't get
+/// them, and the code emitting them waits until they are delivered. Delivery
+/// failures are reported on stderr with the record. The audit logs are not
+/// redacted.
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, Deserialize)]
+pub struct AuditConfig {
+	/// Enables the audit logs
+	#[serde(default = "true_")]
+	pub enabled: bool,
+	/// Where the audit logs are delivered
+	pub destination: AuditDestination,
+}
+
+/// Destination of the audit logs
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditDestination {
+	/// gRPC endpoint of an OTLP collector, receiving each record in its own
+	/// export request
+	Otlp(OtelUrl),
+	/// File the records are appended to as OTLP JSON lines, synced to disk
+	/// after each record
+	File(PathBuf),
+}
+
+/// Instrumentation of the HTTP servers, used by the layers of the
+/// [`axum`](crate::axum) module
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct HttpConfig {
+	/// Metrics of the requests
+	#[serde(default)]
+	pub metrics: HttpMetricsConfig,
+	/// Trace and request ids written to the responses
+	#[serde(default)]
+	pub trace_response: TraceResponseConfig,
+	/// Trust in the trace context and baggage propagated by the callers
+	#[serde(default)]
+	pub incoming_context: IncomingContextConfig,
+	/// Raises the level of the stdout logs and of the logs and traces exported
+	/// over OTLP for the requests with a trusted header
+	pub debug_header: Option<DebugHeaderConfig>,
+	/// Durations of selected spans written to the responses of the requests
+	/// with a trusted header
+	pub server_timing: Option<ServerTimingConfig>,
+	/// Headers and client address recorded in the spans
+	#[serde(default)]
+	pub capture: HttpCaptureConfig,
+}
+
+/// Headers and client address recorded in the spans of the requests by the
+/// [`ServerTraceLayer`](crate::axum::ServerTraceLayer) and the
+/// `OtelMiddleware`
+///
+/// Only the listed headers are recorded, and the `authorization`,
+/// `proxy-authorization`, `cookie` and `set-cookie` headers can't be listed.
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct HttpCaptureConfig {
+	/// Request headers recorded as `http.request.header.<name>`, and the
+	/// `user-agent` one also as `user_agent.original` in the server spans
+	#[serde(default)]
+	pub request_headers: Vec<String>,
+	/// Response headers recorded as `http.response.header.<name>`
+	#[serde(default)]
+	pub response_headers: Vec<String>,
+	/// How the address of the client is recorded as `client.address` in the
+	/// server spans
+	#[serde(default)]
+	pub client_address: ClientAddressMode,
+}
+
+/// How the address of the client is recorded
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ClientAddressMode {
+	/// Not recorded
+	#[default]
+	Omit,
+	/// Recorded as is
+	Full,
+	/// Recorded without the last octet of the IPv4 addresses and beyond the
+	/// /64 prefix of the IPv6 addresses
+	Truncate,
+	/// Replaced by `hash:` and the start of its HMAC-SHA256 with the given
+	/// key, so that the requests of a client can still be correlated
+	Hash(String),
+}
+
+/// Header raising the level of the logs and traces of a single request, to
+/// reproduce a bug without changing the level of the whole service
+///
+/// The header is trusted if its value is signed with the `hmac_key`, or if the
+/// request comes from one of the `allowed_sources`. Without any of them, the
+/// header is never trusted.
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, Deserialize)]
+pub struct DebugHeaderConfig {
+	/// Name of the header
+	#[serde(default = "default_debug_header")]
+	pub header: String,
+	/// Level of the stdout logs and of the logs and traces exported over OTLP
+	/// within the requests with a trusted header
+	#[serde(default = "default_debug_level")]
+	pub level: LevelFilter,
+	/// Key of the HMAC-SHA256 signing the values of the header, which are
+	/// `<expiry>.<signature>` with the expiry in seconds since the Unix epoch
+	/// and the hexadecimal signature of the expiry
+	pub hmac_key: Option<String>,
+	/// Addresses of the callers trusted to send the header with any value.
+	/// Requires serving the router with its connection info
+	#[serde(default)]
+	pub allowed_sources: Vec<IpAddr>,
+}
+
+impl Default for DebugHeaderConfig {
+	fn default() -> Self {
+		Self {
+			header: default_debug_header(),
+			level: default_debug_level(),
+			hmac_key: None,
+			allowed_sources: Vec::new(),
+		}
+	}
+}
+
+/// `Server-Timing` header listing the durations of the selected spans of a
+/// request, for the browser developer tools
+///
+/// The header is only written for the requests with a trusted header, signed
+/// with the `hmac_key` like the [`DebugHeaderConfig`] or coming from one of
+/// the `allowed_sources`, as the durations show the internals of the service.
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, Deserialize)]
+pub struct ServerTimingConfig {
+	/// Name of the header requesting the timings
+	#[serde(default = "default_server_timing_header")]
+	pub header: String,
+	/// Key of the HMAC-SHA256 signing the values of the header
+	pub hmac_key: Option<String>,
+	/// Addresses of the callers trusted to send the header with any value.
+	/// Requires serving the router with its connection info
+	#[serde(default)]
+	pub allowed_sources: Vec<IpAddr>,
+	/// Names of the spans measured
+	#[serde(default)]
+	pub span_names: Vec<String>,
+	/// Targets of the spans measured, also matching the modules within them
+	#[serde(default)]
+	pub span_targets: Vec<String>,
+}
+
+impl Default for ServerTimingConfig {
+	fn default() -> Self {
+		Self {
+			header: default_server_timing_header(),
+			hmac_key: None,
+			allowed_sources: Vec::new(),
+			span_names: Vec::new(),
+			span_targets: Vec::new(),
+		}
+	}
+}
+
+/// Trust in the trace context and baggage propagated by the callers
+///
+/// The spans of the untrusted requests start new traces linking to the context
+/// of the caller, so that the callers can't force the sampling or join our
+/// traces, and their baggage is capped.
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, Deserialize)]
+pub struct IncomingContextConfig {
+	/// Trusts the context of the requests to the routes not listed in
+	/// `routes`. Use a separate config per listener to trust the internal
+	/// listeners only
+	#[serde(default = "true_")]
+	pub trusted: bool,
+	/// Trust per route, by matched path like `/users/{id}`
+	#[serde(default)]
+	pub routes: HashMap<String, bool>,
+	/// Maximum size in bytes of the `baggage` header of the untrusted
+	/// requests, whose list members beyond it are dropped. 0 drops the whole
+	/// baggage
+	#[serde(default)]
+	pub untrusted_baggage_max_size: usize,
+}
+
+impl Default for IncomingContextConfig {
+	fn default() -> Self {
+		Self { trusted: true, routes: HashMap::new(), untrusted_baggage_max_size: 0 }
+	}
+}
+
+/// Trace and request ids written to the responses, so that the callers can
+/// quote them when reporting an error
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, Deserialize)]
+pub struct TraceResponseConfig {
+	/// Writes the trace id and span id of the request in the `traceresponse`
+	/// header of [Trace Context Level 2](https://www.w3.org/TR/trace-context-2/#traceresponse-header)
+	#[serde(default = "true_")]
+	pub traceresponse: bool,
+	/// Header holding the id of the request, recorded in the request span and
+	/// echoed in the response. Generated if the request doesn't have one, or
+	/// if it isn't made of 1 to 128 visible ASCII characters. Disabled if
+	/// `null`
+	#[serde(default = "default_request_id_header")]
+	pub request_id_header: Option<String>,
+}
+
+impl Default for TraceResponseConfig {
+	fn default() -> Self {
+		Self { traceresponse: true, request_id_header: default_request_id_header() }
+	}
+}
+
+/// Metrics of the requests received by the HTTP servers
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, Deserialize)]
+pub struct HttpMetricsConfig {
+	/// Boundaries in seconds of the buckets of the
+	/// `http.server.request.duration` histogram, in increasing order
+	#[serde(default = "default_duration_buckets")]
+	pub duration_buckets: Vec<f64>,
+	/// Paths of the requests not recorded, like `/health`
+	#[serde(default)]
+	pub excluded_paths: Vec<String>,
+	/// Records the `http.server.request.body.size` and
+	/// `http.server.response.body.size` histograms
+	#[serde(default = "true_")]
+	pub body_sizes: bool,
+}
+
+impl Default for HttpMetricsConfig {
+	fn default() -> Self {
+		Self {
+			duration_buckets: default_duration_buckets(),
+			excluded_paths: Vec::new(),
+			body_sizes: true,
 		}
 	}
 }
@@ -101,9 +404,321 @@ pub struct StdoutLogsConfig {
 	/// Level for the dependencies
 	#[serde(default)]
 	pub dependencies_levels: HashMap<String, LevelFilter>,
-	/// Output structured JSON logs
+	/// Output structured JSON logs. Deprecated alias of `format: json`
+	#[deprecated = "Use `format: LogFormat::Json` instead"]
 	#[serde(default)]
 	pub json_output: bool,
+	/// Format of the logs
+	#[serde(default)]
+	pub format: LogFormat,
+	/// Format of the timestamps. The vendor formats use the one of their schema
+	#[serde(default)]
+	pub timestamp: Timestamp,
+	/// Colors the output with ANSI escape codes. Ignored by the structured
+	/// formats
+	#[serde(default = "true_")]
+	pub ansi: bool,
+	/// Includes the target of the events
+	#[serde(default = "true_")]
+	pub target: bool,
+	/// Includes the source file and line of the events
+	#[serde(default)]
+	pub file_line: bool,
+	/// Includes the id of the thread emitting the events
+	#[serde(default)]
+	pub thread_ids: bool,
+	/// Span lifecycle events to log, in addition to the events
+	#[serde(default)]
+	pub span_events: Vec<SpanEvent>,
+	/// Google Cloud project of the traces, to link the logs to them in the
+	/// `gcp` format. The bare trace id is logged if not set.
+	#[serde(default)]
+	pub gcp_project_id: Option<String>,
+	/// Fields holding the OpenTelemetry context of the events in the text and
+	/// JSON formats
+	#[serde(default)]
+	pub trace_fields: TraceFieldsConfig,
+	/// Writes the logs from a background thread, so that a full stdout doesn't
+	/// block the application. The logs are written synchronously if not set
+	#[serde(default)]
+	pub non_blocking: Option<NonBlockingConfig>,
+	/// Writes the events at or above this level to stderr instead of stdout,
+	/// with the same format. Ignored by the `tree` format
+	#[serde(default)]
+	pub stderr_level: Option<LevelFilter>,
+}
+
+/// Configuration of the background thread writing the stdout logs
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, Deserialize)]
+pub struct NonBlockingConfig {
+	/// Maximum number of lines waiting to be written
+	#[serde(default = "default_buffered_lines")]
+	pub buffered_lines: usize,
+	/// What happens to the new lines when the buffer is full
+	#[serde(default)]
+	pub overflow: OverflowPolicy,
+	/// Interval in seconds at which the dropped lines are reported, as a
+	/// warning and as the `telemetry.stdout.dropped_lines` counter
+	#[serde(default = "default_drop_report_interval")]
+	pub report_interval: Seconds<Duration>,
+}
+
+/// What happens to the new log lines when the buffer is full
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum OverflowPolicy {
+	/// The lines are dropped and counted
+	#[default]
+	Drop,
+	/// The thread emitting the logs waits for the buffer to have room
+	Block,
+}
+
+/// Fields holding the OpenTelemetry context of the events in the stdout logs
+///
+/// They are only added when exporting the traces is enabled. The `tree` format
+/// doesn't support them and the vendor formats use the names of their schema.
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, Deserialize)]
+pub struct TraceFieldsConfig {
+	/// Adds the fields to the logs
+	#[serde(default = "true_")]
+	pub enabled: bool,
+	/// Name of the field holding the trace id
+	#[serde(default = "default_trace_id_field")]
+	pub trace_id: String,
+	/// Name of the field holding the span id
+	#[serde(default = "default_span_id_field")]
+	pub span_id: String,
+	/// Name of the field holding whether the trace is sampled
+	#[serde(default = "default_sampled_field")]
+	pub sampled: String,
+}
+
+/// Rotating file logs configuration
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, Deserialize)]
+pub struct FileLogsConfig {
+	/// Enables the file logs
+	#[serde(default = "true_")]
+	pub enabled: bool,
+	/// Level for the crate
+	#[serde(default = "default_level_filter")]
+	pub level: LevelFilter,
+	/// General level
+	#[serde(default = "default_level_filter")]
+	pub general_level: LevelFilter,
+	/// Level for the dependencies
+	#[serde(default)]
+	pub dependencies_levels: HashMap<String, LevelFilter>,
+	/// Format of the logs
+	#[serde(default)]
+	pub format: LogFormat,
+	/// Format of the timestamps
+	#[serde(default)]
+	pub timestamp: Timestamp,
+	/// Directory of the log files, created if missing
+	pub directory: PathBuf,
+	/// Prefix of the names of the log files. Defaults to the name of the
+	/// service
+	#[serde(default)]
+	pub prefix: Option<String>,
+	/// When to start a new log file
+	#[serde(default)]
+	pub rotation: Rotation,
+	/// Maximum number of log files to keep, including the current one. The
+	/// oldest files are deleted when rotating. All files are kept if not set
+	#[serde(default)]
+	pub max_files: Option<usize>,
+}
+
+/// systemd journal logs configuration
+///
+/// The logs are sent with the native journal protocol. The fields of the
+/// events and of their spans become journal fields, with their names upper
+/// cased and the characters other than letters, digits and `_` replaced by
+/// `_`.
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, Deserialize)]
+pub struct JournaldConfig {
+	/// Enables the journal logs
+	#[serde(default = "true_")]
+	pub enabled: bool,
+	/// Level for the crate
+	#[serde(default = "default_level_filter")]
+	pub level: LevelFilter,
+	/// General level
+	#[serde(default = "default_level_filter")]
+	pub general_level: LevelFilter,
+	/// Level for the dependencies
+	#[serde(default)]
+	pub dependencies_levels: HashMap<String, LevelFilter>,
+	/// `SYSLOG_IDENTIFIER` of the logs. Defaults to the name of the service
+	#[serde(default)]
+	pub syslog_identifier: Option<String>,
+	/// Prefix of the names of the journal fields holding the fields of the
+	/// events and spans, to avoid collisions with the standard fields
+	#[serde(default)]
+	pub field_prefix: Option<String>,
+	/// Socket of the journal
+	#[serde(default = "default_journald_socket")]
+	pub socket: PathBuf,
+}
+
+/// Syslog logs configuration
+///
+/// The logs are sent as [RFC 5424](https://www.rfc-editor.org/rfc/rfc5424)
+/// messages, with the fields of the events and of their spans as structured
+/// data.
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, Deserialize)]
+pub struct SyslogConfig {
+	/// Enables the syslog logs
+	#[serde(default = "true_")]
+	pub enabled: bool,
+	/// Level for the crate
+	#[serde(default = "default_level_filter")]
+	pub level: LevelFilter,
+	/// General level
+	#[serde(default = "default_level_filter")]
+	pub general_level: LevelFilter,
+	/// Level for the dependencies
+	#[serde(default)]
+	pub dependencies_levels: HashMap<String, LevelFilter>,
+	/// Where the messages are sent
+	#[serde(default)]
+	pub transport: SyslogTransport,
+	/// Facility of the messages
+	#[serde(default)]
+	pub facility: SyslogFacility,
+	/// `APP-NAME` of the messages. Defaults to the name of the service
+	#[serde(default)]
+	pub app_name: Option<String>,
+	/// `HOSTNAME` of the messages. Defaults to the name of the host
+	#[serde(default)]
+	pub hostname: Option<String>,
+}
+
+/// Where the syslog messages are sent
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SyslogTransport {
+	/// Local Unix datagram socket
+	Unix(PathBuf),
+	/// Server listening on the given `host:port` over UDP
+	Udp(String),
+}
+
+/// Facility of the syslog messages
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SyslogFacility {
+	/// User-level messages
+	#[default]
+	User,
+	/// System daemons
+	Daemon,
+	/// Security and authorization messages
+	Auth,
+	/// Private security and authorization messages
+	Authpriv,
+	/// Local use 0
+	Local0,
+	/// Local use 1
+	Local1,
+	/// Local use 2
+	Local2,
+	/// Local use 3
+	Local3,
+	/// Local use 4
+	Local4,
+	/// Local use 5
+	Local5,
+	/// Local use 6
+	Local6,
+	/// Local use 7
+	Local7,
+}
+
+/// When to start a new log file
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Rotation {
+	/// Every hour, the files are suffixed with the date and hour
+	Hourly,
+	/// Every day, the files are suffixed with the date
+	#[default]
+	Daily,
+	/// When the current file reaches the given size in bytes. The rotated files
+	/// are numbered from the most recent.
+	Size(u64),
+	/// Never, all the logs are written to a single file
+	Never,
+}
+
+/// Format of the stdout logs
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LogFormat {
+	/// Human readable single line logs, including the spans and their fields
+	#[default]
+	Full,
+	/// Shorter single line logs, including the fields of the spans but not
+	/// their names
+	Compact,
+	/// Human readable multi-line logs, for local development
+	Pretty,
+	/// Structured JSON logs
+	Json,
+	/// [logfmt](https://brandur.org/logfmt) key-value pairs
+	Logfmt,
+	/// Hierarchical view of the spans and their events
+	Tree,
+	/// JSON following the [Elastic Common Schema](https://www.elastic.co/guide/en/ecs/current/ecs-reference.html)
+	Ecs,
+	/// JSON following the [structured logging format of Google Cloud Logging](https://cloud.google.com/logging/docs/structured-logging)
+	Gcp,
+	/// JSON encoding of the [OTLP log records](https://opentelemetry.io/docs/specs/otlp/#json-protobuf-encoding)
+	OtlpJson,
+}
+
+/// Format of the timestamps of the stdout logs
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Timestamp {
+	/// RFC 3339 in UTC, with microseconds
+	#[default]
+	Utc,
+	/// RFC 3339 in the local timezone, with microseconds
+	Local,
+	/// RFC 3339 in UTC, with nanoseconds
+	Rfc3339Nanos,
+	/// Milliseconds since the Unix epoch
+	EpochMillis,
+	/// No timestamp, for environments timestamping the logs themselves
+	None,
+}
+
+/// Span lifecycle event
+#[cfg_attr(feature = "schemars", derive(schemars::JsonSchema))]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum SpanEvent {
+	/// The span was created
+	New,
+	/// The span was entered
+	Enter,
+	/// The span was exited
+	Exit,
+	/// The span was closed, including its busy and idle times
+	Close,
 }
 
 /// Provider configuration for OpenTelemetry export
@@ -127,30 +742,80 @@ pub struct ProviderConfig {
 impl ProviderConfig {
 	/// Builds a trace filter
 	pub(crate) fn get_filter(&self, crate_name: &'static str) -> String {
-		format!(
-			"{},{}{}={}",
-			self.general_level,
-			build_dependencies_level_string(&self.dependencies_levels),
-			crate_name,
-			self.level
-		)
+		build_filter(self.general_level, &self.dependencies_levels, crate_name, self.level)
+	}
+}
+
+impl FileLogsConfig {
+	/// Builds a trace filter
+	pub(crate) fn get_filter(&self, crate_name: &'static str) -> String {
+		build_filter(self.general_level, &self.dependencies_levels, crate_name, self.level)
+	}
+
+	/// Formatting of the logs, shared with the stdout logs
+	pub(crate) fn fmt_config(&self) -> StdoutLogsConfig {
+		StdoutLogsConfig {
+			format: self.format,
+			timestamp: self.timestamp,
+			ansi: false,
+			..Default::default()
+		}
+	}
+}
+
+impl JournaldConfig {
+	/// Builds a trace filter
+	pub(crate) fn get_filter(&self, crate_name: &'static str) -> String {
+		build_filter(self.general_level, &self.dependencies_levels, crate_name, self.level)
+	}
+}
+
+impl SyslogConfig {
+	/// Builds a trace filter
+	pub(crate) fn get_filter(&self, crate_name: &'static str) -> String {
+		build_filter(self.general_level, &self.dependencies_levels, crate_name, self.level)
+	}
+}
+
+impl SyslogFacility {
+	/// Numerical code of the facility
+	pub(crate) const fn code(self) -> u8 {
+		match self {
+			Self::User => 1,
+			Self::Daemon => 3,
+			Self::Auth => 4,
+			Self::Authpriv => 10,
+			Self::Local0 => 16,
+			Self::Local1 => 17,
+			Self::Local2 => 18,
+			Self::Local3 => 19,
+			Self::Local4 => 20,
+			Self::Local5 => 21,
+			Self::Local6 => 22,
+			Self::Local7 => 23,
+		}
 	}
 }
 
 impl StdoutLogsConfig {
+	/// Format of the logs, taking the deprecated `json_output` into account
+	#[allow(deprecated)]
+	pub(crate) fn log_format(&self) -> LogFormat {
+		if self.json_output && self.format == LogFormat::Full {
+			LogFormat::Json
+		} else {
+			self.format
+		}
+	}
+
 	/// Builds a trace filter
 	pub(crate) fn get_filter(&self, crate_name: &'static str) -> String {
-		format!(
-			"{},{}{}={}",
-			self.general_level,
-			build_dependencies_level_string(&self.dependencies_levels),
-			crate_name,
-			self.level
-		)
+		build_filter(self.general_level, &self.dependencies_levels, crate_name, self.level)
 	}
 }
 
 impl Default for StdoutLogsConfig {
+	#[allow(deprecated)]
 	fn default() -> Self {
 		Self {
 			enabled: true,
@@ -158,10 +823,54 @@ impl Default for StdoutLogsConfig {
 			general_level: default_level_filter(),
 			dependencies_levels: HashMap::new(),
 			json_output: false,
+			format: LogFormat::default(),
+			timestamp: Timestamp::default(),
+			ansi: true,
+			target: true,
+			file_line: false,
+			thread_ids: false,
+			span_events: Vec::new(),
+			gcp_project_id: None,
+			trace_fields: TraceFieldsConfig::default(),
+			non_blocking: None,
+			stderr_level: None,
+		}
+	}
+}
+
+impl Default for NonBlockingConfig {
+	fn default() -> Self {
+		Self {
+			buffered_lines: default_buffered_lines(),
+			overflow: OverflowPolicy::default(),
+			report_interval: default_drop_report_interval(),
+		}
+	}
+}
+
+impl Default for TraceFieldsConfig {
+	fn default() -> Self {
+		Self {
+			enabled: true,
+			trace_id: default_trace_id_field(),
+			span_id: default_span_id_field(),
+			sampled: default_sampled_field(),
 		}
 	}
 }
 
+impl Default for RedactionMode {
+	fn default() -> Self {
+		Self::Mask("[REDACTED]".to_owned())
+	}
+}
+
+impl Default for SyslogTransport {
+	fn default() -> Self {
+		Self::Unix(PathBuf::from("/dev/log"))
+	}
+}
+
 impl Default for ProviderConfig {
 	fn default() -> Self {
 		Self {
@@ -178,11 +887,81 @@ const fn default_level_filter() -> LevelFilter {
 	LevelFilter(tracing::level_filters::LevelFilter::INFO)
 }
 
+/// Default maximum number of stdout lines waiting to be written
+const fn default_buffered_lines() -> usize {
+	tracing_appender::non_blocking::DEFAULT_BUFFERED_LINES_LIMIT
+}
+
+/// Default interval at which the dropped stdout lines are reported
+const fn default_drop_report_interval() -> Seconds<Duration> {
+	Seconds(Duration::from_secs(60))
+}
+
+/// Default boundaries of the buckets of the request durations, recommended by
+/// the [HTTP semantic conventions](https://opentelemetry.io/docs/specs/semconv/http/http-metrics/#metric-httpserverrequestduration)
+fn default_duration_buckets() -> Vec<f64> {
+	vec![0.005, 0.01, 0.025, 0.05, 0.075, 0.1, 0.25, 0.5, 0.75, 1.0, 2.5, 5.0, 7.5, 10.0]
+}
+
+/// Default header holding the id of the requests
+fn default_request_id_header() -> Option<String> {
+	Some("x-request-id".to_owned())
+}
+
+/// Default header raising the level of a request
+fn default_debug_header() -> String {
+	"x-debug-log".to_owned()
+}
+
+/// Default header requesting the `Server-Timing` header
+fn default_server_timing_header() -> String {
+	"x-server-timing".to_owned()
+}
+
+/// Default level of the requests with a trusted debug header
+const fn default_debug_level() -> LevelFilter {
+	LevelFilter(tracing::level_filters::LevelFilter::DEBUG)
+}
+
+/// Default socket of the systemd journal
+fn default_journald_socket() -> PathBuf {
+	PathBuf::from("/run/systemd/journal/socket")
+}
+
+/// Default name of the field holding the trace id
+fn default_trace_id_field() -> String {
+	"trace_id".to_owned()
+}
+
+/// Default name of the field holding the span id
+fn default_span_id_field() -> String {
+	"span_id".to_owned()
+}
+
+/// Default name of the field holding whether the trace is sampled
+fn default_sampled_field() -> String {
+	"trace_sampled".to_owned()
+}
+
 /// Workaround for [serde-rs/serde#368](https://github.com/serde-rs/serde/issues/368)
 const fn true_() -> bool {
 	true
 }
 
+/// Builds a trace filter from the general level, the levels of the
+/// dependencies and the level of the crate
+fn build_filter(
+	general_level: LevelFilter,
+	dependencies_levels: &HashMap<String, LevelFilter>,
+	crate_name: &str,
+	level: LevelFilter,
+) -> String {
+	format!(
+		"{general_level},{}{crate_name}={level}",
+		build_dependencies_level_string(dependencies_levels)
+	)
+}
+
 /// Builds a string that configures the filter level of each dependency on the
 /// map
 fn build_dependencies_level_string(dependencies_levels: &HashMap<String, LevelFilter>) -> String {
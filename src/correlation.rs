@@ -0,0 +1,161 @@
+// SPDX-FileCopyrightText: 2025 Famedly GmbH (info@famedly.com)
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Log correlation
+//!
+//! Module reading the OpenTelemetry context the events are emitted in, so that
+//! the log formatters can link the records to their trace.
+use opentelemetry::{
+	Context,
+	trace::{SpanId, TraceContextExt as _, TraceId},
+};
+
+/// OpenTelemetry context of an event
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct EventTraceContext {
+	/// Id of the trace
+	pub(crate) trace_id: TraceId,
+	/// Id of the span
+	pub(crate) span_id: SpanId,
+	/// Whether the trace is sampled
+	pub(crate) sampled: bool,
+}
+
+/// OpenTelemetry context of the innermost traced span that is entered
+///
+/// The `OpenTelemetryLayer` attaches the context of the spans when they are
+/// entered, so this is `None` if exporting the traces is disabled.
+pub(crate) fn current_trace_context() -> Option<EventTraceContext> {
+	let context = Context::current();
+	let span_context = context.span().span_context().clone();
+	span_context.is_valid().then(|| EventTraceContext {
+		trace_id: span_context.trace_id(),
+		span_id: span_context.span_id(),
+		sampled: span_context.is_sampled(),
+	})
+}
+
+#[cfg(test)]
+mod tests {
+	#![allow(clippy::expect_used)]
+	use opentelemetry::trace::{SpanContext, TraceContextExt as _, TracerProvider as _};
+	use opentelemetry_sdk::trace::SdkTracerProvider;
+	use tracing_opentelemetry::OpenTelemetrySpanExt as _;
+	use tracing_subscriber::layer::SubscriberExt as _;
+
+	use crate::{
+		builder::BoxedLayer,
+		config::{LogFormat, StdoutLogsConfig, TraceFieldsConfig},
+		stdout::{self, tests::CapturedOutput},
+	};
+
+	/// Logs an event in a traced span, then in a child span, with the given
+	/// config, and returns the output and the context of the span
+	fn log_traced(config: &StdoutLogsConfig) -> (String, SpanContext) {
+		let output = CapturedOutput::default();
+		let tracer = SdkTracerProvider::builder().build().tracer("test");
+		let layers: Vec<BoxedLayer> = vec![
+			Box::new(tracing_opentelemetry::layer().with_tracer(tracer)),
+			stdout::layer(config, output.clone()),
+		];
+		let subscriber = tracing_subscriber::registry().with(layers);
+
+		let span_context = tracing::subscriber::with_default(subscriber, || {
+			let span = tracing::info_span!("request");
+			let _entered = span.enter();
+			tracing::info!("In request");
+			tracing::info_span!("child").in_scope(|| tracing::info!("In child"));
+			span.context().span().span_context().clone()
+		});
+		(output.contents(), span_context)
+	}
+
+	#[test]
+	fn test_gcp_trace_context() {
+		let (output, span_context) = log_traced(&StdoutLogsConfig {
+			format: LogFormat::Gcp,
+			gcp_project_id: Some("project".to_owned()),
+			..Default::default()
+		});
+
+		let records: Vec<serde_json::Value> =
+			output.lines().map(|line| serde_json::from_str(line).expect("Invalid JSON")).collect();
+		assert_eq!(records.len(), 2);
+		for record in &records {
+			assert_eq!(
+				record["logging.googleapis.com/trace"],
+				format!("projects/project/traces/{}", span_context.trace_id())
+			);
+			assert_eq!(record["logging.googleapis.com/trace_sampled"], true);
+		}
+		let span_id = span_context.span_id().to_string();
+		assert_eq!(records[0]["logging.googleapis.com/spanId"], span_id);
+		assert_ne!(records[1]["logging.googleapis.com/spanId"], span_id);
+	}
+
+	#[test]
+	fn test_text_trace_fields() {
+		for format in [LogFormat::Full, LogFormat::Compact, LogFormat::Logfmt] {
+			let (output, span_context) =
+				log_traced(&StdoutLogsConfig { format, ansi: false, ..Default::default() });
+			let first = output.lines().next().expect("Missing record");
+			assert!(
+				first.ends_with(&format!(
+					" trace_id={} span_id={} trace_sampled=true",
+					span_context.trace_id(),
+					span_context.span_id()
+				)),
+				"{output}"
+			);
+		}
+
+		let (output, span_context) =
+			log_traced(&StdoutLogsConfig { format: LogFormat::Pretty, ..Default::default() });
+		assert!(output.contains(&format!("trace_id: {}", span_context.trace_id())), "{output}");
+	}
+
+	#[test]
+	fn test_json_trace_fields() {
+		let (output, span_context) = log_traced(&StdoutLogsConfig {
+			format: LogFormat::Json,
+			trace_fields: TraceFieldsConfig {
+				trace_id: "trace.id".to_owned(),
+				span_id: "span.id".to_owned(),
+				sampled: "sampled".to_owned(),
+				..Default::default()
+			},
+			..Default::default()
+		});
+		let record: serde_json::Value =
+			serde_json::from_str(output.lines().next().expect("Missing record"))
+				.expect("Invalid JSON");
+		assert_eq!(record["trace.id"], span_context.trace_id().to_string());
+		assert_eq!(record["span.id"], span_context.span_id().to_string());
+		assert_eq!(record["sampled"], true);
+		assert_eq!(record["fields"]["message"], "In request");
+	}
+
+	#[test]
+	fn test_trace_fields_disabled() {
+		let (output, _) = log_traced(&StdoutLogsConfig {
+			trace_fields: TraceFieldsConfig { enabled: false, ..Default::default() },
+			..Default::default()
+		});
+		assert!(!output.contains("trace_id"), "{output}");
+	}
+
+	#[test]
+	fn test_no_trace_context_outside_spans() {
+		let output = CapturedOutput::default();
+		let config = StdoutLogsConfig { format: LogFormat::Ecs, ..Default::default() };
+		let layers: Vec<BoxedLayer> =
+			vec![Box::new(tracing_opentelemetry::layer()), stdout::layer(&config, output.clone())];
+		let subscriber = tracing_subscriber::registry().with(layers);
+		tracing::subscriber::with_default(subscriber, || tracing::info!("Not traced"));
+
+		let record: serde_json::Value =
+			serde_json::from_str(&output.contents()).expect("Invalid JSON");
+		assert!(record.get("trace.id").is_none());
+	}
+}
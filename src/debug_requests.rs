@@ -0,0 +1,163 @@
+// SPDX-FileCopyrightText: 2025 Famedly GmbH (info@famedly.com)
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Debug requests
+//!
+//! Module containing the filter raising the level of the events and spans
+//! within the requests marked for debugging, from the
+//! [`DebugHeaderConfig`](crate::config::DebugHeaderConfig).
+use tracing::{
+	Metadata, Subscriber,
+	span::{Attributes, Id, Record},
+	subscriber::Interest,
+};
+use tracing_subscriber::{
+	filter::LevelFilter,
+	layer::{Context, Filter},
+	registry::LookupSpan,
+};
+
+/// Target of the spans marking the requests for debugging
+pub(crate) const TARGET: &str = "telemetry::debug_request";
+
+/// Filter also enabling the events and spans up to a level when they are
+/// within a span marking a request for debugging
+///
+/// The marking spans are always enabled, so that the filtered layers see
+/// them.
+#[derive(Debug)]
+pub(crate) struct DebugScoped<F> {
+	/// Filter of the layer
+	inner: F,
+	/// Level within the marked requests, if debugging requests is enabled
+	level: Option<LevelFilter>,
+}
+
+impl<F> DebugScoped<F> {
+	/// Wraps the filter of a layer, leaving it unchanged without a level
+	pub(crate) const fn new(inner: F, level: Option<LevelFilter>) -> Self {
+		Self { inner, level }
+	}
+}
+
+impl<S, F> Filter<S> for DebugScoped<F>
+where
+	S: Subscriber + for<'a> LookupSpan<'a>,
+	F: Filter<S>,
+{
+	fn enabled(&self, metadata: &Metadata<'_>, cx: &Context<'_, S>) -> bool {
+		if self.inner.enabled(metadata, cx) {
+			return true;
+		}
+		let Some(level) = self.level else {
+			return false;
+		};
+		metadata.target() == TARGET
+			|| (*metadata.level() <= level
+				&& cx.lookup_current().is_some_and(|span| {
+					span.scope().any(|span| span.metadata().target() == TARGET)
+				}))
+	}
+
+	fn callsite_enabled(&self, metadata: &'static Metadata<'static>) -> Interest {
+		let interest = self.inner.callsite_enabled(metadata);
+		match self.level {
+			Some(level)
+				if !interest.is_always()
+					&& (*metadata.level() <= level || metadata.target() == TARGET) =>
+			{
+				Interest::sometimes()
+			}
+			_ => interest,
+		}
+	}
+
+	fn max_level_hint(&self) -> Option<LevelFilter> {
+		let hint = self.inner.max_level_hint();
+		match self.level {
+			Some(level) => hint.map(|hint| hint.max(level).max(LevelFilter::INFO)),
+			None => hint,
+		}
+	}
+
+	fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+		self.inner.on_new_span(attrs, id, ctx);
+	}
+
+	fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+		self.inner.on_record(id, values, ctx);
+	}
+
+	fn on_enter(&self, id: &Id, ctx: Context<'_, S>) {
+		self.inner.on_enter(id, ctx);
+	}
+
+	fn on_exit(&self, id: &Id, ctx: Context<'_, S>) {
+		self.inner.on_exit(id, ctx);
+	}
+
+	fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+		self.inner.on_close(id, ctx);
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	#![allow(clippy::expect_used)]
+	use std::str::FromStr as _;
+
+	use tracing_subscriber::{
+		EnvFilter, Layer as _, filter::LevelFilter, layer::SubscriberExt as _,
+	};
+
+	use super::{DebugScoped, TARGET};
+	use crate::{
+		config::{LogFormat, StdoutLogsConfig, Timestamp},
+		stdout::{self, tests::CapturedOutput},
+	};
+
+	#[test]
+	fn test_debug_scope() {
+		let output = CapturedOutput::default();
+		let config = StdoutLogsConfig {
+			format: LogFormat::Logfmt,
+			timestamp: Timestamp::None,
+			..Default::default()
+		};
+		let filter = EnvFilter::from_str("info").expect("Invalid filter");
+		let layer = stdout::layer(&config, output.clone())
+			.with_filter(DebugScoped::new(filter, Some(LevelFilter::DEBUG)));
+		let subscriber = tracing_subscriber::registry().with(layer);
+		tracing::subscriber::with_default(subscriber, || {
+			tracing::debug!("Outside");
+			tracing::info_span!(target: TARGET, "debug request").in_scope(|| {
+				tracing::debug_span!("handler").in_scope(|| {
+					tracing::debug!("Inside");
+					tracing::trace!("Too verbose");
+				});
+			});
+			tracing::debug!("After");
+		});
+
+		let logs = output.contents();
+		assert_eq!(logs.lines().count(), 1, "{logs}");
+		assert!(logs.contains("Inside"), "{logs}");
+	}
+
+	#[test]
+	fn test_without_level() {
+		let output = CapturedOutput::default();
+		let filter = EnvFilter::from_str("info").expect("Invalid filter");
+		let layer = stdout::layer(&StdoutLogsConfig::default(), output.clone())
+			.with_filter(DebugScoped::new(filter, None));
+		let subscriber = tracing_subscriber::registry().with(layer);
+		tracing::subscriber::with_default(subscriber, || {
+			tracing::info_span!(target: TARGET, "debug request").in_scope(|| {
+				tracing::debug!("Inside");
+			});
+		});
+
+		assert!(output.contents().is_empty());
+	}
+}
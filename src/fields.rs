@@ -0,0 +1,99 @@
+// SPDX-FileCopyrightText: 2025 Famedly GmbH (info@famedly.com)
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Event fields
+//!
+//! Module collecting the fields of the events and of their spans as strings,
+//! for the outputs encoding the records themselves instead of going through
+//! the fmt layer.
+use std::{fmt, marker::PhantomData};
+
+use tracing::{
+	Event, Subscriber,
+	field::{Field, Visit},
+	span::{Attributes, Id, Record},
+};
+use tracing_subscriber::{layer::Context, registry::LookupSpan};
+
+/// Fields of an event or a span
+#[derive(Debug, Default)]
+pub(crate) struct Fields {
+	/// Message of the event
+	pub(crate) message: Option<String>,
+	/// Other fields, in the order they are recorded
+	pub(crate) fields: Vec<(&'static str, String)>,
+}
+
+impl Visit for Fields {
+	fn record_str(&mut self, field: &Field, value: &str) {
+		if field.name() == "message" {
+			self.message = Some(value.to_owned());
+		} else {
+			self.fields.push((field.name(), value.to_owned()));
+		}
+	}
+
+	fn record_error(&mut self, field: &Field, value: &(dyn std::error::Error + 'static)) {
+		self.record_str(field, &value.to_string());
+	}
+
+	fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+		self.record_str(field, &format!("{value:?}"));
+	}
+}
+
+/// Fields of a span, stored in its extensions by the layer `L`
+///
+/// Each layer uses its own type, so that several of them can be enabled.
+struct SpanFields<L> {
+	/// Fields of the span
+	fields: Fields,
+	/// Layer storing the fields
+	layer: PhantomData<fn() -> L>,
+}
+
+/// Stores the fields of a new span for the layer `L`
+pub(crate) fn on_new_span<L: 'static, S>(attrs: &Attributes<'_>, id: &Id, ctx: &Context<'_, S>)
+where
+	S: Subscriber + for<'a> LookupSpan<'a>,
+{
+	let Some(span) = ctx.span(id) else { return };
+	let mut fields = Fields::default();
+	attrs.record(&mut fields);
+	span.extensions_mut().insert(SpanFields::<L> { fields, layer: PhantomData });
+}
+
+/// Adds the fields recorded later on a span for the layer `L`
+pub(crate) fn on_record<L: 'static, S>(id: &Id, values: &Record<'_>, ctx: &Context<'_, S>)
+where
+	S: Subscriber + for<'a> LookupSpan<'a>,
+{
+	let Some(span) = ctx.span(id) else { return };
+	if let Some(span_fields) = span.extensions_mut().get_mut::<SpanFields<L>>() {
+		values.record(&mut span_fields.fields);
+	}
+}
+
+/// Names of the spans in scope of an event, from the root, and the fields
+/// stored by the layer `L` for them, followed by the fields of the event
+pub(crate) fn event_fields<L: 'static, S>(
+	event: &Event<'_>,
+	ctx: &Context<'_, S>,
+) -> (Vec<&'static str>, Fields)
+where
+	S: Subscriber + for<'a> LookupSpan<'a>,
+{
+	let mut span_names = Vec::new();
+	let mut fields = Fields::default();
+	if let Some(scope) = ctx.event_scope(event) {
+		for span in scope.from_root() {
+			span_names.push(span.name());
+			if let Some(span_fields) = span.extensions().get::<SpanFields<L>>() {
+				fields.fields.extend(span_fields.fields.fields.iter().cloned());
+			}
+		}
+	}
+	event.record(&mut fields);
+	(span_names, fields)
+}
@@ -0,0 +1,214 @@
+// SPDX-FileCopyrightText: 2025 Famedly GmbH (info@famedly.com)
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! File logs
+//!
+//! Module building the non-blocking writer of the rotating log files from the
+//! [`FileLogsConfig`].
+use std::{
+	fs::{self, File},
+	io::{self, Write},
+	path::PathBuf,
+};
+
+use tracing_appender::{
+	non_blocking::{NonBlocking, NonBlockingBuilder, WorkerGuard},
+	rolling::{self, RollingFileAppender},
+};
+
+use crate::config::{FileLogsConfig, Rotation};
+
+/// Extension of the log files
+const LOG_FILE_SUFFIX: &str = "log";
+
+/// Builds the non-blocking writer of the log files. The logs are written until
+/// the guard is dropped.
+pub(crate) fn writer(
+	config: &FileLogsConfig,
+	service_name: &str,
+) -> io::Result<(NonBlocking, WorkerGuard)> {
+	let prefix = config.prefix.as_deref().unwrap_or(service_name);
+	let builder = NonBlockingBuilder::default().lossy(false).thread_name("telemetry-file-logs");
+
+	let rotation = match config.rotation {
+		Rotation::Hourly => rolling::Rotation::HOURLY,
+		Rotation::Daily => rolling::Rotation::DAILY,
+		Rotation::Never => rolling::Rotation::NEVER,
+		Rotation::Size(max_bytes) => {
+			let file = SizeRollingFile::new(
+				config.directory.clone(),
+				prefix.to_owned(),
+				max_bytes,
+				config.max_files,
+			)?;
+			return Ok(builder.finish(file));
+		}
+	};
+	let appender = RollingFileAppender::builder()
+		.rotation(rotation)
+		.filename_prefix(prefix)
+		.filename_suffix(LOG_FILE_SUFFIX)
+		.max_log_files(config.max_files.unwrap_or_default())
+		.build(&config.directory)
+		.map_err(io::Error::other)?;
+	Ok(builder.finish(appender))
+}
+
+/// Log file starting over when it reaches a maximum size
+///
+/// The logs are written to `<prefix>.log`, which is renamed to `<prefix>.1.log`
+/// when it's full, shifting the number of the previous files.
+#[derive(Debug)]
+struct SizeRollingFile {
+	/// Directory of the files
+	directory: PathBuf,
+	/// Prefix of the names of the files
+	prefix: String,
+	/// Size in bytes from which the file is rotated
+	max_bytes: u64,
+	/// Maximum number of files, including the current one
+	max_files: Option<usize>,
+	/// Current file
+	file: File,
+	/// Size of the current file
+	size: u64,
+}
+
+impl SizeRollingFile {
+	/// Opens the current log file, creating the directory if needed
+	fn new(
+		directory: PathBuf,
+		prefix: String,
+		max_bytes: u64,
+		max_files: Option<usize>,
+	) -> io::Result<Self> {
+		fs::create_dir_all(&directory)?;
+		let path = directory.join(format!("{prefix}.{LOG_FILE_SUFFIX}"));
+		let file = File::options().create(true).append(true).open(path)?;
+		let size = file.metadata()?.len();
+		Ok(Self { directory, prefix, max_bytes, max_files, file, size })
+	}
+
+	/// Path of the rotated file with the given number
+	fn rotated_path(&self, number: usize) -> PathBuf {
+		self.directory.join(format!("{}.{number}.{LOG_FILE_SUFFIX}", self.prefix))
+	}
+
+	/// Numbers of the rotated files, from the most recent
+	fn rotated_numbers(&self) -> io::Result<Vec<usize>> {
+		let mut numbers = Vec::new();
+		for entry in fs::read_dir(&self.directory)? {
+			let name = entry?.file_name();
+			let number = name
+				.to_str()
+				.and_then(|name| name.strip_prefix(&self.prefix)?.strip_prefix('.'))
+				.and_then(|name| name.strip_suffix(LOG_FILE_SUFFIX)?.strip_suffix('.'))
+				.and_then(|number| number.parse::<usize>().ok());
+			numbers.extend(number);
+		}
+		numbers.sort_unstable();
+		Ok(numbers)
+	}
+
+	/// Shifts the rotated files, deleting the ones over the limit, and starts a
+	/// new current file
+	fn rotate(&mut self) -> io::Result<()> {
+		self.file.flush()?;
+		let kept = self.max_files.map(|max_files| max_files.saturating_sub(1));
+		for number in self.rotated_numbers()?.into_iter().rev() {
+			if kept.is_some_and(|kept| number >= kept) {
+				fs::remove_file(self.rotated_path(number))?;
+			} else {
+				fs::rename(self.rotated_path(number), self.rotated_path(number + 1))?;
+			}
+		}
+
+		let path = self.directory.join(format!("{}.{LOG_FILE_SUFFIX}", self.prefix));
+		if kept != Some(0) {
+			fs::rename(&path, self.rotated_path(1))?;
+		}
+		self.file = File::options().create(true).write(true).truncate(true).open(path)?;
+		self.size = 0;
+		Ok(())
+	}
+}
+
+impl Write for SizeRollingFile {
+	fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+		if self.size > 0 && self.size.saturating_add(buf.len() as u64) > self.max_bytes {
+			self.rotate()?;
+		}
+		let written = self.file.write(buf)?;
+		self.size = self.size.saturating_add(written as u64);
+		Ok(written)
+	}
+
+	fn flush(&mut self) -> io::Result<()> {
+		self.file.flush()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	#![allow(clippy::expect_used)]
+	use std::{collections::HashMap, fs, io::Write as _};
+
+	use super::SizeRollingFile;
+	use crate::{
+		build_otel,
+		config::{FileLogsConfig, LogFormat, OtelConfig, Rotation, Timestamp},
+	};
+
+	/// Config writing the logs of the crate to the directory
+	fn file_config(directory: &std::path::Path) -> FileLogsConfig {
+		FileLogsConfig {
+			enabled: true,
+			level: tracing::level_filters::LevelFilter::DEBUG.into(),
+			general_level: tracing::level_filters::LevelFilter::OFF.into(),
+			dependencies_levels: HashMap::new(),
+			format: LogFormat::Logfmt,
+			timestamp: Timestamp::None,
+			directory: directory.to_owned(),
+			prefix: Some("test".to_owned()),
+			rotation: Rotation::Never,
+			max_files: None,
+		}
+	}
+
+	#[test]
+	fn test_size_rotation() {
+		let directory = tempfile::tempdir().expect("Error creating directory");
+		let mut file =
+			SizeRollingFile::new(directory.path().to_owned(), "test".to_owned(), 10, Some(3))
+				.expect("Error opening file");
+		for line in ["first\n", "second\n", "third\n", "fourth\n"] {
+			file.write_all(line.as_bytes()).expect("Error writing");
+		}
+
+		let read = |name: &str| fs::read_to_string(directory.path().join(name)).ok();
+		assert_eq!(read("test.log").as_deref(), Some("fourth\n"));
+		assert_eq!(read("test.1.log").as_deref(), Some("third\n"));
+		assert_eq!(read("test.2.log").as_deref(), Some("second\n"));
+		assert_eq!(read("test.3.log"), None);
+	}
+
+	#[test]
+	fn test_file_logs() {
+		let directory = tempfile::tempdir().expect("Error creating directory");
+		let config = OtelConfig { file: Some(file_config(directory.path())), ..Default::default() };
+		let (subscriber, guard) = build_otel!(&config).expect("Error initializing Otel");
+		tracing::subscriber::with_default(subscriber, || {
+			tracing::debug!(user = "alice", "Logged to file");
+			tracing::trace!("Filtered out");
+		});
+		drop(guard);
+
+		let logs = fs::read_to_string(directory.path().join("test.log")).expect("Missing log file");
+		assert_eq!(
+			logs,
+			"level=debug target=rust_telemetry::file::tests thread=file::tests::test_file_logs \
+			 msg=\"Logged to file\" user=alice\n"
+		);
+	}
+}
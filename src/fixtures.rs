@@ -0,0 +1,117 @@
+// SPDX-FileCopyrightText: 2025 Famedly GmbH (info@famedly.com)
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Test fixtures
+//!
+//! Module containing the in-memory exporters of the spans and metrics shared
+//! by the tests.
+#![allow(clippy::expect_used)]
+// Some of the fixtures are only used by the tests of the HTTP layers
+#![cfg_attr(not(feature = "axum"), allow(dead_code))]
+use opentelemetry::{
+	Value,
+	metrics::{Meter, MeterProvider as _},
+	trace::TracerProvider as _,
+};
+use opentelemetry_sdk::{
+	metrics::{
+		InMemoryMetricExporter, PeriodicReader, SdkMeterProvider,
+		data::{Metric, ResourceMetrics, ScopeMetrics},
+	},
+	trace::{InMemorySpanExporter, SdkTracer, SdkTracerProvider, SpanData},
+};
+use tracing::{Subscriber, subscriber::DefaultGuard};
+use tracing_opentelemetry::OpenTelemetryLayer;
+use tracing_subscriber::{layer::SubscriberExt as _, registry::LookupSpan};
+
+/// Spans of a test, exported in memory
+#[derive(Debug)]
+pub(crate) struct TestSpans {
+	/// Exporter of the finished spans
+	exporter: InMemorySpanExporter,
+	/// Provider of the tracer of the layers
+	provider: SdkTracerProvider,
+}
+
+impl TestSpans {
+	/// Sets up the exporter
+	pub(crate) fn new() -> Self {
+		let exporter = InMemorySpanExporter::default();
+		let provider = SdkTracerProvider::builder().with_simple_exporter(exporter.clone()).build();
+		Self { exporter, provider }
+	}
+
+	/// Layer exporting the spans
+	pub(crate) fn layer<S>(&self) -> OpenTelemetryLayer<S, SdkTracer>
+	where
+		S: Subscriber + for<'a> LookupSpan<'a>,
+	{
+		tracing_opentelemetry::layer().with_tracer(self.provider.tracer("test"))
+	}
+
+	/// Sets a subscriber exporting the spans as the default of the thread
+	pub(crate) fn set_default(&self) -> DefaultGuard {
+		tracing::subscriber::set_default(tracing_subscriber::registry().with(self.layer()))
+	}
+
+	/// Spans finished so far
+	pub(crate) fn finished(&self) -> Vec<SpanData> {
+		self.exporter.get_finished_spans().expect("Missing spans")
+	}
+}
+
+/// Value of an attribute of a span
+pub(crate) fn attribute(span: &SpanData, key: &str) -> Option<Value> {
+	span.attributes.iter().find(|kv| kv.key.as_str() == key).map(|kv| kv.value.clone())
+}
+
+/// Metrics of a test, exported in memory
+#[derive(Debug)]
+pub(crate) struct TestMetrics {
+	/// Exporter of the collected metrics
+	exporter: InMemoryMetricExporter,
+	/// Provider of the meter of the layers
+	provider: SdkMeterProvider,
+}
+
+impl TestMetrics {
+	/// Sets up the exporter
+	pub(crate) fn new() -> Self {
+		let exporter = InMemoryMetricExporter::default();
+		let provider = SdkMeterProvider::builder()
+			.with_reader(PeriodicReader::builder(exporter.clone()).build())
+			.build();
+		Self { exporter, provider }
+	}
+
+	/// Meter of the layers
+	pub(crate) fn meter(&self) -> Meter {
+		self.provider.meter("test")
+	}
+
+	/// Provider of the meter
+	pub(crate) const fn provider(&self) -> &SdkMeterProvider {
+		&self.provider
+	}
+
+	/// Metrics exported so far
+	pub(crate) fn exported(&self) -> Vec<ResourceMetrics> {
+		self.exporter.get_finished_metrics().expect("Missing metrics")
+	}
+
+	/// Metrics exported after collecting the pending ones
+	pub(crate) fn flushed(&self) -> Vec<ResourceMetrics> {
+		self.provider.force_flush().expect("Error flushing metrics");
+		self.exported()
+	}
+}
+
+/// Metrics of all the resources and scopes
+pub(crate) fn flat_metrics(metrics: &[ResourceMetrics]) -> Vec<&Metric> {
+	metrics
+		.iter()
+		.flat_map(ResourceMetrics::scope_metrics)
+		.flat_map(ScopeMetrics::metrics)
+		.collect()
+}
@@ -0,0 +1,188 @@
+// SPDX-FileCopyrightText: 2025 Famedly GmbH (info@famedly.com)
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Log formats
+//!
+//! Module containing the event and field formatters of the log formats that
+//! `tracing-subscriber` doesn't provide.
+mod json;
+mod timer;
+mod trace_fields;
+
+use std::fmt;
+
+use tracing::{Event, Level, Subscriber, field::Field};
+use tracing_subscriber::{
+	field::{RecordFields, Visit},
+	fmt::{
+		FmtContext, FormatEvent, FormatFields, FormattedFields, format::Writer,
+		time::FormatTime as _,
+	},
+	registry::LookupSpan,
+};
+
+pub(crate) use self::{
+	json::{LogSchema, VendorJson},
+	timer::Timer,
+	trace_fields::{RecordShape, WithTraceFields},
+};
+
+/// Formats events as [logfmt](https://brandur.org/logfmt) key-value pairs
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Logfmt {
+	/// Writes the timestamps of the events
+	pub(crate) timer: Timer,
+	/// Includes the target of the events
+	pub(crate) target: bool,
+	/// Includes the source file and line of the events
+	pub(crate) file_line: bool,
+	/// Includes the id of the thread emitting the events
+	pub(crate) thread_ids: bool,
+}
+
+impl<S, N> FormatEvent<S, N> for Logfmt
+where
+	S: Subscriber + for<'a> LookupSpan<'a>,
+	N: for<'a> FormatFields<'a> + 'static,
+{
+	fn format_event(
+		&self,
+		ctx: &FmtContext<'_, S, N>,
+		mut writer: Writer<'_>,
+		event: &Event<'_>,
+	) -> fmt::Result {
+		let metadata = event.metadata();
+
+		if self.timer.enabled() {
+			writer.write_str("ts=")?;
+			self.timer.format_time(&mut writer)?;
+			writer.write_char(' ')?;
+		}
+		write!(writer, "level={}", level_str(*metadata.level()))?;
+		if self.target {
+			write_pair(&mut writer, "target", metadata.target())?;
+		}
+		if self.file_line {
+			if let Some(file) = metadata.file() {
+				write_pair(&mut writer, "file", file)?;
+			}
+			if let Some(line) = metadata.line() {
+				write!(writer, " line={line}")?;
+			}
+		}
+		let thread = std::thread::current();
+		if let Some(name) = thread.name() {
+			write_pair(&mut writer, "thread", name)?;
+		}
+		if self.thread_ids {
+			write_pair(&mut writer, "thread_id", &format!("{:?}", thread.id()))?;
+		}
+
+		if let Some(scope) = ctx.event_scope() {
+			let mut spans = String::new();
+			for span in scope.from_root() {
+				if !spans.is_empty() {
+					spans.push('>');
+				}
+				spans.push_str(span.name());
+				let extensions = span.extensions();
+				if let Some(fields) = extensions.get::<FormattedFields<N>>()
+					&& !fields.is_empty()
+				{
+					write!(writer, " {fields}")?;
+				}
+			}
+			write_pair(&mut writer, "span", &spans)?;
+		}
+
+		writer.write_char(' ')?;
+		ctx.format_fields(writer.by_ref(), event)?;
+		writeln!(writer)
+	}
+}
+
+/// Formats fields as [logfmt](https://brandur.org/logfmt) key-value pairs
+#[derive(Debug, Clone, Copy, Default)]
+pub(crate) struct LogfmtFields;
+
+impl<'writer> FormatFields<'writer> for LogfmtFields {
+	fn format_fields<R: RecordFields>(&self, writer: Writer<'writer>, fields: R) -> fmt::Result {
+		let mut visitor = LogfmtVisitor { writer, first: true, result: Ok(()) };
+		fields.record(&mut visitor);
+		visitor.result
+	}
+}
+
+/// Visitor writing the fields as logfmt key-value pairs
+struct LogfmtVisitor<'writer> {
+	/// Output of the pairs
+	writer: Writer<'writer>,
+	/// Whether no pair was written yet
+	first: bool,
+	/// Result of the writes
+	result: fmt::Result,
+}
+
+impl Visit for LogfmtVisitor<'_> {
+	fn record_str(&mut self, field: &Field, value: &str) {
+		if self.result.is_err() {
+			return;
+		}
+		let key = match field.name() {
+			"message" => "msg",
+			name => name,
+		};
+		if !self.first {
+			self.result = self.writer.write_char(' ');
+		}
+		self.first = false;
+		self.result = self.result.and_then(|()| {
+			self.writer.write_str(key)?;
+			self.writer.write_char('=')?;
+			write_value(&mut self.writer, value)
+		});
+	}
+
+	fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+		self.record_str(field, &format!("{value:?}"));
+	}
+}
+
+/// Writes ` key=value`, quoting the value if needed
+fn write_pair(writer: &mut Writer<'_>, key: &str, value: &str) -> fmt::Result {
+	write!(writer, " {key}=")?;
+	write_value(writer, value)
+}
+
+/// Writes a logfmt value, quoting and escaping it if needed
+fn write_value(writer: &mut Writer<'_>, value: &str) -> fmt::Result {
+	let needs_quotes = value.is_empty()
+		|| value.chars().any(|c| c.is_whitespace() || c.is_control() || c == '=' || c == '"');
+	if !needs_quotes {
+		return writer.write_str(value);
+	}
+	writer.write_char('"')?;
+	for c in value.chars() {
+		match c {
+			'"' => writer.write_str("\\\"")?,
+			'\\' => writer.write_str("\\\\")?,
+			'\n' => writer.write_str("\\n")?,
+			'\r' => writer.write_str("\\r")?,
+			'\t' => writer.write_str("\\t")?,
+			c => writer.write_char(c)?,
+		}
+	}
+	writer.write_char('"')
+}
+
+/// Lowercase name of a level
+const fn level_str(level: Level) -> &'static str {
+	match level {
+		Level::TRACE => "trace",
+		Level::DEBUG => "debug",
+		Level::INFO => "info",
+		Level::WARN => "warn",
+		_ => "error",
+	}
+}
@@ -0,0 +1,290 @@
+// SPDX-FileCopyrightText: 2025 Famedly GmbH (info@famedly.com)
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Vendor JSON formats
+//!
+//! Module containing the event formatter emitting the JSON records expected by
+//! the log pipelines: Elastic Common Schema, Google Cloud structured logging
+//! and OTLP log records.
+use std::{
+	fmt,
+	time::{SystemTime, UNIX_EPOCH},
+};
+
+use chrono::{DateTime, SecondsFormat, Utc};
+use serde_json::{Map, Value, json};
+use tracing::{Event, Level, Subscriber, field::Field};
+use tracing_subscriber::{
+	field::Visit,
+	fmt::{FmtContext, FormatEvent, FormatFields, FormattedFields, format::Writer},
+	registry::LookupSpan,
+};
+
+use crate::correlation::{EventTraceContext, current_trace_context};
+
+/// Version of the Elastic Common Schema the records follow
+const ECS_VERSION: &str = "8.11.0";
+
+/// Schema of the JSON records
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum LogSchema {
+	/// Elastic Common Schema
+	Ecs,
+	/// Google Cloud structured logging
+	Gcp,
+	/// OTLP log record
+	OtlpJson,
+}
+
+/// Formats events as JSON records following a vendor schema
+///
+/// The fields of the spans are read as JSON, so the layer must use
+/// [`JsonFields`](tracing_subscriber::fmt::format::JsonFields).
+#[derive(Debug, Clone)]
+pub(crate) struct VendorJson {
+	/// Schema of the records
+	pub(crate) schema: LogSchema,
+	/// Google Cloud project of the traces
+	pub(crate) gcp_project_id: Option<String>,
+}
+
+/// Data of an event, independent of the schema
+struct Record<'a> {
+	/// Time of the event
+	time: SystemTime,
+	/// Level of the event
+	level: Level,
+	/// Target of the event
+	target: &'a str,
+	/// Source file of the event
+	file: Option<&'a str>,
+	/// Source line of the event
+	line: Option<u32>,
+	/// Name of the thread emitting the event
+	thread: Option<String>,
+	/// Message of the event
+	message: Option<Value>,
+	/// Fields of the spans in scope, then of the event
+	fields: Map<String, Value>,
+	/// OpenTelemetry context of the event
+	trace_context: Option<EventTraceContext>,
+}
+
+impl<S, N> FormatEvent<S, N> for VendorJson
+where
+	S: Subscriber + for<'a> LookupSpan<'a>,
+	N: for<'a> FormatFields<'a> + 'static,
+{
+	fn format_event(
+		&self,
+		ctx: &FmtContext<'_, S, N>,
+		mut writer: Writer<'_>,
+		event: &Event<'_>,
+	) -> fmt::Result {
+		let metadata = event.metadata();
+
+		let mut fields = Map::new();
+		if let Some(scope) = ctx.event_scope() {
+			for span in scope.from_root() {
+				let extensions = span.extensions();
+				if let Some(formatted) = extensions.get::<FormattedFields<N>>()
+					&& let Ok(Value::Object(span_fields)) =
+						serde_json::from_str::<Value>(&formatted.fields)
+				{
+					fields.extend(span_fields);
+				}
+			}
+		}
+		let mut visitor = JsonVisitor(Map::new());
+		event.record(&mut visitor);
+		fields.extend(visitor.0);
+
+		let record = Record {
+			time: SystemTime::now(),
+			level: *metadata.level(),
+			target: metadata.target(),
+			file: metadata.file(),
+			line: metadata.line(),
+			thread: std::thread::current().name().map(str::to_owned),
+			message: fields.remove("message"),
+			fields,
+			trace_context: current_trace_context(),
+		};
+		let value = match self.schema {
+			LogSchema::Ecs => ecs(record),
+			LogSchema::Gcp => gcp(record, self.gcp_project_id.as_deref()),
+			LogSchema::OtlpJson => otlp_json(record),
+		};
+
+		let json = serde_json::to_string(&value).map_err(|_| fmt::Error)?;
+		writer.write_str(&json)?;
+		writeln!(writer)
+	}
+}
+
+/// Builds an Elastic Common Schema record
+fn ecs(record: Record<'_>) -> Value {
+	let mut object = record.fields;
+	object.insert("@timestamp".to_owned(), rfc3339(record.time).into());
+	object.insert("ecs.version".to_owned(), ECS_VERSION.into());
+	object.insert("log.level".to_owned(), record.level.as_str().into());
+	object.insert("log.logger".to_owned(), record.target.into());
+	if let Some(message) = record.message {
+		object.insert("message".to_owned(), message);
+	}
+	if let Some(file) = record.file {
+		object.insert("log.origin.file.name".to_owned(), file.into());
+	}
+	if let Some(line) = record.line {
+		object.insert("log.origin.file.line".to_owned(), line.into());
+	}
+	if let Some(thread) = record.thread {
+		object.insert("process.thread.name".to_owned(), thread.into());
+	}
+	if let Some(trace_context) = record.trace_context {
+		object.insert("trace.id".to_owned(), trace_context.trace_id.to_string().into());
+		object.insert("span.id".to_owned(), trace_context.span_id.to_string().into());
+	}
+	Value::Object(object)
+}
+
+/// Builds a Google Cloud structured logging record
+fn gcp(record: Record<'_>, project_id: Option<&str>) -> Value {
+	let severity = match record.level {
+		Level::TRACE | Level::DEBUG => "DEBUG",
+		Level::INFO => "INFO",
+		Level::WARN => "WARNING",
+		_ => "ERROR",
+	};
+	let mut source_location = Map::new();
+	source_location.insert("function".to_owned(), record.target.into());
+	if let Some(file) = record.file {
+		source_location.insert("file".to_owned(), file.into());
+	}
+	if let Some(line) = record.line {
+		source_location.insert("line".to_owned(), line.to_string().into());
+	}
+
+	let mut object = record.fields;
+	object.insert("timestamp".to_owned(), rfc3339(record.time).into());
+	object.insert("severity".to_owned(), severity.into());
+	object.insert("logging.googleapis.com/sourceLocation".to_owned(), source_location.into());
+	if let Some(message) = record.message {
+		object.insert("message".to_owned(), message);
+	}
+	if let Some(thread) = record.thread {
+		object.insert("thread".to_owned(), thread.into());
+	}
+	if let Some(trace_context) = record.trace_context {
+		let trace = match project_id {
+			Some(project_id) => format!("projects/{project_id}/traces/{}", trace_context.trace_id),
+			None => trace_context.trace_id.to_string(),
+		};
+		object.insert("logging.googleapis.com/trace".to_owned(), trace.into());
+		object.insert(
+			"logging.googleapis.com/spanId".to_owned(),
+			trace_context.span_id.to_string().into(),
+		);
+		object.insert(
+			"logging.googleapis.com/trace_sampled".to_owned(),
+			trace_context.sampled.into(),
+		);
+	}
+	Value::Object(object)
+}
+
+/// Builds the JSON encoding of an OTLP log record
+fn otlp_json(record: Record<'_>) -> Value {
+	let severity_number = match record.level {
+		Level::TRACE => 1,
+		Level::DEBUG => 5,
+		Level::INFO => 9,
+		Level::WARN => 13,
+		_ => 17,
+	};
+	let time = record.time.duration_since(UNIX_EPOCH).unwrap_or_default().as_nanos().to_string();
+
+	let mut attributes = vec![attribute("target", record.target.into())];
+	if let Some(file) = record.file {
+		attributes.push(attribute("code.file.path", file.into()));
+	}
+	if let Some(line) = record.line {
+		attributes.push(attribute("code.line.number", line.into()));
+	}
+	if let Some(thread) = record.thread {
+		attributes.push(attribute("thread.name", thread.into()));
+	}
+	attributes.extend(record.fields.into_iter().map(|(key, value)| attribute(&key, value)));
+
+	let mut object = Map::new();
+	object.insert("timeUnixNano".to_owned(), time.clone().into());
+	object.insert("observedTimeUnixNano".to_owned(), time.into());
+	object.insert("severityNumber".to_owned(), severity_number.into());
+	object.insert("severityText".to_owned(), record.level.as_str().into());
+	if let Some(message) = record.message {
+		object.insert("body".to_owned(), any_value(message));
+	}
+	object.insert("attributes".to_owned(), attributes.into());
+	if let Some(trace_context) = record.trace_context {
+		object.insert("traceId".to_owned(), trace_context.trace_id.to_string().into());
+		object.insert("spanId".to_owned(), trace_context.span_id.to_string().into());
+		object.insert("flags".to_owned(), u8::from(trace_context.sampled).into());
+	}
+	Value::Object(object)
+}
+
+/// Builds an OTLP key-value attribute
+fn attribute(key: &str, value: Value) -> Value {
+	json!({ "key": key, "value": any_value(value) })
+}
+
+/// Converts a field value to an OTLP `AnyValue`, encoding the integers as
+/// strings like the protobuf JSON mapping does
+fn any_value(value: Value) -> Value {
+	match value {
+		Value::Bool(value) => json!({ "boolValue": value }),
+		Value::Number(number) if number.is_f64() => json!({ "doubleValue": number }),
+		Value::Number(number) => json!({ "intValue": number.to_string() }),
+		Value::String(value) => json!({ "stringValue": value }),
+		value => json!({ "stringValue": value.to_string() }),
+	}
+}
+
+/// Formats a time as RFC 3339, with nanoseconds
+fn rfc3339(time: SystemTime) -> String {
+	DateTime::<Utc>::from(time).to_rfc3339_opts(SecondsFormat::Nanos, true)
+}
+
+/// Visitor collecting the fields as JSON values
+struct JsonVisitor(Map<String, Value>);
+
+impl Visit for JsonVisitor {
+	fn record_f64(&mut self, field: &Field, value: f64) {
+		self.0.insert(field.name().to_owned(), value.into());
+	}
+
+	fn record_i64(&mut self, field: &Field, value: i64) {
+		self.0.insert(field.name().to_owned(), value.into());
+	}
+
+	fn record_u64(&mut self, field: &Field, value: u64) {
+		self.0.insert(field.name().to_owned(), value.into());
+	}
+
+	fn record_bool(&mut self, field: &Field, value: bool) {
+		self.0.insert(field.name().to_owned(), value.into());
+	}
+
+	fn record_str(&mut self, field: &Field, value: &str) {
+		self.0.insert(field.name().to_owned(), value.into());
+	}
+
+	fn record_error(&mut self, field: &Field, value: &(dyn std::error::Error + 'static)) {
+		self.0.insert(field.name().to_owned(), value.to_string().into());
+	}
+
+	fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+		self.0.insert(field.name().to_owned(), format!("{value:?}").into());
+	}
+}
@@ -0,0 +1,61 @@
+// SPDX-FileCopyrightText: 2025 Famedly GmbH (info@famedly.com)
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Timestamps
+//!
+//! Module containing the timer writing the timestamps of the logs in the
+//! configured [`Timestamp`] format.
+use std::{fmt, time::Duration};
+
+use chrono::{Local, Utc};
+use tracing_subscriber::fmt::{format::Writer, time::FormatTime};
+
+use crate::config::Timestamp;
+
+/// Writes the current time in the configured format
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Timer(pub(crate) Timestamp);
+
+impl Timer {
+	/// Whether the timer writes timestamps
+	pub(crate) fn enabled(self) -> bool {
+		self.0 != Timestamp::None
+	}
+
+	/// Writes the current time
+	fn write(self, writer: &mut impl fmt::Write) -> fmt::Result {
+		match self.0 {
+			Timestamp::Utc => write!(writer, "{}", Utc::now().format("%Y-%m-%dT%H:%M:%S%.6fZ")),
+			Timestamp::Local => {
+				write!(writer, "{}", Local::now().format("%Y-%m-%dT%H:%M:%S%.6f%:z"))
+			}
+			Timestamp::Rfc3339Nanos => {
+				write!(writer, "{}", Utc::now().format("%Y-%m-%dT%H:%M:%S%.9fZ"))
+			}
+			Timestamp::EpochMillis => write!(writer, "{}", Utc::now().timestamp_millis()),
+			Timestamp::None => Ok(()),
+		}
+	}
+}
+
+impl FormatTime for Timer {
+	fn format_time(&self, writer: &mut Writer<'_>) -> fmt::Result {
+		self.write(writer)
+	}
+}
+
+impl tracing_tree::time::FormatTime for Timer {
+	fn format_time(&self, writer: &mut impl fmt::Write) -> fmt::Result {
+		self.write(writer)
+	}
+
+	fn style_timestamp(
+		&self,
+		_ansi: bool,
+		_elapsed: Duration,
+		_writer: &mut impl fmt::Write,
+	) -> fmt::Result {
+		Ok(())
+	}
+}
@@ -0,0 +1,103 @@
+// SPDX-FileCopyrightText: 2025 Famedly GmbH (info@famedly.com)
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Trace fields
+//!
+//! Module containing the event formatter adding the OpenTelemetry context of
+//! the events to the records of the text and JSON formats.
+use std::fmt;
+
+use tracing::{Event, Subscriber};
+use tracing_subscriber::{
+	fmt::{FmtContext, FormatEvent, FormatFields, format::Writer},
+	registry::LookupSpan,
+};
+
+use crate::{config::TraceFieldsConfig, correlation::current_trace_context};
+
+/// Shape of the records the fields are added to
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum RecordShape {
+	/// Single line of `key=value` pairs
+	Line,
+	/// Multi-line record of the pretty format, followed by an empty line
+	Pretty,
+	/// JSON object
+	Json,
+}
+
+/// Formats events with another formatter, then adds the trace id, span id and
+/// sampled flag of the event to the record
+#[derive(Debug, Clone)]
+pub(crate) struct WithTraceFields<F> {
+	/// Formatter of the records
+	inner: F,
+	/// Names of the fields
+	fields: TraceFieldsConfig,
+	/// Shape of the records written by the inner formatter
+	shape: RecordShape,
+}
+
+impl<F> WithTraceFields<F> {
+	/// Adds the configured fields to the records of the formatter
+	pub(crate) fn new(fields: &TraceFieldsConfig, inner: F, shape: RecordShape) -> Self {
+		Self { inner, fields: fields.clone(), shape }
+	}
+}
+
+impl<S, N, F> FormatEvent<S, N> for WithTraceFields<F>
+where
+	S: Subscriber + for<'a> LookupSpan<'a>,
+	N: for<'a> FormatFields<'a> + 'static,
+	F: FormatEvent<S, N>,
+{
+	fn format_event(
+		&self,
+		ctx: &FmtContext<'_, S, N>,
+		mut writer: Writer<'_>,
+		event: &Event<'_>,
+	) -> fmt::Result {
+		let Some(trace_context) = current_trace_context().filter(|_| self.fields.enabled) else {
+			return self.inner.format_event(ctx, writer, event);
+		};
+
+		let mut record = String::new();
+		self.inner.format_event(ctx, Writer::new(&mut record), event)?;
+		let record = record.trim_end_matches('\n');
+		let TraceFieldsConfig { trace_id, span_id, sampled, .. } = &self.fields;
+		match self.shape {
+			RecordShape::Line => {
+				writer.write_str(record)?;
+				write!(
+					writer,
+					" {trace_id}={} {span_id}={} {sampled}={}",
+					trace_context.trace_id, trace_context.span_id, trace_context.sampled
+				)?;
+			}
+			RecordShape::Pretty => {
+				writeln!(writer, "{record}")?;
+				writeln!(
+					writer,
+					"    with {trace_id}: {}, {span_id}: {}, {sampled}: {}",
+					trace_context.trace_id, trace_context.span_id, trace_context.sampled
+				)?;
+			}
+			RecordShape::Json => {
+				let object = record.strip_suffix('}').ok_or(fmt::Error)?;
+				let key = |name: &str| serde_json::to_string(name).map_err(|_| fmt::Error);
+				write!(
+					writer,
+					r#"{object},{}:"{}",{}:"{}",{}:{}}}"#,
+					key(trace_id)?,
+					trace_context.trace_id,
+					key(span_id)?,
+					trace_context.span_id,
+					key(sampled)?,
+					trace_context.sampled
+				)?;
+			}
+		}
+		writeln!(writer)
+	}
+}
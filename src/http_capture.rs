@@ -0,0 +1,147 @@
+// SPDX-FileCopyrightText: 2025 Famedly GmbH (info@famedly.com)
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! HTTP capture
+//!
+//! Module recording the allowed headers and the client address of the HTTP
+//! requests in their spans, from the [`HttpCaptureConfig`].
+#[cfg(feature = "axum")]
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+use http::{HeaderMap, HeaderName, header};
+use opentelemetry::{Array, StringValue, Value};
+use tracing::Span;
+use tracing_opentelemetry::OpenTelemetrySpanExt as _;
+
+use crate::{OtelInitError, config::HttpCaptureConfig, redaction::redact_span_value};
+#[cfg(feature = "axum")]
+use crate::{
+	config::ClientAddressMode,
+	redaction::{HASH_LEN, hmac_hex},
+};
+
+/// Headers that are never recorded, as they hold credentials
+const SENSITIVE_HEADERS: [HeaderName; 4] =
+	[header::AUTHORIZATION, header::PROXY_AUTHORIZATION, header::COOKIE, header::SET_COOKIE];
+
+/// Headers recorded in the spans, with their attribute names
+#[derive(Debug, Clone, Default)]
+pub(crate) struct HeaderCapture {
+	/// Request headers and their `http.request.header.<name>` attributes
+	request: Vec<(HeaderName, String)>,
+	/// Response headers and their `http.response.header.<name>` attributes
+	response: Vec<(HeaderName, String)>,
+}
+
+impl HeaderCapture {
+	/// Parses the allowed headers, failing on invalid or sensitive ones
+	pub(crate) fn new(config: &HttpCaptureConfig) -> Result<Self, OtelInitError> {
+		let parse = |headers: &[String], prefix: &str| {
+			headers
+				.iter()
+				.map(|name| {
+					let header = HeaderName::try_from(name.as_str())?;
+					if SENSITIVE_HEADERS.contains(&header) {
+						return Err(OtelInitError::SensitiveHeader(header.to_string()));
+					}
+					let attribute = format!("{prefix}.{header}");
+					Ok((header, attribute))
+				})
+				.collect::<Result<Vec<_>, OtelInitError>>()
+		};
+		Ok(Self {
+			request: parse(&config.request_headers, "http.request.header")?,
+			response: parse(&config.response_headers, "http.response.header")?,
+		})
+	}
+
+	/// Whether the request header is allowed
+	#[cfg(feature = "axum")]
+	pub(crate) fn captures_request(&self, header: &HeaderName) -> bool {
+		self.request.iter().any(|(name, _)| name == header)
+	}
+
+	/// Records the allowed request headers in the span
+	pub(crate) fn record_request(&self, span: &Span, headers: &HeaderMap) {
+		record(&self.request, span, headers);
+	}
+
+	/// Records the allowed response headers in the span
+	pub(crate) fn record_response(&self, span: &Span, headers: &HeaderMap) {
+		record(&self.response, span, headers);
+	}
+}
+
+/// Records the values of the headers present in the map as string arrays, as
+/// a header can be repeated
+fn record(captured: &[(HeaderName, String)], span: &Span, headers: &HeaderMap) {
+	for (header, attribute) in captured {
+		let values: Vec<StringValue> = headers
+			.get_all(header)
+			.iter()
+			.filter_map(|value| value.to_str().ok())
+			.map(|value| StringValue::from(redact_span_value(span, attribute, value.to_owned())))
+			.collect();
+		if !values.is_empty() {
+			span.set_attribute(attribute.clone(), Value::Array(Array::String(values)));
+		}
+	}
+}
+
+/// Value of the `client.address` attribute in the given mode, if recorded
+#[cfg(feature = "axum")]
+pub(crate) fn client_address(mode: &ClientAddressMode, address: IpAddr) -> Option<String> {
+	let address = address.to_canonical();
+	match mode {
+		ClientAddressMode::Omit => None,
+		ClientAddressMode::Full => Some(address.to_string()),
+		ClientAddressMode::Truncate => Some(match address {
+			IpAddr::V4(v4) => Ipv4Addr::from_bits(v4.to_bits() & !0xff).to_string(),
+			IpAddr::V6(v6) => Ipv6Addr::from_bits(v6.to_bits() & !u128::from(u64::MAX)).to_string(),
+		}),
+		ClientAddressMode::Hash(key) => {
+			Some(format!("hash:{}", hmac_hex(key, &address.to_string(), HASH_LEN)))
+		}
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	#![allow(clippy::expect_used)]
+	use super::HeaderCapture;
+	use crate::{OtelInitError, config::HttpCaptureConfig};
+
+	#[test]
+	fn test_sensitive_headers() {
+		for header in ["Authorization", "cookie", "Set-Cookie"] {
+			let config = HttpCaptureConfig {
+				request_headers: vec!["user-agent".to_owned(), header.to_owned()],
+				..Default::default()
+			};
+			assert!(matches!(HeaderCapture::new(&config), Err(OtelInitError::SensitiveHeader(_))));
+		}
+	}
+
+	#[cfg(feature = "axum")]
+	#[test]
+	fn test_client_address() {
+		use super::client_address;
+		use crate::config::ClientAddressMode;
+
+		let v4 = "192.0.2.42".parse().expect("Invalid address");
+		let v6 = "2001:db8:1:2:3:4:5:6".parse().expect("Invalid address");
+		let mapped = "::ffff:192.0.2.42".parse().expect("Invalid address");
+		assert_eq!(client_address(&ClientAddressMode::Omit, v4), None);
+		assert_eq!(client_address(&ClientAddressMode::Full, v4).as_deref(), Some("192.0.2.42"));
+		let truncate = ClientAddressMode::Truncate;
+		assert_eq!(client_address(&truncate, v4).as_deref(), Some("192.0.2.0"));
+		assert_eq!(client_address(&truncate, v6).as_deref(), Some("2001:db8:1:2::"));
+		assert_eq!(client_address(&truncate, mapped).as_deref(), Some("192.0.2.0"));
+
+		let hash = ClientAddressMode::Hash("secret".to_owned());
+		let hashed = client_address(&hash, v4).expect("Missing address");
+		assert!(hashed.starts_with("hash:") && hashed.len() == 21, "{hashed}");
+		assert_eq!(client_address(&hash, mapped), Some(hashed));
+	}
+}
@@ -0,0 +1,265 @@
+// SPDX-FileCopyrightText: 2025 Famedly GmbH (info@famedly.com)
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Journal logs
+//!
+//! Module containing the layer sending the events to the systemd journal from
+//! the [`JournaldConfig`], using the [native journal protocol](https://systemd.io/JOURNAL_NATIVE_PROTOCOL/).
+use std::io;
+
+use tracing::{
+	Event, Subscriber,
+	span::{Attributes, Id, Record},
+};
+use tracing_subscriber::{Layer, layer::Context, registry::LookupSpan};
+
+use crate::{
+	config::JournaldConfig,
+	correlation::current_trace_context,
+	fields,
+	syslog::{Destination, severity},
+};
+
+/// Maximum length of the names of the journal fields
+const MAX_FIELD_NAME_LEN: usize = 64;
+
+/// Layer sending the events to the journal
+///
+/// Records too large for a single datagram, or sent while the journal is
+/// backed up, are dropped and their number is reported in the
+/// `DROPPED_RECORDS` field of the next record.
+#[derive(Debug)]
+pub(crate) struct JournaldLayer {
+	/// Socket of the journal
+	destination: Destination,
+	/// `SYSLOG_IDENTIFIER` of the records
+	syslog_identifier: String,
+	/// Prefix of the names of the fields of the events and spans
+	field_prefix: Option<String>,
+}
+
+impl JournaldLayer {
+	/// Opens the socket of the journal
+	pub(crate) fn new(config: &JournaldConfig, service_name: &str) -> io::Result<Self> {
+		Ok(Self {
+			destination: Destination::unix(&config.socket)?,
+			syslog_identifier: config
+				.syslog_identifier
+				.clone()
+				.unwrap_or_else(|| service_name.to_owned()),
+			field_prefix: config.field_prefix.clone(),
+		})
+	}
+
+	/// Builds the record of an event, reporting the number of records dropped
+	/// before it if any
+	fn record<S>(&self, event: &Event<'_>, ctx: &Context<'_, S>, dropped: u64) -> Vec<u8>
+	where
+		S: Subscriber + for<'a> LookupSpan<'a>,
+	{
+		let metadata = event.metadata();
+		let (span_names, fields) = fields::event_fields::<Self, S>(event, ctx);
+
+		let mut record = Vec::new();
+		if let Some(message) = &fields.message {
+			put_field(&mut record, "MESSAGE", message);
+		}
+		put_field(&mut record, "PRIORITY", &severity(*metadata.level()).to_string());
+		put_field(&mut record, "SYSLOG_IDENTIFIER", &self.syslog_identifier);
+		put_field(&mut record, "TARGET", metadata.target());
+		if let Some(file) = metadata.file() {
+			put_field(&mut record, "CODE_FILE", file);
+		}
+		if let Some(line) = metadata.line() {
+			put_field(&mut record, "CODE_LINE", &line.to_string());
+		}
+		for span_name in span_names {
+			put_field(&mut record, "SPAN_NAME", span_name);
+		}
+		if let Some(trace_context) = current_trace_context() {
+			put_field(&mut record, "TRACE_ID", &trace_context.trace_id.to_string());
+			put_field(&mut record, "SPAN_ID", &trace_context.span_id.to_string());
+		}
+		for (name, value) in &fields.fields {
+			if let Some(name) = field_name(self.field_prefix.as_deref(), name) {
+				put_field(&mut record, &name, value);
+			}
+		}
+		if dropped > 0 {
+			put_field(&mut record, "DROPPED_RECORDS", &dropped.to_string());
+		}
+		record
+	}
+}
+
+impl<S> Layer<S> for JournaldLayer
+where
+	S: Subscriber + for<'a> LookupSpan<'a>,
+{
+	fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+		fields::on_new_span::<Self, S>(attrs, id, &ctx);
+	}
+
+	fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+		fields::on_record::<Self, S>(id, values, &ctx);
+	}
+
+	fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+		let dropped = self.destination.dropped();
+		self.destination.send(&self.record(event, &ctx, dropped), dropped);
+	}
+}
+
+/// Name of the journal field holding a field of an event or span
+///
+/// The journal only accepts upper case letters, digits and `_`, and names not
+/// starting with `_` or a digit. `None` if nothing is left of the name.
+fn field_name(prefix: Option<&str>, name: &str) -> Option<String> {
+	let name = prefix.map_or_else(|| name.to_owned(), |prefix| format!("{prefix}_{name}"));
+	let name: String = name
+		.chars()
+		.map(|c| if c.is_ascii_alphanumeric() { c.to_ascii_uppercase() } else { '_' })
+		.skip_while(|c| *c == '_' || c.is_ascii_digit())
+		.take(MAX_FIELD_NAME_LEN)
+		.collect();
+	(!name.is_empty()).then_some(name)
+}
+
+/// Appends a field to a record, in the binary form if the value spans several
+/// lines
+fn put_field(record: &mut Vec<u8>, name: &str, value: &str) {
+	record.extend_from_slice(name.as_bytes());
+	if value.contains('\n') {
+		record.push(b'\n');
+		record.extend_from_slice(&(value.len() as u64).to_le_bytes());
+	} else {
+		record.push(b'=');
+	}
+	record.extend_from_slice(value.as_bytes());
+	record.push(b'\n');
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+	#![allow(clippy::expect_used)]
+	use std::{collections::HashMap, os::unix::net::UnixDatagram};
+
+	use super::field_name;
+	use crate::{
+		build_otel,
+		config::{JournaldConfig, OtelConfig},
+	};
+
+	/// Parses a record of the native protocol
+	fn parse_record(mut record: &[u8]) -> Vec<(String, String)> {
+		let mut fields = Vec::new();
+		while let Some(end) = record.iter().position(|b| matches!(b, b'=' | b'\n')) {
+			let name = String::from_utf8(record[..end].to_vec()).expect("Invalid name");
+			let (value, rest) = if record[end] == b'=' {
+				let len = record[end..].iter().position(|b| *b == b'\n').expect("Missing end");
+				(&record[end + 1..end + len], &record[end + len + 1..])
+			} else {
+				let len_bytes = record[end + 1..end + 9].try_into().expect("Missing length");
+				let len = usize::try_from(u64::from_le_bytes(len_bytes)).expect("Invalid length");
+				(&record[end + 9..end + 9 + len], &record[end + 10 + len..])
+			};
+			fields.push((name, String::from_utf8(value.to_vec()).expect("Invalid value")));
+			record = rest;
+		}
+		fields
+	}
+
+	#[test]
+	fn test_field_name() {
+		assert_eq!(field_name(None, "http.method").as_deref(), Some("HTTP_METHOD"));
+		assert_eq!(field_name(None, "_9user-id").as_deref(), Some("USER_ID"));
+		assert_eq!(field_name(Some("f"), "user").as_deref(), Some("F_USER"));
+		assert_eq!(field_name(None, "__"), None);
+	}
+
+	#[test]
+	fn test_journald_logs() {
+		let directory = tempfile::tempdir().expect("Error creating directory");
+		let socket = directory.path().join("journal.sock");
+		let server = UnixDatagram::bind(&socket).expect("Error binding socket");
+		let config = OtelConfig {
+			journald: Some(JournaldConfig {
+				enabled: true,
+				level: tracing::level_filters::LevelFilter::DEBUG.into(),
+				general_level: tracing::level_filters::LevelFilter::OFF.into(),
+				dependencies_levels: HashMap::new(),
+				syslog_identifier: None,
+				field_prefix: None,
+				socket,
+			}),
+			..Default::default()
+		};
+		let (subscriber, _guard) = build_otel!(&config).expect("Error initializing Otel");
+		tracing::subscriber::with_default(subscriber, || {
+			tracing::info_span!("request", user = "alice").in_scope(|| {
+				tracing::error!(details = "first\nsecond", "Sent to the journal");
+			});
+			tracing::trace!("Filtered out");
+		});
+
+		let mut buf = [0; 1024];
+		let len = server.recv(&mut buf).expect("Missing record");
+		let fields = parse_record(&buf[..len]);
+		let field = |name: &str| {
+			fields.iter().find(|(field, _)| field == name).map(|(_, value)| value.as_str())
+		};
+		assert_eq!(field("MESSAGE"), Some("Sent to the journal"));
+		assert_eq!(field("PRIORITY"), Some("3"));
+		assert_eq!(field("SYSLOG_IDENTIFIER"), Some(env!("CARGO_PKG_NAME")));
+		assert_eq!(field("TARGET"), Some("rust_telemetry::journald::tests"));
+		assert_eq!(field("SPAN_NAME"), Some("request"));
+		assert_eq!(field("USER"), Some("alice"));
+		assert_eq!(field("DETAILS"), Some("first\nsecond"));
+		assert!(field("CODE_LINE").is_some());
+
+		server.set_nonblocking(true).expect("Error setting non-blocking");
+		assert!(server.recv(&mut buf).is_err(), "Unexpected record");
+	}
+
+	#[test]
+	fn test_journald_backed_up() {
+		let directory = tempfile::tempdir().expect("Error creating directory");
+		let socket = directory.path().join("journal.sock");
+		let server = UnixDatagram::bind(&socket).expect("Error binding socket");
+		let config = OtelConfig {
+			journald: Some(JournaldConfig {
+				enabled: true,
+				level: tracing::level_filters::LevelFilter::INFO.into(),
+				general_level: tracing::level_filters::LevelFilter::OFF.into(),
+				dependencies_levels: HashMap::new(),
+				syslog_identifier: None,
+				field_prefix: None,
+				socket,
+			}),
+			..Default::default()
+		};
+		let (subscriber, _guard) = build_otel!(&config).expect("Error initializing Otel");
+		tracing::subscriber::with_default(subscriber, || {
+			// Nothing is received, the queue of the socket fills up without blocking
+			for _ in 0..10_000 {
+				tracing::info!("Backed up");
+			}
+			server.set_nonblocking(true).expect("Error setting non-blocking");
+			let mut buf = [0; 1024];
+			let mut received = 0;
+			while server.recv(&mut buf).is_ok() {
+				received += 1;
+			}
+			tracing::info!("Caught up");
+
+			let len = server.recv(&mut buf).expect("Missing record");
+			let fields = parse_record(&buf[..len]);
+			let dropped = fields
+				.iter()
+				.find(|(name, _)| name == "DROPPED_RECORDS")
+				.map(|(_, value)| value.parse::<u64>().expect("Invalid count"));
+			assert_eq!(dropped, Some(10_000 - received));
+		});
+	}
+}
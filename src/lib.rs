@@ -7,95 +7,43 @@
 //! Lib containing the definitions and initializations of the OpenTelemetry
 //! tools
 #![cfg_attr(all(doc, not(doctest), docsrs), feature(doc_cfg))]
-use std::{collections::BTreeMap as Map, str::FromStr as _};
+use std::time::{Duration, Instant};
 
-use config::{OtelConfig, OtelUrl, StdoutLogsConfig};
-use opentelemetry::{KeyValue, trace::TracerProvider as _};
-use opentelemetry_appender_tracing::layer::OpenTelemetryTracingBridge;
-use opentelemetry_otlp::{ExporterBuildError, LogExporter, SpanExporter, WithExportConfig as _};
-use opentelemetry_resource_detectors::{K8sResourceDetector, ProcessResourceDetector};
+use builder::TelemetryBuilder;
+use config::OtelConfig;
+use opentelemetry_otlp::ExporterBuildError;
 use opentelemetry_sdk::{
-	Resource,
-	logs::SdkLoggerProvider,
-	metrics::{MeterProviderBuilder, PeriodicReader, SdkMeterProvider},
-	propagation::TraceContextPropagator,
-	trace::{RandomIdGenerator, SdkTracerProvider},
-};
-use opentelemetry_semantic_conventions::resource::SERVICE_VERSION;
-use tracing_opentelemetry::{MetricsLayer, OpenTelemetryLayer};
-use tracing_subscriber::{
-	EnvFilter, Layer, layer::SubscriberExt as _, util::SubscriberInitExt as _,
+	error::OTelSdkResult, logs::SdkLoggerProvider, metrics::SdkMeterProvider,
+	trace::SdkTracerProvider,
 };
+use tracing::Subscriber;
+use tracing_appender::non_blocking::WorkerGuard;
 
+pub mod audit;
 #[cfg(feature = "axum")]
 pub mod axum;
+pub mod builder;
 pub mod config;
+mod correlation;
+mod debug_requests;
+mod fields;
+mod file;
+#[cfg(test)]
+mod fixtures;
+mod format;
+#[cfg(any(feature = "axum", feature = "reqwest-middleware"))]
+mod http_capture;
+mod journald;
+mod non_blocking;
+mod redaction;
 pub mod reexport;
 #[cfg(feature = "reqwest-middleware")]
 pub mod reqwest_middleware;
-
-/// Crates a resource for the Otel providers
-fn mk_resource(
-	service_name: &'static str,
-	version: &'static str,
-	resource_metadata: Map<String, String>,
-) -> Resource {
-	Resource::builder()
-		.with_attributes(
-			resource_metadata.into_iter().map(|(key, value)| KeyValue::new(key, value)),
-		)
-		.with_detector(Box::new(K8sResourceDetector {}))
-		.with_detector(Box::new(ProcessResourceDetector {}))
-		.with_attribute(KeyValue::new(SERVICE_VERSION, version))
-		.with_service_name(service_name)
-		.build()
-}
-
-/// Setup a Otel exporter and a provider for traces
-fn init_traces(
-	endpoint: OtelUrl,
-	resource: Resource,
-) -> Result<SdkTracerProvider, ExporterBuildError> {
-	let exporter = SpanExporter::builder().with_tonic().with_endpoint(endpoint.url).build()?;
-	let tracer_provider = SdkTracerProvider::builder()
-		.with_id_generator(RandomIdGenerator::default())
-		.with_resource(resource)
-		.with_batch_exporter(exporter)
-		.build();
-
-	opentelemetry::global::set_tracer_provider(tracer_provider.clone());
-	Ok(tracer_provider)
-}
-
-/// Setup a Otel exporter and a provider for metrics
-fn init_metrics(
-	endpoint: OtelUrl,
-	resource: Resource,
-) -> Result<SdkMeterProvider, ExporterBuildError> {
-	let exporter = opentelemetry_otlp::MetricExporter::builder()
-		.with_tonic()
-		.with_endpoint(endpoint.url)
-		.with_temporality(opentelemetry_sdk::metrics::Temporality::default())
-		.build()?;
-
-	let reader = PeriodicReader::builder(exporter).build();
-
-	let meter_provider =
-		MeterProviderBuilder::default().with_resource(resource).with_reader(reader).build();
-
-	opentelemetry::global::set_meter_provider(meter_provider.clone());
-	Ok(meter_provider)
-}
-
-/// Setup a Otel exporter and a provider for logs
-fn init_logs(
-	endpoint: OtelUrl,
-	resource: Resource,
-) -> Result<SdkLoggerProvider, ExporterBuildError> {
-	let exporter = LogExporter::builder().with_tonic().with_endpoint(endpoint.url).build()?;
-
-	Ok(SdkLoggerProvider::builder().with_resource(resource).with_batch_exporter(exporter).build())
-}
+pub mod shutdown;
+mod stdout;
+mod syslog;
+#[cfg(feature = "testing")]
+pub mod testing;
 
 /// Initializes the OpenTelemetry
 ///
@@ -147,91 +95,92 @@ pub fn init_otel(
 	service_name: &'static str,
 	pkg_version: &'static str,
 ) -> Result<ProvidersGuard, OtelInitError> {
-	opentelemetry::global::set_text_map_propagator(TraceContextPropagator::default());
-
-	let stdout_layer = config
-		.stdout
-		.as_ref()
-		.or(Some(&StdoutLogsConfig::default()))
-		.and_then(|stdout| stdout.enabled.then_some(stdout))
-		.map(|logger_config| {
-			let filter_fmt = EnvFilter::from_str(&logger_config.get_filter(main_crate))?;
-			let stdout_layer = tracing_subscriber::fmt::layer().with_thread_names(true);
-			Ok::<_, OtelInitError>(
-				if logger_config.json_output {
-					Box::new(stdout_layer.json()) as Box<dyn Layer<_> + Send + Sync>
-				} else {
-					Box::new(stdout_layer)
-				}
-				.with_filter(filter_fmt),
-			)
-		})
-		.transpose()?;
+	TelemetryBuilder::new(config.clone(), main_crate, service_name, pkg_version).init()
+}
 
-	let exporter_with_resource = config.exporter.as_ref().map(|exporter| {
-		(exporter, mk_resource(service_name, pkg_version, exporter.resource_metadata.clone()))
-	});
+/// Initializes the OpenTelemetry, returning an error instead of panicking if a
+/// global subscriber is already set
+///
+/// example
+/// ```rust
+/// use rust_telemetry::{OtelInitError, config::OtelConfig, try_init_otel};
+///
+/// #[tokio::main]
+/// async fn main() {
+/// 	let _guard = match try_init_otel!(&OtelConfig::default()) {
+/// 		Ok(guard) => Some(guard),
+/// 		Err(OtelInitError::AlreadyInitialized) => None,
+/// 		Err(err) => panic!("Error initializing Otel: {err}"),
+/// 	};
+///
+/// 	// ...
+/// }
+/// ```
+#[macro_export]
+macro_rules! try_init_otel {
+	($config:expr) => {
+		$crate::try_init_otel(
+			$config,
+			env!("CARGO_CRATE_NAME"),
+			env!("CARGO_PKG_NAME"),
+			env!("CARGO_PKG_VERSION"),
+		)
+	};
+}
 
-	let (logger_provider, logs_layer) = exporter_with_resource
-		.as_ref()
-		.and_then(|(exporter, resource)| {
-			exporter.logs.as_ref().and_then(|c| c.enabled.then_some(c)).map(|logger_config| {
-				let filter_otel = EnvFilter::from_str(&logger_config.get_filter(main_crate))?;
-				let logger_provider = init_logs(exporter.endpoint.clone(), resource.clone())?;
-
-				// Create a new OpenTelemetryTracingBridge using the above LoggerProvider.
-				let logs_layer =
-					OpenTelemetryTracingBridge::new(&logger_provider).with_filter(filter_otel);
-
-				Ok::<_, OtelInitError>((Some(logger_provider), Some(logs_layer)))
-			})
-		})
-		.transpose()?
-		.unwrap_or((None, None));
-
-	let (tracer_provider, tracer_layer) = exporter_with_resource
-		.as_ref()
-		.and_then(|(exporter, resource)| {
-			exporter.traces.as_ref().and_then(|c| c.enabled.then_some(c)).map(|tracer_config| {
-				let trace_filter = EnvFilter::from_str(&tracer_config.get_filter(main_crate))?;
-				let tracer_provider = init_traces(exporter.endpoint.clone(), resource.clone())?;
-				let tracer = tracer_provider.tracer(service_name);
-				let tracer_layer = OpenTelemetryLayer::new(tracer).with_filter(trace_filter);
-				Ok::<_, OtelInitError>((Some(tracer_provider), Some(tracer_layer)))
-			})
-		})
-		.transpose()?
-		.unwrap_or((None, None));
-
-	let (meter_provider, meter_layer) = exporter_with_resource
-		.as_ref()
-		.and_then(|(exporter, resource)| {
-			exporter.metrics.as_ref().and_then(|c| c.enabled.then_some(c)).map(|meter_config| {
-				let metrics_filter = EnvFilter::from_str(&meter_config.get_filter(main_crate))?;
-				let meter_provider = init_metrics(exporter.endpoint.clone(), resource.clone())?;
-				let meter_layer =
-					MetricsLayer::new(meter_provider.clone()).with_filter(metrics_filter);
-
-				Ok::<_, OtelInitError>((Some(meter_provider), Some(meter_layer)))
-			})
-		})
-		.transpose()?
-		.unwrap_or((None, None));
-
-	// Initialize the tracing subscriber with the stdout layer and
-	// layers for exporting over OpenTelemetry the logs, traces and metrics.
-	let subscriber = tracing_subscriber::registry()
-		.with(logs_layer)
-		.with(stdout_layer)
-		.with(meter_layer)
-		.with(tracer_layer);
-
-	#[cfg(feature = "tracing-error")]
-	let subscriber = subscriber.with(tracing_error::ErrorLayer::default());
-
-	subscriber.init();
-
-	Ok(ProvidersGuard { logger_provider, tracer_provider, meter_provider })
+/// Initializes the OpenTelemetry, returning
+/// [`OtelInitError::AlreadyInitialized`] if a global subscriber is already
+/// set. In that case, neither the subscriber nor the global providers are
+/// touched.
+#[must_use = "The return is a guard for the providers and it need to be kept to properly shutdown them"]
+pub fn try_init_otel(
+	config: &OtelConfig,
+	main_crate: &'static str,
+	service_name: &'static str,
+	pkg_version: &'static str,
+) -> Result<ProvidersGuard, OtelInitError> {
+	TelemetryBuilder::new(config.clone(), main_crate, service_name, pkg_version).try_init()
+}
+
+/// Builds the OpenTelemetry subscriber without installing it
+///
+/// example
+/// ```rust
+/// use rust_telemetry::{build_otel, config::OtelConfig};
+///
+/// #[tokio::main]
+/// async fn main() {
+/// 	let (subscriber, _guard) = build_otel!(&OtelConfig::default()).unwrap();
+/// 	tracing::subscriber::with_default(subscriber, || {
+/// 		// ...
+/// 	});
+/// }
+/// ```
+#[macro_export]
+macro_rules! build_otel {
+	($config:expr) => {
+		$crate::build_otel(
+			$config,
+			env!("CARGO_CRATE_NAME"),
+			env!("CARGO_PKG_NAME"),
+			env!("CARGO_PKG_VERSION"),
+		)
+	};
+}
+
+/// Builds the OpenTelemetry subscriber without installing it, for use with
+/// [`tracing::subscriber::set_default`] or
+/// [`tracing::subscriber::with_default`]
+///
+/// Neither the subscriber, the tracer and meter providers nor the text map
+/// propagator are set globally.
+pub fn build_otel(
+	config: &OtelConfig,
+	main_crate: &'static str,
+	service_name: &'static str,
+	pkg_version: &'static str,
+) -> Result<(impl Subscriber + Send + Sync + 'static, ProvidersGuard), OtelInitError> {
+	TelemetryBuilder::new(config.clone(), main_crate, service_name, pkg_version).build()
 }
 
 /// Guarding object to make sure the providers are properly shutdown
@@ -243,37 +192,90 @@ pub struct ProvidersGuard {
 	tracer_provider: Option<SdkTracerProvider>,
 	/// Meter provider
 	meter_provider: Option<SdkMeterProvider>,
+	/// Guard of the non-blocking writer of the stdout logs, flushing them when
+	/// dropped
+	stdout_guard: Option<non_blocking::StdoutGuard>,
+	/// Guard of the writer of the log files, flushing them when dropped
+	file_guard: Option<WorkerGuard>,
 }
 
+/// Maximum time the [`ProvidersGuard`] waits for the providers to shutdown
+/// when dropped
+const DROP_SHUTDOWN_TIMEOUT: Duration = Duration::from_secs(5);
+
 // Necessary to call TracerProvider::shutdown() on exit
 // due to a bug with flushing on global shutdown:
 // https://github.com/open-telemetry/opentelemetry-rust/issues/1961
+//
+// This is only a fallback for when `ProvidersGuard::shutdown` wasn't called.
+// The providers are shutdown concurrently, each on its own thread against a
+// shared deadline, so a slow exporter doesn't keep the others from flushing,
+// and outside of the runtime so the drop doesn't hang when the exporters need
+// the runtime we are blocking, see
+// https://github.com/open-telemetry/opentelemetry-rust/issues/536
 impl Drop for ProvidersGuard {
 	fn drop(&mut self) {
-		// This causes a hang in testing.
-		// Some relevant information:
-		// https://github.com/open-telemetry/opentelemetry-rust/issues/536
-		#[cfg(not(test))]
-		{
-			if let Some(logger_provider) = self.logger_provider.as_ref() {
-				let _ = logger_provider.shutdown().inspect_err(|err| {
-					tracing::error!("Could not shutdown LoggerProvider: {err}");
-				});
-			}
-			if let Some(tracer_provider) = self.tracer_provider.as_ref() {
-				let _ = tracer_provider.shutdown().inspect_err(|err| {
-					tracing::error!("Could not shutdown TracerProvider: {err}");
-				});
-			}
-			if let Some(meter_provider) = self.meter_provider.as_ref() {
-				let _ = meter_provider.shutdown().inspect_err(|err| {
-					tracing::error!("Could not shutdown MeterProvider: {err}");
+		// Emits the last report of the dropped stdout lines while the providers
+		// can still export it
+		drop(self.stdout_guard.take());
+		let logger_provider = self.logger_provider.take();
+		let tracer_provider = self.tracer_provider.take();
+		let meter_provider = self.meter_provider.take();
+		if logger_provider.is_none() && tracer_provider.is_none() && meter_provider.is_none() {
+			return;
+		}
+
+		let deadline = Instant::now() + DROP_SHUTDOWN_TIMEOUT;
+		let (done, wait) = std::sync::mpsc::channel();
+		let spawned =
+			std::thread::Builder::new().name("otel-shutdown".to_owned()).spawn(move || {
+				std::thread::scope(|scope| {
+					if let Some(logger_provider) = logger_provider {
+						shutdown_scoped(scope, "LoggerProvider", deadline, move |timeout| {
+							logger_provider.shutdown_with_timeout(timeout)
+						});
+					}
+					if let Some(tracer_provider) = tracer_provider {
+						shutdown_scoped(scope, "TracerProvider", deadline, move |timeout| {
+							tracer_provider.shutdown_with_timeout(timeout)
+						});
+					}
+					if let Some(meter_provider) = meter_provider {
+						shutdown_scoped(scope, "MeterProvider", deadline, move |timeout| {
+							meter_provider.shutdown_with_timeout(timeout)
+						});
+					}
 				});
+				let _ = done.send(());
+			});
+		match spawned {
+			Ok(_) => {
+				if wait.recv_timeout(deadline.saturating_duration_since(Instant::now())).is_err() {
+					tracing::error!("Timed out shutting down the providers");
+				}
 			}
+			Err(err) => tracing::error!("Could not spawn thread to shutdown the providers: {err}"),
 		}
 	}
 }
 
+/// Shuts down a provider in its own thread of the scope, with what's left
+/// until the deadline
+fn shutdown_scoped<'scope>(
+	scope: &'scope std::thread::Scope<'scope, '_>,
+	name: &'static str,
+	deadline: Instant,
+	shutdown: impl FnOnce(Duration) -> OTelSdkResult + Send + 'scope,
+) {
+	let spawned = std::thread::Builder::new().spawn_scoped(scope, move || {
+		let _ = shutdown(deadline.saturating_duration_since(Instant::now()))
+			.inspect_err(|err| tracing::error!("Could not shutdown {name}: {err}"));
+	});
+	if let Err(err) = spawned {
+		tracing::error!("Could not spawn thread to shutdown the {name}: {err}");
+	}
+}
+
 /// OpenTelemetry setup errors
 #[allow(missing_docs)]
 #[derive(Debug, thiserror::Error)]
@@ -282,78 +284,223 @@ pub enum OtelInitError {
 	BuildExporterError(#[from] ExporterBuildError),
 	#[error("Parsing EnvFilter directives error: {0}")]
 	EnvFilterError(#[from] tracing_subscriber::filter::ParseError),
+	#[error("A global tracing subscriber is already set")]
+	AlreadyInitialized,
+	#[error("Error opening the log files: {0}")]
+	LogFileError(#[from] std::io::Error),
+	#[error("Error connecting to the journal: {0}")]
+	JournaldError(std::io::Error),
+	#[error("Error connecting to the syslog server: {0}")]
+	SyslogError(std::io::Error),
+	#[error("Invalid redaction pattern: {0}")]
+	RedactionPatternError(#[from] regex::Error),
+	#[error("Error setting up the audit logs: {0}")]
+	AuditError(std::io::Error),
+	#[error("Invalid HTTP metrics config: {0}")]
+	HttpMetricsConfigError(String),
+	#[error("Invalid HTTP header name: {0}")]
+	InvalidHeaderName(#[from] http::header::InvalidHeaderName),
+	#[error("The {0} header can't be recorded in the spans")]
+	SensitiveHeader(String),
 }
 
 #[cfg(test)]
 mod tests {
 	#![allow(clippy::expect_used)]
+	#[cfg(feature = "testing")]
+	use opentelemetry_proto::tonic::common::v1::any_value::Value;
+	use tracing_subscriber::util::SubscriberInitExt as _;
+
 	use super::config::{ExporterConfig, OtelConfig, ProviderConfig};
-	use crate::config::StdoutLogsConfig;
+	#[cfg(feature = "testing")]
+	use crate::testing::{ExportRequest, MockCollector, RecordedRequest, Transport};
+	use crate::{OtelInitError, config::StdoutLogsConfig};
+
+	/// Maximum time to wait for the exports to reach the collector
+	#[cfg(feature = "testing")]
+	const TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+	/// Builds a config exporting to the mock collector
+	#[cfg(feature = "testing")]
+	fn exporter_config(collector: &MockCollector, exporter: ExporterConfig) -> OtelConfig {
+		OtelConfig {
+			exporter: Some(ExporterConfig {
+				endpoint: collector.endpoint().into(),
+				resource_metadata: [("service.instance.id".to_owned(), "test-instance".to_owned())]
+					.into(),
+				..exporter
+			}),
+			..Default::default()
+		}
+	}
+
+	/// Checks the transport, headers and resource of an export request
+	#[cfg(feature = "testing")]
+	fn assert_common_export<T: ExportRequest>(request: &RecordedRequest<T>) {
+		assert_eq!(request.transport, Transport::Grpc);
+		assert_eq!(
+			request.headers.get(http::header::CONTENT_TYPE).expect("Missing content type"),
+			"application/grpc"
+		);
+		assert_eq!(request.resource_attribute("service.name"), Some("rust-telemetry"));
+		assert_eq!(request.resource_attribute("service.version"), Some(env!("CARGO_PKG_VERSION")));
+		assert_eq!(request.resource_attribute("service.instance.id"), Some("test-instance"));
+	}
 
 	#[tokio::test]
 	async fn test_tracer_provider_enabled() {
 		let config = OtelConfig {
-			stdout: None,
 			exporter: Some(ExporterConfig {
 				traces: Some(ProviderConfig { enabled: true, ..Default::default() }),
 				..Default::default()
 			}),
+			..Default::default()
 		};
-		let guard = init_otel!(&config).expect("Error initializing Otel");
+		let (_, guard) = build_otel!(&config).expect("Error initializing Otel");
 		assert!(guard.tracer_provider.is_some());
 	}
 	#[tokio::test]
 	async fn test_tracer_provider_disabled() {
 		let config_enabled_false = OtelConfig {
-			stdout: None,
 			exporter: Some(ExporterConfig {
 				traces: Some(ProviderConfig { enabled: false, ..Default::default() }),
 				..Default::default()
 			}),
+			..Default::default()
 		};
-		let guard = init_otel!(&config_enabled_false).expect("Error initializing Otel");
+		let (_, guard) = build_otel!(&config_enabled_false).expect("Error initializing Otel");
 		assert!(guard.tracer_provider.is_none());
 	}
 
-	// There seems to be a problem when testing the scenario when the meter
-	// provider is enable. The tests hangs when calling the shutdown from the
-	// PeriodicReader. For now we won't test this scenarios
-	//https://github.com/open-telemetry/opentelemetry-rust/issues/2056
+	#[cfg(feature = "testing")]
+	#[tokio::test(flavor = "multi_thread")]
+	async fn test_meter_provider_enabled() {
+		let collector = MockCollector::start().await.expect("Error starting collector");
+		let config = exporter_config(
+			&collector,
+			ExporterConfig {
+				metrics: Some(ProviderConfig { enabled: true, ..Default::default() }),
+				..Default::default()
+			},
+		);
+		let (subscriber, guard) = build_otel!(&config).expect("Error initializing Otel");
+		let _default = tracing::subscriber::set_default(subscriber);
+		let meter_provider = guard.meter_provider.as_ref().expect("Missing meter provider");
+
+		tracing::info!(monotonic_counter.test_counter = 1_u64, "Counting");
+		meter_provider.force_flush().expect("Error flushing metrics");
+
+		assert!(collector.wait_until(TIMEOUT, |c| !c.metrics().is_empty()).await);
+		let metrics = collector.metrics();
+		assert_common_export(&metrics[0]);
+		assert!(
+			metrics
+				.iter()
+				.flat_map(|m| &m.request.resource_metrics)
+				.flat_map(|m| &m.scope_metrics)
+				.flat_map(|m| &m.metrics)
+				.any(|metric| metric.name == "test_counter")
+		);
+	}
 
 	#[tokio::test]
 	async fn test_meter_provider_disabled() {
 		let config_enabled_false = OtelConfig {
-			stdout: None,
 			exporter: Some(ExporterConfig {
 				metrics: Some(ProviderConfig { enabled: false, ..Default::default() }),
 				..Default::default()
 			}),
+			..Default::default()
 		};
-		let guard = init_otel!(&config_enabled_false).expect("Error initializing Otel");
+		let (_, guard) = build_otel!(&config_enabled_false).expect("Error initializing Otel");
 		assert!(guard.meter_provider.is_none());
 	}
+	#[cfg(feature = "testing")]
+	#[tokio::test(flavor = "multi_thread")]
+	async fn test_traces_export() {
+		let collector = MockCollector::start().await.expect("Error starting collector");
+		let config = exporter_config(
+			&collector,
+			ExporterConfig {
+				traces: Some(ProviderConfig { enabled: true, ..Default::default() }),
+				..Default::default()
+			},
+		);
+		let (subscriber, guard) = build_otel!(&config).expect("Error initializing Otel");
+		let _default = tracing::subscriber::set_default(subscriber);
+		let tracer_provider = guard.tracer_provider.as_ref().expect("Missing tracer provider");
+
+		tracing::info_span!("test_span").in_scope(|| tracing::info!("Inside the span"));
+		tracer_provider.force_flush().expect("Error flushing traces");
+
+		assert!(collector.wait_until(TIMEOUT, |c| !c.traces().is_empty()).await);
+		let traces = collector.traces();
+		assert_common_export(&traces[0]);
+		assert!(
+			traces
+				.iter()
+				.flat_map(|t| &t.request.resource_spans)
+				.flat_map(|s| &s.scope_spans)
+				.flat_map(|s| &s.spans)
+				.any(|span| span.name == "test_span")
+		);
+	}
+
+	#[cfg(feature = "testing")]
+	#[tokio::test(flavor = "multi_thread")]
+	async fn test_logs_export() {
+		let collector = MockCollector::start().await.expect("Error starting collector");
+		let config = exporter_config(
+			&collector,
+			ExporterConfig {
+				logs: Some(ProviderConfig { enabled: true, ..Default::default() }),
+				..Default::default()
+			},
+		);
+		let (subscriber, guard) = build_otel!(&config).expect("Error initializing Otel");
+		let _default = tracing::subscriber::set_default(subscriber);
+		let logger_provider = guard.logger_provider.as_ref().expect("Missing logger provider");
+
+		tracing::info!("Exported log line");
+		logger_provider.force_flush().expect("Error flushing logs");
+
+		assert!(collector.wait_until(TIMEOUT, |c| !c.logs().is_empty()).await);
+		let logs = collector.logs();
+		assert_common_export(&logs[0]);
+		assert!(
+			logs.iter()
+				.flat_map(|l| &l.request.resource_logs)
+				.flat_map(|l| &l.scope_logs)
+				.flat_map(|l| &l.log_records)
+				.filter_map(|record| record.body.as_ref()?.value.as_ref())
+				.any(
+					|body| matches!(body, Value::StringValue(body) if body == "Exported log line")
+				)
+		);
+	}
+
 	#[tokio::test]
 	async fn test_logger_provider_enabled() {
 		let config = OtelConfig {
-			stdout: None,
 			exporter: Some(ExporterConfig {
 				logs: Some(ProviderConfig { enabled: true, ..Default::default() }),
 				..Default::default()
 			}),
+			..Default::default()
 		};
-		let guard = init_otel!(&config).expect("Error initializing Otel");
+		let (_, guard) = build_otel!(&config).expect("Error initializing Otel");
 		assert!(guard.logger_provider.is_some());
 	}
 	#[tokio::test]
 	async fn test_logger_provider_disabled() {
 		let config_enabled_false = OtelConfig {
-			stdout: None,
 			exporter: Some(ExporterConfig {
 				logs: Some(ProviderConfig { enabled: false, ..Default::default() }),
 				..Default::default()
 			}),
+			..Default::default()
 		};
-		let guard = init_otel!(&config_enabled_false).expect("Error initializing Otel");
+		let (_, guard) = build_otel!(&config_enabled_false).expect("Error initializing Otel");
 		assert!(guard.logger_provider.is_none());
 	}
 
@@ -362,10 +509,19 @@ mod tests {
 		let config_none = OtelConfig {
 			stdout: Some(StdoutLogsConfig { enabled: true, ..Default::default() }),
 			exporter: Some(ExporterConfig::default()),
+			..Default::default()
 		};
-		let guard = init_otel!(&config_none).expect("Error initializing Otel");
+		let (_, guard) = build_otel!(&config_none).expect("Error initializing Otel");
 		assert!(guard.meter_provider.is_none());
 		assert!(guard.tracer_provider.is_none());
 		assert!(guard.logger_provider.is_none());
 	}
+
+	#[test]
+	fn test_try_init_already_initialized() {
+		// Another test may have set the global subscriber already
+		let _ = tracing_subscriber::registry().try_init();
+		let result = try_init_otel!(&OtelConfig::for_tests());
+		assert!(matches!(result, Err(OtelInitError::AlreadyInitialized)));
+	}
 }
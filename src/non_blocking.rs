@@ -0,0 +1,277 @@
+// SPDX-FileCopyrightText: 2025 Famedly GmbH (info@famedly.com)
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Non-blocking stdout
+//!
+//! Module building the writer of the stdout logs running in a background
+//! thread from the [`NonBlockingConfig`], and reporting the lines it drops.
+use std::{
+	io::{self, Write},
+	sync::{
+		Arc, OnceLock,
+		mpsc::{self, RecvTimeoutError, Sender},
+	},
+	thread::{self, JoinHandle},
+};
+
+use tracing::{
+	Dispatch, Subscriber,
+	dispatcher::{self, WeakDispatch},
+};
+use tracing_appender::non_blocking::{ErrorCounter, NonBlocking, NonBlockingBuilder, WorkerGuard};
+use tracing_subscriber::Layer;
+
+use crate::config::{NonBlockingConfig, OverflowPolicy};
+
+/// Dispatch of the subscriber the drop reports are emitted through, known
+/// once the subscriber is in use
+type ReportDispatch = Arc<OnceLock<WeakDispatch>>;
+
+/// Guard of the non-blocking stdout and stderr, emitting the last drop report
+/// and flushing the buffered lines when dropped
+#[derive(Debug)]
+pub(crate) struct StdoutGuard {
+	/// Guards of the threads writing the lines
+	_workers: Vec<WorkerGuard>,
+	/// Stops the thread reporting the dropped lines when dropped, and that
+	/// thread
+	reports: Option<(Sender<()>, JoinHandle<()>)>,
+	/// Dispatch the drop reports are emitted through
+	dispatch: ReportDispatch,
+}
+
+impl StdoutGuard {
+	/// Layer capturing the dispatch of the subscriber it is added to, so the
+	/// drop reports reach that subscriber, wherever it is set as default
+	pub(crate) fn dispatch_capture(&self) -> DispatchCapture {
+		DispatchCapture(Some(self.dispatch.clone()))
+	}
+}
+
+impl Drop for StdoutGuard {
+	fn drop(&mut self) {
+		if let Some((stop_reports, reports)) = self.reports.take() {
+			drop(stop_reports);
+			let _ = reports.join();
+		}
+	}
+}
+
+/// Layer capturing the dispatch of the subscriber the drop reports are
+/// emitted through
+#[derive(Debug, Default)]
+pub(crate) struct DispatchCapture(Option<ReportDispatch>);
+
+impl<S: Subscriber> Layer<S> for DispatchCapture {
+	fn on_register_dispatch(&self, subscriber: &Dispatch) {
+		if let Some(dispatch) = &self.0 {
+			let _ = dispatch.set(subscriber.downgrade());
+		}
+	}
+}
+
+/// Builds the non-blocking writers of the stdout logs, and of the logs routed
+/// to stderr if given
+///
+/// The dropped lines are reported from another thread, through the subscriber
+/// the [`StdoutGuard::dispatch_capture`] layer is added to, and a last time
+/// when the guard is dropped, also written to stderr as the report itself may
+/// be dropped from the full buffer.
+pub(crate) fn writers(
+	config: &NonBlockingConfig,
+	stdout: impl Write + Send + 'static,
+	stderr: Option<impl Write + Send + 'static>,
+) -> (NonBlocking, Option<NonBlocking>, StdoutGuard) {
+	let builder = |thread_name: &str| {
+		NonBlockingBuilder::default()
+			.buffered_lines_limit(config.buffered_lines)
+			.lossy(config.overflow == OverflowPolicy::Drop)
+			.thread_name(thread_name)
+	};
+	let (stdout, stdout_worker) = builder("telemetry-stdout").finish(stdout);
+	let mut workers = vec![stdout_worker];
+	let mut counters = vec![stdout.error_counter()];
+	let stderr = stderr.map(|stderr| {
+		let (stderr, stderr_worker) = builder("telemetry-stderr").finish(stderr);
+		workers.push(stderr_worker);
+		counters.push(stderr.error_counter());
+		stderr
+	});
+
+	let dispatch = ReportDispatch::default();
+	let (stop_reports, stopped) = mpsc::channel();
+	let mut reporter = DropReporter::new(counters, dispatch.clone());
+	let interval = *config.report_interval;
+	let spawned =
+		thread::Builder::new().name("telemetry-stdout-drops".to_owned()).spawn(move || {
+			while let Err(RecvTimeoutError::Timeout) = stopped.recv_timeout(interval) {
+				reporter.report();
+			}
+			let dropped = reporter.report();
+			if dropped > 0 {
+				let _ = writeln!(
+					io::stderr(),
+					"Dropped {dropped} stdout log lines because the buffer was full"
+				);
+			}
+		});
+	let reports = match spawned {
+		Ok(reports) => Some((stop_reports, reports)),
+		Err(err) => {
+			tracing::warn!("Could not start reporting the dropped stdout lines: {err}");
+			None
+		}
+	};
+	(stdout, stderr, StdoutGuard { _workers: workers, reports, dispatch })
+}
+
+/// Reports the lines dropped by non-blocking writers since the last report
+#[derive(Debug)]
+struct DropReporter {
+	/// Counters of the lines dropped by each writer
+	counters: Vec<ErrorCounter>,
+	/// Number of dropped lines already reported
+	reported: usize,
+	/// Dispatch the reports are emitted through, the default one until known
+	dispatch: ReportDispatch,
+}
+
+impl DropReporter {
+	/// Reports the lines dropped by the writers of the counters from now on
+	const fn new(counters: Vec<ErrorCounter>, dispatch: ReportDispatch) -> Self {
+		Self { counters, reported: 0, dispatch }
+	}
+
+	/// Emits a warning with the number of lines dropped since the last
+	/// report, if any, which the metrics layer adds to the
+	/// `telemetry.stdout.dropped_lines` counter, and returns that number
+	fn report(&mut self) -> u64 {
+		let dropped = self.counters.iter().map(ErrorCounter::dropped_lines).sum::<usize>();
+		let new = dropped.saturating_sub(self.reported) as u64;
+		if new > 0 {
+			let warn = || {
+				tracing::warn!(
+					monotonic_counter.telemetry.stdout.dropped_lines = new,
+					dropped_lines = new,
+					"Dropped stdout log lines because the buffer was full"
+				);
+			};
+			match self.dispatch.get().and_then(WeakDispatch::upgrade) {
+				Some(dispatch) => dispatcher::with_default(&dispatch, warn),
+				None => warn(),
+			}
+		}
+		self.reported = dropped;
+		new
+	}
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+	#![allow(clippy::expect_used)]
+	use std::{
+		io::{self, Write},
+		sync::{
+			Arc,
+			mpsc::{self, Receiver},
+		},
+		time::Duration,
+	};
+
+	use famedly_rust_utils::duration::Seconds;
+	use tracing::Dispatch;
+	use tracing_appender::non_blocking::NonBlockingBuilder;
+	use tracing_subscriber::layer::SubscriberExt as _;
+
+	use super::{DropReporter, writers};
+	use crate::{
+		builder::BoxedLayer,
+		config::{LogFormat, NonBlockingConfig, OverflowPolicy, StdoutLogsConfig, Timestamp},
+		stdout::{self, tests::CapturedOutput},
+	};
+
+	/// Writer waiting for a signal before each write
+	pub(crate) struct Stalled(pub(crate) Receiver<()>);
+
+	impl Write for Stalled {
+		fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+			let _ = self.0.recv();
+			Ok(buf.len())
+		}
+
+		fn flush(&mut self) -> io::Result<()> {
+			Ok(())
+		}
+	}
+
+	#[test]
+	fn test_drop_reports() {
+		let (release, stalled) = mpsc::channel();
+		let (mut writer, guard) = NonBlockingBuilder::default()
+			.buffered_lines_limit(1)
+			.lossy(true)
+			.finish(Stalled(stalled));
+		let mut reporter = DropReporter::new(vec![writer.error_counter()], Arc::default());
+		for _ in 0..5 {
+			writer.write_all(b"line\n").expect("Error writing");
+		}
+		let dropped = writer.error_counter().dropped_lines();
+		assert!(dropped >= 3, "{dropped}");
+
+		let output = CapturedOutput::default();
+		let config = StdoutLogsConfig {
+			format: LogFormat::Logfmt,
+			timestamp: Timestamp::None,
+			..Default::default()
+		};
+		let layers: Vec<BoxedLayer> = vec![stdout::layer(&config, output.clone())];
+		let subscriber = tracing_subscriber::registry().with(layers);
+		tracing::subscriber::with_default(subscriber, || {
+			reporter.report();
+			reporter.report();
+		});
+
+		let report = output.contents();
+		assert_eq!(report.lines().count(), 1, "{report}");
+		assert!(report.contains(&format!(" dropped_lines={dropped}")), "{report}");
+
+		drop(release);
+		drop(guard);
+	}
+
+	#[test]
+	fn test_writers_report_through_subscriber() {
+		let (release, stalled) = mpsc::channel();
+		let config = NonBlockingConfig {
+			buffered_lines: 1,
+			overflow: OverflowPolicy::Drop,
+			report_interval: Seconds(Duration::from_secs(3600)),
+		};
+		let (writer, stderr, guard) = writers(&config, Stalled(stalled), None::<io::Sink>);
+		assert!(stderr.is_none());
+
+		let output = CapturedOutput::default();
+		let config = StdoutLogsConfig {
+			format: LogFormat::Logfmt,
+			timestamp: Timestamp::None,
+			..Default::default()
+		};
+		let layers: Vec<BoxedLayer> =
+			vec![stdout::layer(&config, writer), stdout::layer(&config, output.clone())];
+		let subscriber = tracing_subscriber::registry().with(layers).with(guard.dispatch_capture());
+		let dispatch = Dispatch::new(subscriber);
+		tracing::dispatcher::with_default(&dispatch, || {
+			for _ in 0..5 {
+				tracing::info!("line");
+			}
+		});
+
+		// The last report is emitted when the guard is dropped, outside of the
+		// scope of the subscriber
+		drop(release);
+		drop(guard);
+		let report = output.contents();
+		assert!(report.contains("Dropped stdout log lines"), "{report}");
+	}
+}
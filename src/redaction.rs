@@ -0,0 +1,685 @@
+// SPDX-FileCopyrightText: 2025 Famedly GmbH (info@famedly.com)
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Redaction
+//!
+//! Module containing the layer removing the sensitive values from the events
+//! and spans before the outputs see them, from the [`RedactionConfig`].
+use std::{any::TypeId, collections::HashSet, error::Error, fmt};
+
+use hmac::{Hmac, Mac as _, digest::KeyInit};
+use regex::Regex;
+use sha2::Sha256;
+use tracing::{
+	Dispatch, Event, Metadata, Subscriber,
+	field::{DebugValue, Field, FieldSet, Value, Visit},
+	level_filters::LevelFilter,
+	span::{Attributes, Id, Record},
+	subscriber::Interest,
+};
+use tracing_subscriber::{Layer, layer::Context, registry::LookupSpan};
+
+use crate::config::{RedactionConfig, RedactionMode};
+
+/// Number of bytes of the HMAC kept in the hashed values
+pub(crate) const HASH_LEN: usize = 8;
+
+/// Hex encoding of the first `len` bytes of the HMAC-SHA256 of the data
+#[allow(clippy::expect_used)]
+pub(crate) fn hmac_hex(key: &str, data: &str, len: usize) -> String {
+	let mut mac = <Hmac<Sha256> as KeyInit>::new_from_slice(key.as_bytes())
+		.expect("HMAC accepts keys of any size");
+	mac.update(data.as_bytes());
+	mac.finalize().into_bytes().iter().take(len).map(|byte| format!("{byte:02x}")).collect()
+}
+
+/// Redacts the sensitive values
+#[derive(Debug)]
+pub(crate) struct Redactor {
+	/// Lower case names of the fields to redact
+	fields: HashSet<String>,
+	/// Union of the patterns to redact, if any
+	pattern: Option<Regex>,
+	/// How the values are redacted
+	mode: RedactionMode,
+}
+
+impl Redactor {
+	/// Compiles the patterns of the config
+	pub(crate) fn new(config: &RedactionConfig) -> Result<Self, regex::Error> {
+		let pattern = (!config.patterns.is_empty())
+			.then(|| {
+				let union: Vec<_> =
+					config.patterns.iter().map(|pattern| format!("(?:{pattern})")).collect();
+				Regex::new(&union.join("|"))
+			})
+			.transpose()?;
+		Ok(Self {
+			fields: config.fields.iter().map(|field| field.to_lowercase()).collect(),
+			pattern,
+			mode: config.mode.clone(),
+		})
+	}
+
+	/// Whether the values of the field are always redacted
+	fn is_denied(&self, name: &str) -> bool {
+		let name = name.to_lowercase();
+		let last = name.rsplit('.').next().unwrap_or_default();
+		self.fields.contains(&name) || self.fields.contains(last)
+	}
+
+	/// Whether nothing can be redacted from the values of the fields
+	fn skips(&self, fields: &FieldSet) -> bool {
+		self.pattern.is_none() && !fields.iter().any(|field| self.is_denied(field.name()))
+	}
+
+	/// Replacement of a sensitive value
+	fn replace(&self, value: &str) -> String {
+		match &self.mode {
+			RedactionMode::Mask(mask) => mask.clone(),
+			RedactionMode::Hash(key) => format!("hash:{}", hmac_hex(key, value, HASH_LEN)),
+		}
+	}
+
+	/// Redacts a text value of a field, `None` if nothing is redacted
+	fn redact(&self, name: &str, value: &str) -> Option<String> {
+		if self.is_denied(name) {
+			return Some(self.replace(value));
+		}
+		let pattern = self.pattern.as_ref()?;
+		pattern
+			.is_match(value)
+			.then(|| {
+				pattern.replace_all(value, |caps: &regex::Captures<'_>| self.replace(&caps[0]))
+			})
+			.map(std::borrow::Cow::into_owned)
+	}
+}
+
+/// Redacts a text value set on the OpenTelemetry span of a span with
+/// [`OpenTelemetrySpanExt`](tracing_opentelemetry::OpenTelemetrySpanExt),
+/// which bypasses the [`Redacted`] layer, with the redactor of that layer in
+/// the subscriber of the span if any
+#[cfg(any(feature = "axum", feature = "reqwest-middleware"))]
+pub(crate) fn redact_span_value(span: &tracing::Span, key: &str, value: String) -> String {
+	let redacted = span.with_subscriber(|(_, dispatch)| {
+		dispatch.downcast_ref::<Redactor>().and_then(|redactor| redactor.redact(key, &value))
+	});
+	redacted.flatten().unwrap_or(value)
+}
+
+/// Value of a field, copied to be recorded again
+enum Captured {
+	/// Signed integer
+	I64(i64),
+	/// Unsigned integer
+	U64(u64),
+	/// Floating point number
+	F64(f64),
+	/// Boolean
+	Bool(bool),
+	/// String
+	Str(String),
+	/// Value recorded with its `Debug` implementation, already formatted
+	Debug(DebugValue<Formatted>),
+	/// Error, already formatted
+	Error(Box<dyn Error + Send + Sync>),
+}
+
+impl Captured {
+	/// Value to record
+	fn as_value(&self) -> &dyn Value {
+		match self {
+			Self::I64(value) => value,
+			Self::U64(value) => value,
+			Self::F64(value) => value,
+			Self::Bool(value) => value,
+			Self::Str(value) => value,
+			Self::Debug(value) => value,
+			Self::Error(value) => value,
+		}
+	}
+}
+
+/// Text written as is by its `Debug` implementation
+struct Formatted(String);
+
+impl fmt::Debug for Formatted {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(&self.0)
+	}
+}
+
+/// Error whose message was redacted
+#[derive(Debug)]
+struct RedactedError(String);
+
+impl fmt::Display for RedactedError {
+	fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+		f.write_str(&self.0)
+	}
+}
+
+impl Error for RedactedError {}
+
+/// Visitor copying the values of the fields, redacted
+struct Redacting<'a> {
+	/// Redactor of the values
+	redactor: &'a Redactor,
+	/// Values, by index of their field
+	values: Vec<Option<Captured>>,
+	/// Whether a value was redacted
+	redacted: bool,
+}
+
+impl<'a> Redacting<'a> {
+	/// Copies the values of the fields of the set
+	fn new(redactor: &'a Redactor, fields: &FieldSet) -> Self {
+		Self { redactor, values: fields.iter().map(|_| None).collect(), redacted: false }
+	}
+
+	/// Stores the value of a field
+	fn store(&mut self, field: &Field, value: Captured) {
+		if let Some(slot) = self.values.get_mut(field.index()) {
+			*slot = Some(value);
+		}
+	}
+
+	/// Stores a value which isn't text, redacting it if the field is denied
+	fn store_plain(&mut self, field: &Field, value: Captured, text: impl FnOnce() -> String) {
+		if self.redactor.is_denied(field.name()) {
+			self.redacted = true;
+			self.store(field, Captured::Str(self.redactor.replace(&text())));
+		} else {
+			self.store(field, value);
+		}
+	}
+
+	/// Stores a text value, redacted
+	fn store_text(&mut self, field: &Field, text: String, wrap: fn(String) -> Captured) {
+		let text = match self.redactor.redact(field.name(), &text) {
+			Some(redacted) => {
+				self.redacted = true;
+				redacted
+			}
+			None => text,
+		};
+		self.store(field, wrap(text));
+	}
+
+	/// Values to record, if any was redacted
+	fn into_values(self) -> Option<Vec<Option<Captured>>> {
+		self.redacted.then_some(self.values)
+	}
+}
+
+impl Visit for Redacting<'_> {
+	fn record_f64(&mut self, field: &Field, value: f64) {
+		self.store_plain(field, Captured::F64(value), || value.to_string());
+	}
+
+	fn record_i64(&mut self, field: &Field, value: i64) {
+		self.store_plain(field, Captured::I64(value), || value.to_string());
+	}
+
+	fn record_u64(&mut self, field: &Field, value: u64) {
+		self.store_plain(field, Captured::U64(value), || value.to_string());
+	}
+
+	fn record_bool(&mut self, field: &Field, value: bool) {
+		self.store_plain(field, Captured::Bool(value), || value.to_string());
+	}
+
+	fn record_str(&mut self, field: &Field, value: &str) {
+		self.store_text(field, value.to_owned(), Captured::Str);
+	}
+
+	fn record_error(&mut self, field: &Field, value: &(dyn Error + 'static)) {
+		self.store_text(field, value.to_string(), |text| {
+			Captured::Error(Box::new(RedactedError(text)))
+		});
+	}
+
+	fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+		self.store_text(field, format!("{value:?}"), |text| {
+			Captured::Debug(tracing::field::debug(Formatted(text)))
+		});
+	}
+}
+
+/// References to the values to record
+fn value_refs(values: &[Option<Captured>]) -> Vec<Option<&dyn Value>> {
+	values.iter().map(|value| value.as_ref().map(Captured::as_value)).collect()
+}
+
+/// Layer passing the events and spans to the inner layer with their sensitive
+/// values redacted
+#[derive(Debug)]
+pub(crate) struct Redacted<L> {
+	/// Layer receiving the redacted data
+	inner: L,
+	/// Redactor of the values
+	redactor: Redactor,
+}
+
+impl<L> Redacted<L> {
+	/// Redacts the data passed to the layer
+	pub(crate) const fn new(inner: L, redactor: Redactor) -> Self {
+		Self { inner, redactor }
+	}
+}
+
+impl<S, L> Layer<S> for Redacted<L>
+where
+	S: Subscriber + for<'a> LookupSpan<'a>,
+	L: Layer<S>,
+{
+	fn on_register_dispatch(&self, subscriber: &Dispatch) {
+		self.inner.on_register_dispatch(subscriber);
+	}
+
+	fn on_layer(&mut self, subscriber: &mut S) {
+		self.inner.on_layer(subscriber);
+	}
+
+	fn register_callsite(&self, metadata: &'static Metadata<'static>) -> Interest {
+		self.inner.register_callsite(metadata)
+	}
+
+	fn enabled(&self, metadata: &Metadata<'_>, ctx: Context<'_, S>) -> bool {
+		self.inner.enabled(metadata, ctx)
+	}
+
+	fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+		let metadata = attrs.metadata();
+		if self.redactor.skips(metadata.fields()) {
+			return self.inner.on_new_span(attrs, id, ctx);
+		}
+		let mut visitor = Redacting::new(&self.redactor, metadata.fields());
+		attrs.record(&mut visitor);
+		let Some(values) = visitor.into_values() else {
+			return self.inner.on_new_span(attrs, id, ctx);
+		};
+		let refs = value_refs(&values);
+		let value_set = metadata.fields().value_set_all(&refs);
+		let redacted = if attrs.is_root() {
+			Attributes::new_root(metadata, &value_set)
+		} else if let Some(parent) = attrs.parent() {
+			Attributes::child_of(parent.clone(), metadata, &value_set)
+		} else {
+			Attributes::new(metadata, &value_set)
+		};
+		self.inner.on_new_span(&redacted, id, ctx);
+	}
+
+	fn max_level_hint(&self) -> Option<LevelFilter> {
+		self.inner.max_level_hint()
+	}
+
+	fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+		let Some(metadata) = ctx.metadata(id) else {
+			return self.inner.on_record(id, values, ctx);
+		};
+		if self.redactor.skips(metadata.fields()) {
+			return self.inner.on_record(id, values, ctx);
+		}
+		let mut visitor = Redacting::new(&self.redactor, metadata.fields());
+		values.record(&mut visitor);
+		let Some(redacted_values) = visitor.into_values() else {
+			return self.inner.on_record(id, values, ctx);
+		};
+		let refs = value_refs(&redacted_values);
+		let value_set = metadata.fields().value_set_all(&refs);
+		self.inner.on_record(id, &Record::new(&value_set), ctx);
+	}
+
+	fn on_follows_from(&self, span: &Id, follows: &Id, ctx: Context<'_, S>) {
+		self.inner.on_follows_from(span, follows, ctx);
+	}
+
+	fn event_enabled(&self, event: &Event<'_>, ctx: Context<'_, S>) -> bool {
+		self.inner.event_enabled(event, ctx)
+	}
+
+	fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+		let metadata = event.metadata();
+		if self.redactor.skips(metadata.fields()) {
+			return self.inner.on_event(event, ctx);
+		}
+		let mut visitor = Redacting::new(&self.redactor, metadata.fields());
+		event.record(&mut visitor);
+		let Some(values) = visitor.into_values() else {
+			return self.inner.on_event(event, ctx);
+		};
+		let refs = value_refs(&values);
+		let value_set = metadata.fields().value_set_all(&refs);
+		let redacted = if event.is_contextual() {
+			Event::new(metadata, &value_set)
+		} else {
+			Event::new_child_of(event.parent().cloned(), metadata, &value_set)
+		};
+		self.inner.on_event(&redacted, ctx);
+	}
+
+	fn on_enter(&self, id: &Id, ctx: Context<'_, S>) {
+		self.inner.on_enter(id, ctx);
+	}
+
+	fn on_exit(&self, id: &Id, ctx: Context<'_, S>) {
+		self.inner.on_exit(id, ctx);
+	}
+
+	fn on_close(&self, id: Id, ctx: Context<'_, S>) {
+		self.inner.on_close(id, ctx);
+	}
+
+	fn on_id_change(&self, old: &Id, new: &Id, ctx: Context<'_, S>) {
+		self.inner.on_id_change(old, new, ctx);
+	}
+
+	unsafe fn downcast_raw(&self, id: TypeId) -> Option<*const ()> {
+		if id == TypeId::of::<Self>() {
+			return Some(std::ptr::from_ref(self).cast());
+		}
+		// Lets the values set with `OpenTelemetrySpanExt` be redacted
+		if id == TypeId::of::<Redactor>() {
+			return Some(std::ptr::from_ref(&self.redactor).cast());
+		}
+		// SAFETY: forwarded to the inner layer, which upholds the contract
+		unsafe { self.inner.downcast_raw(id) }
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	#![allow(clippy::expect_used)]
+	use std::{collections::HashMap, error::Error, fs, io, net::UdpSocket, time::Duration};
+	#[cfg(unix)]
+	use std::{os::unix::net::UnixDatagram, path::Path};
+
+	use opentelemetry::Value;
+	use opentelemetry_appender_tracing::layer::OpenTelemetryTracingBridge;
+	use opentelemetry_sdk::logs::{InMemoryLogExporter, SdkLoggerProvider};
+	use tracing::level_filters::LevelFilter;
+	use tracing_subscriber::layer::SubscriberExt as _;
+
+	use super::{Redacted, Redactor};
+	use crate::{
+		OtelInitError, build_otel,
+		builder::BoxedLayer,
+		config::{
+			FileLogsConfig, LogFormat, OtelConfig, RedactionConfig, RedactionMode, Rotation,
+			StdoutLogsConfig, SyslogConfig, SyslogFacility, SyslogTransport, Timestamp,
+		},
+		file,
+		fixtures::{TestSpans, attribute},
+		stdout::{self, tests::CapturedOutput},
+		syslog::SyslogLayer,
+	};
+	#[cfg(unix)]
+	use crate::{config::JournaldConfig, journald::JournaldLayer};
+
+	/// Sensitive values logged by the tests
+	const SECRETS: [&str; 4] =
+		["s3cr3t-token", "alice@example.com", "@alice:example.org", "bob@example.com"];
+
+	/// Redaction of the access tokens, patient ids, email addresses and Matrix
+	/// user ids
+	fn redaction_config(mode: RedactionMode) -> RedactionConfig {
+		RedactionConfig {
+			fields: vec!["access_token".to_owned(), "PATIENT_ID".to_owned()],
+			patterns: vec![r"[\w.+-]+@[\w-]+\.[\w.]+".to_owned(), r"@[\w.=/-]+:[\w.-]+".to_owned()],
+			mode,
+		}
+	}
+
+	/// Logs sensitive values in spans and events
+	fn log_sensitive_values() {
+		let span = tracing::info_span!(
+			"request",
+			user.email = "alice@example.com",
+			patient_id = 4711,
+			room = tracing::field::Empty,
+		);
+		span.in_scope(|| {
+			span.record("room", "!room:example.org joined by @alice:example.org");
+			tracing::info!(access_token = "s3cr3t-token", "Login of bob@example.com");
+			let error = io::Error::other("@alice:example.org is not allowed");
+			tracing::error!(error = &error as &(dyn Error + 'static), "Access denied");
+		});
+	}
+
+	/// Number of events logged by the tests, plus the exception of the panic of
+	/// the request
+	const EVENTS: usize = if cfg!(feature = "axum") { 3 } else { 2 };
+
+	/// Prepares a request with sensitive headers to a traced router panicking
+	/// with a sensitive message, which the layers set on the span with
+	/// `OpenTelemetrySpanExt`
+	///
+	/// The router is built outside of the subscriber of the test, as the meter
+	/// provider logs its own events.
+	#[cfg(feature = "axum")]
+	fn sensitive_request(metrics: &crate::fixtures::TestMetrics) -> impl FnOnce() {
+		use axum::{Router, body::Body, routing::get};
+		use http::Request;
+		use tower::ServiceExt as _;
+
+		use crate::{
+			axum::{CatchPanicLayer, ServerTraceLayer, TraceResponseLayer},
+			config::{HttpCaptureConfig, TraceResponseConfig},
+		};
+
+		let capture =
+			HttpCaptureConfig { request_headers: vec!["x-user".to_owned()], ..Default::default() };
+		let trace_response =
+			TraceResponseLayer::new(&TraceResponseConfig::default()).expect("Invalid config");
+		let app = Router::new()
+			.route("/", get(async || -> &'static str { panic!("Unknown bob@example.com") }))
+			.layer(CatchPanicLayer::with_meter(&metrics.meter()))
+			.layer(trace_response)
+			.layer(ServerTraceLayer::default().with_capture(&capture).expect("Invalid config"));
+		let request = Request::get("/")
+			.header("x-user", "alice@example.com")
+			.header("x-request-id", "@alice:example.org")
+			.body(Body::empty())
+			.expect("Invalid request");
+		let runtime =
+			tokio::runtime::Builder::new_current_thread().build().expect("Error building runtime");
+		move || {
+			runtime.block_on(app.oneshot(request)).expect("Error sending request");
+		}
+	}
+
+	/// Adds a syslog layer sending to the returned server
+	fn syslog_server(layers: &mut Vec<BoxedLayer>) -> UdpSocket {
+		let server = UdpSocket::bind("127.0.0.1:0").expect("Error binding socket");
+		server.set_read_timeout(Some(Duration::from_secs(5))).expect("Error setting timeout");
+		let config = SyslogConfig {
+			enabled: true,
+			level: LevelFilter::TRACE.into(),
+			general_level: LevelFilter::TRACE.into(),
+			dependencies_levels: HashMap::new(),
+			transport: SyslogTransport::Udp(
+				server.local_addr().expect("Missing address").to_string(),
+			),
+			facility: SyslogFacility::Local0,
+			app_name: None,
+			hostname: None,
+		};
+		layers.push(Box::new(SyslogLayer::new(&config, "test").expect("Error opening socket")));
+		server
+	}
+
+	/// Adds a journald layer sending to the returned journal bound to `socket`
+	#[cfg(unix)]
+	fn journal_server(socket: &Path, layers: &mut Vec<BoxedLayer>) -> UnixDatagram {
+		let journal = UnixDatagram::bind(socket).expect("Error binding socket");
+		let config = JournaldConfig {
+			enabled: true,
+			level: LevelFilter::TRACE.into(),
+			general_level: LevelFilter::TRACE.into(),
+			dependencies_levels: HashMap::new(),
+			syslog_identifier: None,
+			field_prefix: None,
+			socket: socket.to_owned(),
+		};
+		layers.push(Box::new(JournaldLayer::new(&config, "test").expect("Error opening socket")));
+		journal
+	}
+
+	#[test]
+	fn test_nothing_leaks() {
+		let formats = [
+			LogFormat::Full,
+			LogFormat::Compact,
+			LogFormat::Pretty,
+			LogFormat::Json,
+			LogFormat::Logfmt,
+			LogFormat::Tree,
+			LogFormat::Ecs,
+			LogFormat::Gcp,
+			LogFormat::OtlpJson,
+		];
+		let outputs: Vec<_> = formats.iter().map(|_| CapturedOutput::default()).collect();
+		let log_exporter = InMemoryLogExporter::default();
+		let logger_provider =
+			SdkLoggerProvider::builder().with_simple_exporter(log_exporter.clone()).build();
+		let spans = TestSpans::new();
+
+		let mut layers: Vec<BoxedLayer> = formats
+			.iter()
+			.zip(&outputs)
+			.map(|(format, output)| {
+				let config =
+					StdoutLogsConfig { format: *format, ansi: false, ..Default::default() };
+				stdout::layer(&config, output.clone())
+			})
+			.collect();
+		layers.push(Box::new(OpenTelemetryTracingBridge::new(&logger_provider)));
+		layers.push(Box::new(spans.layer()));
+
+		let directory = tempfile::tempdir().expect("Error creating directory");
+		let file_config = FileLogsConfig {
+			enabled: true,
+			level: LevelFilter::TRACE.into(),
+			general_level: LevelFilter::TRACE.into(),
+			dependencies_levels: HashMap::new(),
+			format: LogFormat::Logfmt,
+			timestamp: Timestamp::None,
+			directory: directory.path().to_owned(),
+			prefix: Some("test".to_owned()),
+			rotation: Rotation::Never,
+			max_files: None,
+		};
+		let (file_writer, file_guard) =
+			file::writer(&file_config, "test").expect("Error opening log file");
+		layers.push(stdout::layer(&file_config.fmt_config(), file_writer));
+
+		let server = syslog_server(&mut layers);
+		#[cfg(unix)]
+		let journal = journal_server(&directory.path().join("journal.sock"), &mut layers);
+
+		let redactor = Redactor::new(&redaction_config(RedactionMode::default()))
+			.expect("Invalid redaction config");
+		let subscriber = tracing_subscriber::registry().with(Redacted::new(layers, redactor));
+		#[cfg(feature = "axum")]
+		let metrics = crate::fixtures::TestMetrics::new();
+		#[cfg(feature = "axum")]
+		let send_sensitive_request = sensitive_request(&metrics);
+		tracing::subscriber::with_default(subscriber, || {
+			log_sensitive_values();
+			#[cfg(feature = "axum")]
+			send_sensitive_request();
+		});
+
+		let logs = log_exporter.get_emitted_logs().expect("Missing logs");
+		let spans = spans.finished();
+		assert_eq!(logs.len(), EVENTS);
+		assert_eq!(spans.len(), EVENTS - 1);
+		drop(file_guard);
+		let file_logs =
+			fs::read_to_string(directory.path().join("test.log")).expect("Missing log file");
+		let mut buf = [0; 1024];
+		let syslog_messages = [(); EVENTS].map(|()| {
+			let len = server.recv(&mut buf).expect("Missing syslog message");
+			// Unescapes the structured data
+			String::from_utf8_lossy(&buf[..len]).replace("\\]", "]")
+		});
+		let mut recorded: Vec<_> = outputs
+			.iter()
+			.map(CapturedOutput::contents)
+			.chain([format!("{logs:?}"), format!("{spans:?}"), file_logs])
+			.chain(syslog_messages)
+			.collect();
+		#[cfg(unix)]
+		recorded.extend([(); EVENTS].map(|()| {
+			let len = journal.recv(&mut buf).expect("Missing journal record");
+			String::from_utf8_lossy(&buf[..len]).into_owned()
+		}));
+		for output in recorded {
+			assert!(output.contains("[REDACTED]"), "{output}");
+			for secret in SECRETS {
+				assert!(!output.contains(secret), "{secret} leaked in {output}");
+			}
+		}
+
+		assert_eq!(attribute(&spans[0], "patient_id"), Some(Value::from("[REDACTED]")));
+		assert_eq!(
+			attribute(&spans[0], "room"),
+			Some(Value::from("!room:example.org joined by [REDACTED]"))
+		);
+		#[cfg(feature = "axum")]
+		assert_request_redacted(&spans[1]);
+	}
+
+	/// Checks the values set on the span of the sensitive request
+	#[cfg(feature = "axum")]
+	fn assert_request_redacted(server: &opentelemetry_sdk::trace::SpanData) {
+		use opentelemetry::{Array, StringValue, trace::Status};
+
+		assert_eq!(
+			attribute(server, "http.request.header.x-user"),
+			Some(Value::Array(Array::String(vec![StringValue::from("[REDACTED]")])))
+		);
+		assert_eq!(
+			attribute(server, "http.request.header.x-request-id"),
+			Some(Value::from("[REDACTED]"))
+		);
+		assert_eq!(server.status, Status::error("Unknown [REDACTED]"));
+	}
+
+	#[test]
+	fn test_hash_mode() {
+		let output = CapturedOutput::default();
+		let config = StdoutLogsConfig { format: LogFormat::Logfmt, ..Default::default() };
+		let redactor = Redactor::new(&redaction_config(RedactionMode::Hash("key".to_owned())))
+			.expect("Invalid redaction config");
+		let layers = vec![stdout::layer(&config, output.clone())];
+		let subscriber = tracing_subscriber::registry().with(Redacted::new(layers, redactor));
+		tracing::subscriber::with_default(subscriber, || {
+			tracing::info!(user = "bob@example.com", "First");
+			tracing::info!(user = "bob@example.com", "Second");
+		});
+
+		let output = output.contents();
+		let hashes: Vec<_> =
+			output.lines().filter_map(|line| line.split_once(" user=")).map(|(_, h)| h).collect();
+		assert_eq!(hashes.len(), 2, "{output}");
+		assert_eq!(hashes[0], hashes[1]);
+		assert!(hashes[0].starts_with("hash:") && hashes[0].len() == 21, "{output}");
+	}
+
+	#[test]
+	fn test_invalid_pattern() {
+		let config = OtelConfig {
+			redaction: Some(RedactionConfig {
+				patterns: vec!["(".to_owned()],
+				..Default::default()
+			}),
+			..Default::default()
+		};
+		assert!(matches!(build_otel!(&config), Err(OtelInitError::RedactionPatternError(_))));
+	}
+}
@@ -6,3 +6,4 @@
 
 #[cfg(feature = "reqwest-middleware")]
 pub use reqwest_middleware;
+pub use tracing;
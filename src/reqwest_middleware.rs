@@ -6,16 +6,24 @@
 //!
 //! This module provides a reqwest middleware that will propagate the
 //! OpenTelemetry current context by setting the appropriated headers on the
-//! request. Use with the reexported [`reqwest_middleware`].
+//! request, and record the allowed headers in a client span. Use with the
+//! reexported [`reqwest_middleware`].
 use http::Extensions;
 use opentelemetry_http::HeaderInjector;
 use reqwest::{Request, Response};
 use reqwest_middleware::{Middleware, Next, Result};
-use tracing::Span;
+use tracing::{Instrument as _, Span, field::Empty};
 use tracing_opentelemetry::OpenTelemetrySpanExt as _;
 
+use crate::{OtelInitError, config::HttpCaptureConfig, http_capture::HeaderCapture};
+
 /// Middleware for [`reqwest_middleware`] to propagate the Otel context
 ///
+/// The default middleware doesn't open spans and propagates the current
+/// context. The one created with [`OtelMiddleware::new`] opens a client span
+/// per request, child of the current span, and records the allowed headers in
+/// it.
+///
 /// Example
 ///
 /// ```rust,no_run
@@ -34,7 +42,19 @@ use tracing_opentelemetry::OpenTelemetrySpanExt as _;
 /// }
 /// ```
 #[derive(Debug, Default)]
-pub struct OtelMiddleware;
+pub struct OtelMiddleware {
+	/// Headers recorded in the client spans, if the middleware opens them
+	capture: Option<HeaderCapture>,
+}
+
+impl OtelMiddleware {
+	/// Creates the middleware opening a client span per request and recording
+	/// the allowed request and response headers in it, failing if a header is
+	/// invalid or holds credentials. The client address is not recorded.
+	pub fn new(capture: &HttpCaptureConfig) -> std::result::Result<Self, OtelInitError> {
+		Ok(Self { capture: Some(HeaderCapture::new(capture)?) })
+	}
+}
 
 #[async_trait::async_trait]
 impl Middleware for OtelMiddleware {
@@ -44,10 +64,126 @@ impl Middleware for OtelMiddleware {
 		extensions: &mut Extensions,
 		next: Next<'_>,
 	) -> Result<Response> {
-		opentelemetry::global::get_text_map_propagator(|propagator| {
-			let cx = Span::current().context();
-			propagator.inject_context(&cx, &mut HeaderInjector(req.headers_mut()));
-		});
-		next.run(req, extensions).await
+		let Some(capture) = &self.capture else {
+			inject(&Span::current(), &mut req);
+			return next.run(req, extensions).await;
+		};
+
+		let span = client_span(&req);
+		inject(&span, &mut req);
+		capture.record_request(&span, req.headers());
+		let response = next.run(req, extensions).instrument(span.clone()).await;
+		match &response {
+			Ok(response) => {
+				capture.record_response(&span, response.headers());
+				let status = response.status().as_u16();
+				span.record("http.response.status_code", i64::from(status));
+				if status >= 400 {
+					span.record("otel.status_code", "ERROR");
+					span.record("error.type", status.to_string());
+				}
+			}
+			Err(_) => {
+				span.record("otel.status_code", "ERROR");
+				span.record("error.type", "_OTHER");
+			}
+		}
+		response
+	}
+}
+
+/// Injects the context of the span in the headers of the request
+fn inject(span: &Span, req: &mut Request) {
+	opentelemetry::global::get_text_map_propagator(|propagator| {
+		propagator.inject_context(&span.context(), &mut HeaderInjector(req.headers_mut()));
+	});
+}
+
+/// Opens the client span of a request, without its URL as it may hold
+/// credentials
+fn client_span(req: &Request) -> Span {
+	let method = req.method().as_str();
+	tracing::info_span!(
+		"HTTP request",
+		otel.name = method,
+		otel.kind = "client",
+		otel.status_code = Empty,
+		http.request.method = method,
+		http.response.status_code = Empty,
+		server.address = req.url().host_str(),
+		server.port = req.url().port_or_known_default().map(i64::from),
+		error.type = Empty,
+	)
+}
+
+#[cfg(test)]
+mod tests {
+	#![allow(clippy::expect_used)]
+	use axum::{Router, http::HeaderMap, routing::get};
+	use opentelemetry::{Array, StringValue, Value, trace::SpanKind};
+	use opentelemetry_sdk::propagation::TraceContextPropagator;
+	use reqwest_middleware::ClientBuilder;
+	use tracing::Instrument as _;
+
+	use super::OtelMiddleware;
+	use crate::{
+		config::HttpCaptureConfig,
+		fixtures::{TestSpans, attribute},
+	};
+
+	#[tokio::test]
+	async fn test_client_span() {
+		opentelemetry::global::set_text_map_propagator(TraceContextPropagator::new());
+		let spans = TestSpans::new();
+		let _default = spans.set_default();
+
+		let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.expect("Error binding");
+		let address = listener.local_addr().expect("Missing address");
+		let app = Router::new().route(
+			"/",
+			get(async |headers: HeaderMap| {
+				let traceparent = headers.get("traceparent").and_then(|value| value.to_str().ok());
+				([("x-served-by", "test")], traceparent.unwrap_or_default().to_owned())
+			}),
+		);
+		tokio::spawn(async move { axum::serve(listener, app).await });
+
+		let capture = HttpCaptureConfig {
+			request_headers: vec!["x-matrix-client".to_owned()],
+			response_headers: vec!["x-served-by".to_owned()],
+			..Default::default()
+		};
+		let middleware = OtelMiddleware::new(&capture).expect("Error creating middleware");
+		let client = ClientBuilder::new(reqwest::Client::new()).with(middleware).build();
+		let traceparent = async {
+			let response = client
+				.get(format!("http://{address}/"))
+				.header("x-matrix-client", "element")
+				.send()
+				.await
+				.expect("Error sending request");
+			response.text().await.expect("Error reading body")
+		}
+		.instrument(tracing::info_span!("parent"))
+		.await;
+
+		let spans = spans.finished();
+		let span = |name: &str| spans.iter().find(|span| span.name == name).expect("Missing span");
+		let (client_span, parent) = (span("GET"), span("parent"));
+		assert_eq!(client_span.span_kind, SpanKind::Client);
+		assert_eq!(client_span.parent_span_id, parent.span_context.span_id());
+		assert!(traceparent.contains(&client_span.span_context.span_id().to_string()));
+		let header =
+			|value: &str| Value::Array(Array::String(vec![StringValue::from(value.to_owned())]));
+		assert_eq!(
+			attribute(client_span, "http.request.header.x-matrix-client"),
+			Some(header("element"))
+		);
+		assert_eq!(
+			attribute(client_span, "http.response.header.x-served-by"),
+			Some(header("test"))
+		);
+		assert_eq!(attribute(client_span, "http.response.status_code"), Some(Value::I64(200)));
+		assert!(parent.attributes.iter().all(|kv| !kv.key.as_str().starts_with("http.")));
 	}
 }
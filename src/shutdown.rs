@@ -0,0 +1,503 @@
+// SPDX-FileCopyrightText: 2025 Famedly GmbH (info@famedly.com)
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Providers shutdown
+//!
+//! Module containing the asynchronous flushing and shutdown of the providers
+//! held by the [`ProvidersGuard`], and the [`GracefulShutdown`] helper to stop
+//! a server and its telemetry on SIGTERM/SIGINT.
+use std::{
+	future::Future,
+	sync::{Arc, OnceLock},
+	time::Duration,
+};
+
+use opentelemetry_sdk::{
+	error::{OTelSdkError, OTelSdkResult},
+	logs::SdkLoggerProvider,
+	metrics::SdkMeterProvider,
+	trace::SdkTracerProvider,
+};
+use tokio::time::Instant;
+
+use crate::ProvidersGuard;
+
+/// Outcome of flushing or shutting down the providers, per signal
+///
+/// Each field is `None` if exporting the signal is disabled.
+#[derive(Debug)]
+pub struct ProvidersReport {
+	/// Outcome for the logger provider
+	pub logs: Option<OTelSdkResult>,
+	/// Outcome for the tracer provider
+	pub traces: Option<OTelSdkResult>,
+	/// Outcome for the meter provider
+	pub metrics: Option<OTelSdkResult>,
+}
+
+impl ProvidersReport {
+	/// Whether no provider failed
+	#[must_use]
+	pub fn is_ok(&self) -> bool {
+		self.failures().next().is_none()
+	}
+
+	/// Signals whose provider failed, with their error
+	pub fn failures(&self) -> impl Iterator<Item = (&'static str, &OTelSdkError)> {
+		[("logs", &self.logs), ("traces", &self.traces), ("metrics", &self.metrics)]
+			.into_iter()
+			.filter_map(|(signal, outcome)| Some((signal, outcome.as_ref()?.as_ref().err()?)))
+	}
+}
+
+/// Runs a blocking operation on a provider, giving up once the deadline is
+/// reached
+///
+/// The operation runs on a detached thread rather than on the blocking pool
+/// of the runtime, which waits for its tasks when shutting down, so an
+/// operation still running past the deadline doesn't hold up the exit.
+async fn run_until<P: Clone + Send + Sync + 'static>(
+	provider: Option<&P>,
+	deadline: Instant,
+	operation: fn(&P, Duration) -> OTelSdkResult,
+) -> Option<OTelSdkResult> {
+	let provider = provider?.clone();
+	let timeout = deadline.saturating_duration_since(Instant::now());
+	let (done, result) = tokio::sync::oneshot::channel();
+	let spawned = std::thread::Builder::new().name("otel-shutdown".to_owned()).spawn(move || {
+		let _ = done.send(operation(&provider, timeout));
+	});
+	if let Err(err) = spawned {
+		return Some(Err(OTelSdkError::InternalFailure(format!("Could not spawn thread: {err}"))));
+	}
+	Some(match tokio::time::timeout_at(deadline, result).await {
+		Ok(Ok(result)) => result,
+		Ok(Err(_)) => Err(OTelSdkError::InternalFailure("Operation panicked".to_owned())),
+		Err(_) => Err(OTelSdkError::Timeout(timeout)),
+	})
+}
+
+impl ProvidersGuard {
+	/// Flushes all the providers concurrently, waiting at most `timeout`
+	///
+	/// The providers can't flush with a timeout, so the flush itself is not
+	/// bounded: a provider still flushing at the deadline is reported as timed
+	/// out and goes on flushing in the background.
+	pub async fn force_flush(&self, timeout: Duration) -> ProvidersReport {
+		let deadline = Instant::now() + timeout;
+		let (logs, traces, metrics) = tokio::join!(
+			run_until(self.logger_provider.as_ref(), deadline, |provider, _| provider
+				.force_flush()),
+			run_until(self.tracer_provider.as_ref(), deadline, |provider, _| provider
+				.force_flush()),
+			run_until(self.meter_provider.as_ref(), deadline, |provider, _| provider.force_flush()),
+		);
+		ProvidersReport { logs, traces, metrics }
+	}
+
+	/// Shuts down all the providers concurrently, waiting at most `timeout`.
+	/// The buffered stdout logs are flushed first, so the providers still
+	/// export the last report of the dropped lines, then pending data is
+	/// exported before the providers stop and the log files are flushed.
+	pub async fn shutdown(mut self, timeout: Duration) -> ProvidersReport {
+		let deadline = Instant::now() + timeout;
+		drop(self.stdout_guard.take());
+		let (logger_provider, tracer_provider, meter_provider) =
+			(self.logger_provider.take(), self.tracer_provider.take(), self.meter_provider.take());
+		let (logs, traces, metrics) = tokio::join!(
+			run_until(logger_provider.as_ref(), deadline, SdkLoggerProvider::shutdown_with_timeout),
+			run_until(tracer_provider.as_ref(), deadline, SdkTracerProvider::shutdown_with_timeout),
+			run_until(meter_provider.as_ref(), deadline, SdkMeterProvider::shutdown_with_timeout),
+		);
+		drop(self.file_guard.take());
+		ProvidersReport { logs, traces, metrics }
+	}
+}
+
+/// Default deadline of the [`GracefulShutdown`], matching the default
+/// termination grace period of Kubernetes pods
+const DEFAULT_GRACE_PERIOD: Duration = Duration::from_secs(30);
+
+/// Graceful shutdown of a server and its telemetry
+///
+/// [`GracefulShutdown::signal`] resolves on SIGTERM or SIGINT, so the server
+/// stops accepting requests and drains the in-flight ones. Once it's drained,
+/// [`GracefulShutdown::finish`] flushes and shuts down the providers within
+/// what's left of the deadline, counted from the reception of the signal. Set
+/// the deadline below the termination grace period of the pod.
+///
+/// Example
+///
+/// ```rust,no_run
+/// use std::time::Duration;
+///
+/// use axum::routing::{Router, get};
+/// use rust_telemetry::{
+/// 	config::OtelConfig, init_otel, shutdown::GracefulShutdown,
+/// };
+///
+/// #[tokio::main]
+/// async fn main() {
+/// 	let guard = init_otel!(&OtelConfig::default()).unwrap();
+/// 	let shutdown =
+/// 		GracefulShutdown::new(guard).with_deadline(Duration::from_secs(25));
+///
+/// 	let app = Router::new().route("/", get("Test"));
+/// 	let listener =
+/// 		tokio::net::TcpListener::bind("127.0.0.1:8000").await.unwrap();
+/// 	axum::serve(listener, app)
+/// 		.with_graceful_shutdown(shutdown.signal())
+/// 		.await
+/// 		.unwrap();
+///
+/// 	shutdown.finish().await;
+/// }
+/// ```
+#[derive(Debug)]
+pub struct GracefulShutdown {
+	/// Providers to shutdown
+	guard: ProvidersGuard,
+	/// Maximum time from the reception of the signal to the end of the
+	/// telemetry shutdown
+	deadline: Duration,
+	/// When the shutdown signal was received
+	signaled_at: Arc<OnceLock<Instant>>,
+}
+
+impl GracefulShutdown {
+	/// Creates a graceful shutdown for the providers of the guard, with a
+	/// deadline of 30 seconds
+	#[must_use]
+	pub fn new(guard: ProvidersGuard) -> Self {
+		Self { guard, deadline: DEFAULT_GRACE_PERIOD, signaled_at: Arc::default() }
+	}
+
+	/// Sets the maximum time from the reception of the signal to the end of
+	/// the telemetry shutdown
+	#[must_use]
+	pub const fn with_deadline(mut self, deadline: Duration) -> Self {
+		self.deadline = deadline;
+		self
+	}
+
+	/// Future resolving when the process receives SIGTERM or SIGINT, for use
+	/// with `axum::serve(...).with_graceful_shutdown`
+	///
+	/// On Unix, the signal handlers are installed when this function is
+	/// called, so the signals received before the future is polled are not
+	/// missed.
+	pub fn signal(&self) -> impl Future<Output = ()> + Send + 'static {
+		let signaled_at = self.signaled_at.clone();
+		let (terminate, interrupt) = shutdown_signals();
+		async move {
+			let signal = tokio::select! {
+				() = terminate => "SIGTERM",
+				() = interrupt => "SIGINT",
+			};
+			signaled_at.get_or_init(Instant::now);
+			tracing::info!("Received {signal}, waiting for the server to drain");
+		}
+	}
+
+	/// Flushes and shuts down the providers within what's left of the
+	/// deadline. Call it once the server is drained.
+	pub async fn finish(self) -> ProvidersReport {
+		let signaled_at = *self.signaled_at.get_or_init(Instant::now);
+		let drained_in = signaled_at.elapsed();
+		tracing::info!("Server drained in {drained_in:?}, shutting down the telemetry");
+
+		let started = Instant::now();
+		let timeout = self.deadline.saturating_sub(drained_in);
+		let report = self.guard.shutdown(timeout).await;
+		let elapsed = started.elapsed();
+
+		if report.is_ok() {
+			tracing::info!("Telemetry shut down in {elapsed:?}");
+		}
+		for (signal, err) in report.failures() {
+			tracing::error!("Could not shutdown the {signal} provider in {elapsed:?}: {err}");
+		}
+		tracing::info!("Shutdown completed in {:?}", signaled_at.elapsed());
+		report
+	}
+}
+
+/// Futures resolving when the process receives SIGTERM and SIGINT, with their
+/// handlers installed right away
+#[cfg(unix)]
+fn shutdown_signals()
+-> (impl Future<Output = ()> + Send + 'static, impl Future<Output = ()> + Send + 'static) {
+	use tokio::signal::unix::SignalKind;
+
+	(
+		unix_signal(SignalKind::terminate(), "SIGTERM"),
+		unix_signal(SignalKind::interrupt(), "SIGINT"),
+	)
+}
+
+/// Future resolving when the process receives the signal, never resolving if
+/// its handler can't be installed
+#[cfg(unix)]
+fn unix_signal(
+	kind: tokio::signal::unix::SignalKind,
+	name: &'static str,
+) -> impl Future<Output = ()> + Send + 'static {
+	let signal = tokio::signal::unix::signal(kind)
+		.inspect_err(|err| tracing::error!("Could not install the {name} handler: {err}"))
+		.ok();
+	async move {
+		match signal {
+			Some(mut signal) => {
+				signal.recv().await;
+			}
+			None => std::future::pending().await,
+		}
+	}
+}
+
+/// Futures resolving when the process receives SIGTERM, which never happens
+/// outside of Unix, and Ctrl-C, whose handler is installed when first polled
+#[cfg(not(unix))]
+fn shutdown_signals()
+-> (impl Future<Output = ()> + Send + 'static, impl Future<Output = ()> + Send + 'static) {
+	let interrupt = async {
+		if let Err(err) = tokio::signal::ctrl_c().await {
+			tracing::error!("Could not install the Ctrl-C handler: {err}");
+			std::future::pending::<()>().await;
+		}
+	};
+	(std::future::pending(), interrupt)
+}
+
+#[cfg(test)]
+mod tests {
+	#![allow(clippy::expect_used)]
+	use std::{
+		io,
+		sync::{
+			Arc,
+			atomic::{AtomicBool, Ordering},
+			mpsc,
+		},
+		time::Duration,
+	};
+
+	use famedly_rust_utils::duration::Seconds;
+	use opentelemetry_sdk::{
+		error::{OTelSdkError, OTelSdkResult},
+		logs::{LogBatch, LogExporter, SdkLoggerProvider},
+		trace::{SdkTracerProvider, SpanData, SpanExporter},
+	};
+	use tracing::Dispatch;
+	use tracing_opentelemetry::MetricsLayer;
+	use tracing_subscriber::layer::SubscriberExt as _;
+
+	use super::GracefulShutdown;
+	use crate::{
+		ProvidersGuard, build_otel,
+		builder::BoxedLayer,
+		config::{
+			ExporterConfig, LogFormat, NonBlockingConfig, OtelConfig, OverflowPolicy,
+			ProviderConfig, StdoutLogsConfig,
+		},
+		fixtures::{TestMetrics, flat_metrics},
+		non_blocking::{self, tests::Stalled},
+		stdout,
+	};
+
+	/// Config exporting all the signals to an endpoint where nothing listens
+	fn unreachable_exporter_config() -> OtelConfig {
+		let enabled = Some(ProviderConfig { enabled: true, ..Default::default() });
+		OtelConfig {
+			exporter: Some(ExporterConfig {
+				endpoint: url::Url::parse("http://127.0.0.1:9").expect("Invalid URL").into(),
+				logs: enabled.clone(),
+				traces: enabled.clone(),
+				metrics: enabled,
+				..Default::default()
+			}),
+			..Default::default()
+		}
+	}
+
+	#[tokio::test]
+	async fn test_shutdown_without_providers() {
+		let (_, guard) = build_otel!(&OtelConfig::for_tests()).expect("Error initializing Otel");
+		let report = guard.shutdown(Duration::from_secs(1)).await;
+		assert!(report.is_ok());
+		assert!(report.logs.is_none() && report.traces.is_none() && report.metrics.is_none());
+	}
+
+	#[tokio::test(flavor = "multi_thread")]
+	async fn test_shutdown_reports_per_signal() {
+		let (_, guard) =
+			build_otel!(&unreachable_exporter_config()).expect("Error initializing Otel");
+		let report = guard.shutdown(Duration::from_secs(5)).await;
+		assert!(report.logs.is_some() && report.traces.is_some() && report.metrics.is_some());
+	}
+
+	#[tokio::test(flavor = "multi_thread")]
+	async fn test_shutdown_respects_deadline() {
+		let (_, guard) =
+			build_otel!(&unreachable_exporter_config()).expect("Error initializing Otel");
+		let started = std::time::Instant::now();
+		let _ = guard.shutdown(Duration::from_millis(1)).await;
+		assert!(started.elapsed() < Duration::from_secs(1));
+	}
+
+	#[cfg(feature = "testing")]
+	#[tokio::test(flavor = "multi_thread")]
+	async fn test_force_flush_exports_all_signals() {
+		let collector =
+			crate::testing::MockCollector::start().await.expect("Error starting collector");
+		let mut config = unreachable_exporter_config();
+		if let Some(exporter) = config.exporter.as_mut() {
+			exporter.endpoint = collector.endpoint().into();
+		}
+		let (subscriber, guard) = build_otel!(&config).expect("Error initializing Otel");
+		tracing::subscriber::with_default(subscriber, || {
+			tracing::info_span!("flushed").in_scope(|| {
+				tracing::info!(monotonic_counter.flushed = 1_u64, "Flushed");
+			});
+		});
+
+		let report = guard.force_flush(Duration::from_secs(10)).await;
+		assert!(report.is_ok(), "{report:?}");
+		assert!(!collector.traces().is_empty());
+		assert!(!collector.logs().is_empty());
+		assert!(!collector.metrics().is_empty());
+	}
+
+	#[test]
+	fn test_shutdown_doesnt_hold_up_runtime() {
+		let guard = ProvidersGuard {
+			logger_provider: Some(
+				SdkLoggerProvider::builder().with_simple_exporter(SlowLogExporter).build(),
+			),
+			tracer_provider: None,
+			meter_provider: None,
+			stdout_guard: None,
+			file_guard: None,
+		};
+		let started = std::time::Instant::now();
+		let runtime = tokio::runtime::Builder::new_current_thread()
+			.enable_all()
+			.build()
+			.expect("Error building runtime");
+		let report = runtime.block_on(guard.shutdown(Duration::from_millis(100)));
+		drop(runtime);
+		assert!(matches!(report.logs, Some(Err(OTelSdkError::Timeout(_)))), "{report:?}");
+		assert!(started.elapsed() < Duration::from_secs(1));
+	}
+
+	#[tokio::test]
+	async fn test_shutdown_exports_last_drop_report() {
+		let metrics = TestMetrics::new();
+		let (release, stalled) = mpsc::channel();
+		let config = NonBlockingConfig {
+			buffered_lines: 1,
+			overflow: OverflowPolicy::Drop,
+			report_interval: Seconds(Duration::from_secs(3600)),
+		};
+		let (writer, _, stdout_guard) =
+			non_blocking::writers(&config, Stalled(stalled), None::<io::Sink>);
+		let config = StdoutLogsConfig { format: LogFormat::Logfmt, ..Default::default() };
+		let layers: Vec<BoxedLayer> = vec![stdout::layer(&config, writer)];
+		let dispatch = Dispatch::new(
+			tracing_subscriber::registry()
+				.with(layers)
+				.with(MetricsLayer::new(metrics.provider().clone()))
+				.with(stdout_guard.dispatch_capture()),
+		);
+		tracing::dispatcher::with_default(&dispatch, || {
+			for _ in 0..5 {
+				tracing::info!("line");
+			}
+		});
+		drop(release);
+
+		let guard = ProvidersGuard {
+			logger_provider: None,
+			tracer_provider: None,
+			meter_provider: Some(metrics.provider().clone()),
+			stdout_guard: Some(stdout_guard),
+			file_guard: None,
+		};
+		let report = guard.shutdown(Duration::from_secs(5)).await;
+		assert!(report.is_ok(), "{report:?}");
+		let exported = metrics.exported();
+		assert!(
+			flat_metrics(&exported)
+				.iter()
+				.any(|metric| metric.name() == "telemetry.stdout.dropped_lines"),
+			"{exported:?}"
+		);
+	}
+
+	/// Log exporter taking longer than the deadline to shutdown
+	#[derive(Debug)]
+	struct SlowLogExporter;
+
+	impl LogExporter for SlowLogExporter {
+		async fn export(&self, _batch: LogBatch<'_>) -> OTelSdkResult {
+			Ok(())
+		}
+
+		fn shutdown_with_timeout(&self, timeout: Duration) -> OTelSdkResult {
+			std::thread::sleep(timeout + Duration::from_secs(1));
+			Ok(())
+		}
+	}
+
+	/// Span exporter recording whether it was shutdown
+	#[derive(Debug, Clone, Default)]
+	struct ShutdownSpanExporter(Arc<AtomicBool>);
+
+	impl SpanExporter for ShutdownSpanExporter {
+		async fn export(&self, _batch: Vec<SpanData>) -> OTelSdkResult {
+			Ok(())
+		}
+
+		fn shutdown_with_timeout(&self, _timeout: Duration) -> OTelSdkResult {
+			self.0.store(true, Ordering::SeqCst);
+			Ok(())
+		}
+	}
+
+	#[test]
+	fn test_drop_shuts_down_concurrently() {
+		let span_exporter = ShutdownSpanExporter::default();
+		let guard = ProvidersGuard {
+			logger_provider: Some(
+				SdkLoggerProvider::builder().with_simple_exporter(SlowLogExporter).build(),
+			),
+			tracer_provider: Some(
+				SdkTracerProvider::builder().with_simple_exporter(span_exporter.clone()).build(),
+			),
+			meter_provider: None,
+			stdout_guard: None,
+			file_guard: None,
+		};
+		drop(guard);
+		assert!(span_exporter.0.load(Ordering::SeqCst));
+	}
+
+	#[cfg(unix)]
+	#[tokio::test]
+	async fn test_graceful_shutdown_on_sigterm() {
+		let (_, guard) = build_otel!(&OtelConfig::for_tests()).expect("Error initializing Otel");
+		let shutdown = GracefulShutdown::new(guard).with_deadline(Duration::from_secs(5));
+		let signal = shutdown.signal();
+
+		// SAFETY: raising a signal has no memory safety requirement, and the
+		// handler installed by `signal` keeps it from terminating the process
+		let raised = unsafe { libc::raise(libc::SIGTERM) };
+		assert_eq!(raised, 0, "Error raising SIGTERM");
+
+		tokio::time::timeout(Duration::from_secs(5), signal)
+			.await
+			.expect("Signal future did not resolve");
+		assert!(shutdown.finish().await.is_ok());
+	}
+}
@@ -0,0 +1,388 @@
+// SPDX-FileCopyrightText: 2025 Famedly GmbH (info@famedly.com)
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Stdout logs
+//!
+//! Module building the layer writing the logs to stdout, and optionally the
+//! most severe ones to stderr, from the [`StdoutLogsConfig`].
+use tracing_subscriber::{
+	Registry,
+	fmt::{
+		Layer, MakeWriter,
+		format::{DefaultFields, FmtSpan, Format, Full, JsonFields},
+		writer::MakeWriterExt as _,
+	},
+};
+use tracing_tree::HierarchicalLayer;
+
+use crate::{
+	builder::BoxedLayer,
+	config::{LogFormat, SpanEvent, StdoutLogsConfig, Timestamp},
+	format::{LogSchema, Logfmt, LogfmtFields, RecordShape, Timer, VendorJson, WithTraceFields},
+};
+
+/// Indentation of the nested spans in the tree format
+const TREE_INDENT: usize = 2;
+
+impl From<SpanEvent> for FmtSpan {
+	fn from(event: SpanEvent) -> Self {
+		match event {
+			SpanEvent::New => FmtSpan::NEW,
+			SpanEvent::Enter => FmtSpan::ENTER,
+			SpanEvent::Exit => FmtSpan::EXIT,
+			SpanEvent::Close => FmtSpan::CLOSE,
+		}
+	}
+}
+
+/// Switches a layer to a vendor JSON schema
+fn vendor_json<W>(
+	layer: Layer<Registry, DefaultFields, Format<Full, Timer>, W>,
+	config: &StdoutLogsConfig,
+	schema: LogSchema,
+) -> Layer<Registry, JsonFields, VendorJson, W>
+where
+	W: for<'writer> MakeWriter<'writer> + 'static,
+{
+	layer
+		.event_format(VendorJson { schema, gcp_project_id: config.gcp_project_id.clone() })
+		.fmt_fields(JsonFields::new())
+}
+
+/// Builds the layer writing the logs in the configured format
+pub(crate) fn layer<W>(config: &StdoutLogsConfig, make_writer: W) -> BoxedLayer
+where
+	W: for<'writer> MakeWriter<'writer> + Send + Sync + 'static,
+{
+	let span_events = config
+		.span_events
+		.iter()
+		.fold(FmtSpan::NONE, |span_events, event| span_events | FmtSpan::from(*event));
+	let timer = Timer(config.timestamp);
+	let fmt_layer = |make_writer: W| {
+		let layer = tracing_subscriber::fmt::layer()
+			.with_writer(make_writer)
+			.with_ansi(config.ansi)
+			.with_target(config.target)
+			.with_file(config.file_line)
+			.with_line_number(config.file_line)
+			.with_thread_ids(config.thread_ids)
+			.with_thread_names(true)
+			.with_span_events(span_events.clone());
+		match config.timestamp {
+			Timestamp::None => layer.without_time().with_timer(timer),
+			_ => layer.with_timer(timer),
+		}
+	};
+
+	let (trace_fields, ansi) = (&config.trace_fields, config.ansi);
+	match config.log_format() {
+		LogFormat::Full => Box::new(fmt_layer(make_writer).map_event_format(|format| {
+			WithTraceFields::new(trace_fields, format.with_ansi(ansi), RecordShape::Line)
+		})),
+		LogFormat::Compact => {
+			Box::new(fmt_layer(make_writer).compact().map_event_format(|format| {
+				WithTraceFields::new(trace_fields, format.with_ansi(ansi), RecordShape::Line)
+			}))
+		}
+		LogFormat::Pretty => Box::new(fmt_layer(make_writer).pretty().map_event_format(|format| {
+			WithTraceFields::new(trace_fields, format.with_ansi(ansi), RecordShape::Pretty)
+		})),
+		LogFormat::Json => Box::new(fmt_layer(make_writer).json().map_event_format(|format| {
+			WithTraceFields::new(trace_fields, format, RecordShape::Json)
+		})),
+		LogFormat::Logfmt => Box::new(
+			fmt_layer(make_writer)
+				.event_format(WithTraceFields::new(
+					trace_fields,
+					Logfmt {
+						timer,
+						target: config.target,
+						file_line: config.file_line,
+						thread_ids: config.thread_ids,
+					},
+					RecordShape::Line,
+				))
+				.fmt_fields(LogfmtFields),
+		),
+		LogFormat::Ecs => Box::new(vendor_json(fmt_layer(make_writer), config, LogSchema::Ecs)),
+		LogFormat::Gcp => Box::new(vendor_json(fmt_layer(make_writer), config, LogSchema::Gcp)),
+		LogFormat::OtlpJson => {
+			Box::new(vendor_json(fmt_layer(make_writer), config, LogSchema::OtlpJson))
+		}
+		LogFormat::Tree => Box::new(
+			HierarchicalLayer::new(TREE_INDENT)
+				.with_writer(make_writer)
+				.with_timer(timer)
+				.with_ansi(config.ansi)
+				.with_targets(config.target)
+				.with_thread_ids(config.thread_ids)
+				.with_thread_names(true)
+				.with_verbose_entry(
+					config
+						.span_events
+						.iter()
+						.any(|e| matches!(e, SpanEvent::New | SpanEvent::Enter)),
+				)
+				.with_verbose_exit(
+					config
+						.span_events
+						.iter()
+						.any(|e| matches!(e, SpanEvent::Exit | SpanEvent::Close)),
+				),
+		),
+	}
+}
+
+/// Builds the layer writing the events at or above the `stderr_level` to
+/// `stderr`, and the other ones to `stdout`
+pub(crate) fn routed_layer<O, E>(config: &StdoutLogsConfig, stdout: O, stderr: E) -> BoxedLayer
+where
+	O: for<'writer> MakeWriter<'writer> + Send + Sync + 'static,
+	E: for<'writer> MakeWriter<'writer> + Send + Sync + 'static,
+{
+	match config.stderr_level.and_then(|level| level.0.into_level()) {
+		Some(level) => layer(config, stderr.with_max_level(level).or_else(stdout)),
+		None => layer(config, stdout),
+	}
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+	#![allow(clippy::expect_used)]
+	use std::{
+		io,
+		sync::{Arc, Mutex, PoisonError},
+	};
+
+	use tracing_subscriber::{fmt::MakeWriter, layer::SubscriberExt as _};
+
+	use super::{layer, routed_layer};
+	use crate::config::{LogFormat, SpanEvent, StdoutLogsConfig, Timestamp};
+
+	/// Writer keeping the logs in memory
+	#[derive(Debug, Clone, Default)]
+	pub(crate) struct CapturedOutput(Arc<Mutex<Vec<u8>>>);
+
+	impl CapturedOutput {
+		/// Logs written so far
+		pub(crate) fn contents(&self) -> String {
+			String::from_utf8_lossy(&self.0.lock().unwrap_or_else(PoisonError::into_inner))
+				.into_owned()
+		}
+	}
+
+	impl io::Write for CapturedOutput {
+		fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+			self.0.lock().unwrap_or_else(PoisonError::into_inner).extend_from_slice(buf);
+			Ok(buf.len())
+		}
+
+		fn flush(&mut self) -> io::Result<()> {
+			Ok(())
+		}
+	}
+
+	impl MakeWriter<'_> for CapturedOutput {
+		type Writer = Self;
+
+		fn make_writer(&self) -> Self::Writer {
+			self.clone()
+		}
+	}
+
+	/// Logs an event inside a span with the given config and returns the
+	/// output
+	fn log_with(config: &StdoutLogsConfig) -> String {
+		let output = CapturedOutput::default();
+		let subscriber = tracing_subscriber::registry().with(layer(config, output.clone()));
+		tracing::subscriber::with_default(subscriber, || {
+			tracing::info_span!("request", user = "alice").in_scope(|| {
+				tracing::info!(status = 200, "Request \"done\"");
+			});
+		});
+		output.contents()
+	}
+
+	#[test]
+	fn test_logfmt_format() {
+		let output = log_with(&StdoutLogsConfig {
+			format: LogFormat::Logfmt,
+			ansi: false,
+			..Default::default()
+		});
+		assert!(output.starts_with("ts="), "{output}");
+		assert!(output.contains(" level=info target=rust_telemetry::stdout::tests "), "{output}");
+		assert!(output.contains(" user=alice span=request "), "{output}");
+		assert!(output.contains(r#"msg="Request \"done\"" status=200"#), "{output}");
+	}
+
+	#[test]
+	#[allow(deprecated)]
+	fn test_json_output_alias() {
+		let config = StdoutLogsConfig { json_output: true, ..Default::default() };
+		assert_eq!(config.log_format(), LogFormat::Json);
+		let output = log_with(&config);
+		assert!(output.starts_with('{'), "{output}");
+		assert!(output.contains(r#""status":200"#), "{output}");
+
+		let config = StdoutLogsConfig {
+			json_output: true,
+			format: LogFormat::Compact,
+			..Default::default()
+		};
+		assert_eq!(config.log_format(), LogFormat::Compact);
+	}
+
+	#[test]
+	fn test_text_options() {
+		let output = log_with(&StdoutLogsConfig {
+			format: LogFormat::Compact,
+			ansi: false,
+			target: false,
+			file_line: true,
+			span_events: vec![SpanEvent::Close],
+			..Default::default()
+		});
+		assert!(!output.contains("rust_telemetry::stdout::tests"), "{output}");
+		assert!(output.contains("src/stdout.rs:"), "{output}");
+		assert!(output.contains("close"), "{output}");
+		assert!(!output.contains('\u{1b}'), "{output}");
+	}
+
+	#[test]
+	fn test_tree_format() {
+		let output = log_with(&StdoutLogsConfig {
+			format: LogFormat::Tree,
+			ansi: false,
+			..Default::default()
+		});
+		let mut lines = output.lines();
+		let span = lines.next().expect("Missing span line");
+		let event = lines.next().expect("Missing event line");
+		assert!(span.contains("request") && span.contains("user=\"alice\""), "{output}");
+		assert!(event.contains(" INFO ") && event.contains("Request"), "{output}");
+	}
+
+	/// Logs an event with the given vendor format and parses the record
+	fn vendor_record(format: LogFormat) -> serde_json::Value {
+		let output = log_with(&StdoutLogsConfig { format, ..Default::default() });
+		serde_json::from_str(&output).expect("Invalid JSON record")
+	}
+
+	#[test]
+	fn test_ecs_format() {
+		let record = vendor_record(LogFormat::Ecs);
+		assert_eq!(record["log.level"], "INFO");
+		assert_eq!(record["log.logger"], "rust_telemetry::stdout::tests");
+		assert_eq!(record["message"], "Request \"done\"");
+		assert_eq!(record["status"], 200);
+		assert_eq!(record["user"], "alice");
+		assert!(record["@timestamp"].is_string() && record["ecs.version"].is_string());
+	}
+
+	#[test]
+	fn test_gcp_format() {
+		let record = vendor_record(LogFormat::Gcp);
+		assert_eq!(record["severity"], "INFO");
+		assert_eq!(record["message"], "Request \"done\"");
+		assert_eq!(record["status"], 200);
+		assert_eq!(
+			record["logging.googleapis.com/sourceLocation"]["function"],
+			"rust_telemetry::stdout::tests"
+		);
+		assert!(record["timestamp"].is_string());
+	}
+
+	#[test]
+	fn test_otlp_json_format() {
+		let record = vendor_record(LogFormat::OtlpJson);
+		assert_eq!(record["severityNumber"], 9);
+		assert_eq!(record["severityText"], "INFO");
+		assert_eq!(record["body"]["stringValue"], "Request \"done\"");
+		let attributes = record["attributes"].as_array().expect("Missing attributes");
+		assert!(attributes.contains(&serde_json::json!({
+			"key": "status",
+			"value": { "intValue": "200" },
+		})));
+		assert!(attributes.contains(&serde_json::json!({
+			"key": "user",
+			"value": { "stringValue": "alice" },
+		})));
+	}
+
+	#[test]
+	fn test_timestamps() {
+		let timestamp = |timestamp| {
+			let output = log_with(&StdoutLogsConfig {
+				format: LogFormat::Logfmt,
+				timestamp,
+				..Default::default()
+			});
+			let ts = output.strip_prefix("ts=").expect("Missing timestamp");
+			ts[..ts.find(' ').expect("Missing level")].to_owned()
+		};
+
+		let utc = timestamp(Timestamp::Utc);
+		let parsed = chrono::DateTime::parse_from_rfc3339(&utc).expect("Invalid timestamp");
+		assert!(utc.ends_with('Z') && utc.len() == "2025-01-01T00:00:00.000000Z".len(), "{utc}");
+		assert!((chrono::Utc::now() - parsed.to_utc()).num_seconds() < 10);
+
+		let nanos = timestamp(Timestamp::Rfc3339Nanos);
+		chrono::DateTime::parse_from_rfc3339(&nanos).expect("Invalid timestamp");
+		assert_eq!(nanos.len(), "2025-01-01T00:00:00.000000000Z".len(), "{nanos}");
+
+		let local = timestamp(Timestamp::Local);
+		let parsed = chrono::DateTime::parse_from_rfc3339(&local).expect("Invalid timestamp");
+		assert_eq!(parsed.offset(), chrono::Local::now().offset());
+
+		let millis: i64 = timestamp(Timestamp::EpochMillis).parse().expect("Invalid timestamp");
+		assert!((chrono::Utc::now().timestamp_millis() - millis).abs() < 10_000);
+	}
+
+	#[test]
+	fn test_no_timestamp() {
+		for format in [LogFormat::Logfmt, LogFormat::Full, LogFormat::Json] {
+			let output = log_with(&StdoutLogsConfig {
+				format,
+				ansi: false,
+				timestamp: Timestamp::None,
+				..Default::default()
+			});
+			assert!(
+				output.starts_with("level=")
+					|| output.starts_with(" INFO")
+					|| output.starts_with(r#"{"level""#),
+				"{output}"
+			);
+		}
+	}
+
+	#[test]
+	fn test_stderr_routing() {
+		let (stdout, stderr) = (CapturedOutput::default(), CapturedOutput::default());
+		let config = StdoutLogsConfig {
+			format: LogFormat::Logfmt,
+			timestamp: Timestamp::None,
+			stderr_level: Some(tracing::level_filters::LevelFilter::WARN.into()),
+			..Default::default()
+		};
+		let subscriber = tracing_subscriber::registry().with(routed_layer(
+			&config,
+			stdout.clone(),
+			stderr.clone(),
+		));
+		tracing::subscriber::with_default(subscriber, || {
+			tracing::info!("Routine");
+			tracing::warn!("Suspicious");
+			tracing::error!("Broken");
+		});
+
+		let (stdout, stderr) = (stdout.contents(), stderr.contents());
+		assert_eq!(stdout.lines().count(), 1, "{stdout}");
+		assert!(stdout.contains("msg=Routine"), "{stdout}");
+		assert_eq!(stderr.lines().count(), 2, "{stderr}");
+		assert!(stderr.contains("msg=Suspicious") && stderr.contains("msg=Broken"), "{stderr}");
+	}
+}
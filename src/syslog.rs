@@ -0,0 +1,347 @@
+// SPDX-FileCopyrightText: 2025 Famedly GmbH (info@famedly.com)
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Syslog logs
+//!
+//! Module containing the layer sending the events as RFC 5424 syslog messages
+//! from the [`SyslogConfig`], and the datagram sockets shared with the journal
+//! logs.
+use std::{
+	fmt::Write as _,
+	io,
+	net::{ToSocketAddrs as _, UdpSocket},
+	path::{Path, PathBuf},
+	sync::atomic::{AtomicU64, Ordering},
+};
+
+use chrono::Utc;
+use tracing::{
+	Event, Level, Subscriber,
+	span::{Attributes, Id, Record},
+};
+use tracing_subscriber::{Layer, layer::Context, registry::LookupSpan};
+
+use crate::{
+	config::{SyslogConfig, SyslogTransport},
+	correlation::current_trace_context,
+	fields,
+};
+
+/// Id of the structured data element holding the fields of the events, using
+/// the private enterprise number reserved for documentation
+const SD_ID: &str = "tracing@32473";
+
+/// Non-blocking datagram socket the records are sent to, dropping them
+/// rather than stalling the emitting thread when the server is backed up
+#[derive(Debug)]
+pub(crate) struct Destination {
+	/// Socket of the server
+	socket: Socket,
+	/// Number of records dropped since the last one sent
+	dropped: AtomicU64,
+}
+
+/// Datagram socket of the server
+#[derive(Debug)]
+enum Socket {
+	/// Local Unix socket, addressed on every send so that restarting the
+	/// server doesn't break the logs
+	#[cfg(unix)]
+	Unix {
+		/// Unbound socket sending the records
+		socket: std::os::unix::net::UnixDatagram,
+		/// Path of the socket of the server
+		path: PathBuf,
+	},
+	/// UDP socket connected to the server
+	Udp(UdpSocket),
+}
+
+impl Destination {
+	/// Opens a socket sending to the given Unix socket, checking that the
+	/// server is listening
+	#[cfg(unix)]
+	pub(crate) fn unix(path: &Path) -> io::Result<Self> {
+		use std::os::unix::net::UnixDatagram;
+
+		UnixDatagram::unbound()?.connect(path)?;
+		let socket = UnixDatagram::unbound()?;
+		socket.set_nonblocking(true)?;
+		Ok(Self::new(Socket::Unix { socket, path: path.to_owned() }))
+	}
+
+	/// Unix sockets are not supported outside of Unix
+	#[cfg(not(unix))]
+	pub(crate) fn unix(_path: &Path) -> io::Result<Self> {
+		Err(io::Error::new(io::ErrorKind::Unsupported, "Unix sockets require a Unix system"))
+	}
+
+	/// Opens a UDP socket sending to the given `host:port`
+	pub(crate) fn udp(address: &str) -> io::Result<Self> {
+		let address = address.to_socket_addrs()?.next().ok_or_else(|| {
+			io::Error::new(io::ErrorKind::InvalidInput, format!("Could not resolve {address}"))
+		})?;
+		let local = if address.is_ipv4() { "0.0.0.0:0" } else { "[::]:0" };
+		let socket = UdpSocket::bind(local)?;
+		socket.connect(address)?;
+		socket.set_nonblocking(true)?;
+		Ok(Self::new(Socket::Udp(socket)))
+	}
+
+	/// Sends the records to the socket
+	const fn new(socket: Socket) -> Self {
+		Self { socket, dropped: AtomicU64::new(0) }
+	}
+
+	/// Number of records dropped since the last one sent, to be reported in
+	/// the next record
+	pub(crate) fn dropped(&self) -> u64 {
+		self.dropped.load(Ordering::Relaxed)
+	}
+
+	/// Sends a record reporting the given number of dropped records in a
+	/// single datagram, or counts it as dropped if the socket would block or
+	/// fails, as errors can't be reported without logging again
+	pub(crate) fn send(&self, payload: &[u8], reported: u64) {
+		let sent = match &self.socket {
+			#[cfg(unix)]
+			Socket::Unix { socket, path } => socket.send_to(payload, path),
+			Socket::Udp(socket) => socket.send(payload),
+		};
+		if sent.is_ok() {
+			self.dropped.fetch_sub(reported, Ordering::Relaxed);
+		} else {
+			self.dropped.fetch_add(1, Ordering::Relaxed);
+		}
+	}
+}
+
+/// Syslog severity of a level
+pub(crate) const fn severity(level: Level) -> u8 {
+	match level {
+		Level::ERROR => 3,
+		Level::WARN => 4,
+		Level::INFO => 6,
+		_ => 7,
+	}
+}
+
+/// Layer sending the events as RFC 5424 messages
+///
+/// Messages sent while the server is backed up are dropped and their number is
+/// reported in the `dropped_messages` parameter of the next message.
+#[derive(Debug)]
+pub(crate) struct SyslogLayer {
+	/// Socket of the server
+	destination: Destination,
+	/// Code of the facility
+	facility: u8,
+	/// `HOSTNAME` header field
+	hostname: String,
+	/// `APP-NAME` header field
+	app_name: String,
+	/// `PROCID` header field
+	procid: u32,
+}
+
+impl SyslogLayer {
+	/// Opens the socket of the configured server
+	pub(crate) fn new(config: &SyslogConfig, service_name: &str) -> io::Result<Self> {
+		let destination = match &config.transport {
+			SyslogTransport::Unix(path) => Destination::unix(path)?,
+			SyslogTransport::Udp(address) => Destination::udp(address)?,
+		};
+		let hostname = config.hostname.clone().or_else(system_hostname).unwrap_or_default();
+		Ok(Self {
+			destination,
+			facility: config.facility.code(),
+			hostname: header_field(&hostname, 255),
+			app_name: header_field(config.app_name.as_deref().unwrap_or(service_name), 48),
+			procid: std::process::id(),
+		})
+	}
+
+	/// Builds the message of an event, reporting the number of messages
+	/// dropped before it if any
+	fn message<S>(&self, event: &Event<'_>, ctx: &Context<'_, S>, dropped: u64) -> String
+	where
+		S: Subscriber + for<'a> LookupSpan<'a>,
+	{
+		let metadata = event.metadata();
+		let (span_names, fields) = fields::event_fields::<Self, S>(event, ctx);
+		let priority = self.facility * 8 + severity(*metadata.level());
+		let timestamp = Utc::now().format("%Y-%m-%dT%H:%M:%S%.6fZ");
+
+		let mut message = format!(
+			"<{priority}>1 {timestamp} {} {} {} - [{SD_ID}",
+			self.hostname, self.app_name, self.procid
+		);
+		let mut param = |name: &str, value: &str| {
+			let _ = write!(message, " {}=\"{}\"", param_name(name), param_value(value));
+		};
+		param("target", metadata.target());
+		if !span_names.is_empty() {
+			param("spans", &span_names.join(":"));
+		}
+		if let Some(trace_context) = current_trace_context() {
+			param("trace_id", &trace_context.trace_id.to_string());
+			param("span_id", &trace_context.span_id.to_string());
+		}
+		for (name, value) in &fields.fields {
+			param(name, value);
+		}
+		if dropped > 0 {
+			param("dropped_messages", &dropped.to_string());
+		}
+		message.push(']');
+		if let Some(text) = fields.message {
+			message.push(' ');
+			message.push_str(&text);
+		}
+		message
+	}
+}
+
+impl<S> Layer<S> for SyslogLayer
+where
+	S: Subscriber + for<'a> LookupSpan<'a>,
+{
+	fn on_new_span(&self, attrs: &Attributes<'_>, id: &Id, ctx: Context<'_, S>) {
+		fields::on_new_span::<Self, S>(attrs, id, &ctx);
+	}
+
+	fn on_record(&self, id: &Id, values: &Record<'_>, ctx: Context<'_, S>) {
+		fields::on_record::<Self, S>(id, values, &ctx);
+	}
+
+	fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+		let dropped = self.destination.dropped();
+		self.destination.send(self.message(event, &ctx, dropped).as_bytes(), dropped);
+	}
+}
+
+/// Name of the host, read from the kernel
+fn system_hostname() -> Option<String> {
+	std::fs::read_to_string("/proc/sys/kernel/hostname")
+		.ok()
+		.map(|hostname| hostname.trim().to_owned())
+		.filter(|hostname| !hostname.is_empty())
+}
+
+/// Header field made of printable ASCII characters, with the given maximum
+/// length, or the nil value if empty
+fn header_field(value: &str, max_len: usize) -> String {
+	let field: String =
+		value.chars().map(|c| if c.is_ascii_graphic() { c } else { '_' }).take(max_len).collect();
+	if field.is_empty() { "-".to_owned() } else { field }
+}
+
+/// Name of a structured data parameter, which can't contain `=`, spaces, `]`
+/// or `"` and is at most 32 characters long
+fn param_name(name: &str) -> String {
+	name.chars()
+		.map(|c| if c.is_ascii_graphic() && !matches!(c, '=' | ']' | '"') { c } else { '_' })
+		.take(32)
+		.collect()
+}
+
+/// Value of a structured data parameter, escaping `"`, `\` and `]`
+fn param_value(value: &str) -> String {
+	let mut escaped = String::with_capacity(value.len());
+	for c in value.chars() {
+		if matches!(c, '"' | '\\' | ']') {
+			escaped.push('\\');
+		}
+		escaped.push(c);
+	}
+	escaped
+}
+
+#[cfg(test)]
+mod tests {
+	#![allow(clippy::expect_used)]
+	use std::{collections::HashMap, net::UdpSocket, time::Duration};
+
+	use crate::{
+		build_otel,
+		config::{OtelConfig, SyslogConfig, SyslogFacility, SyslogTransport},
+	};
+
+	/// Config sending the logs of the crate to the given transport
+	fn syslog_config(transport: SyslogTransport) -> SyslogConfig {
+		SyslogConfig {
+			enabled: true,
+			level: tracing::level_filters::LevelFilter::DEBUG.into(),
+			general_level: tracing::level_filters::LevelFilter::OFF.into(),
+			dependencies_levels: HashMap::new(),
+			transport,
+			facility: SyslogFacility::Local0,
+			app_name: Some("my app".to_owned()),
+			hostname: Some("host".to_owned()),
+		}
+	}
+
+	/// Logs test events with the config
+	fn log(config: SyslogConfig) {
+		let config = OtelConfig { syslog: Some(config), ..Default::default() };
+		let (subscriber, _guard) = build_otel!(&config).expect("Error initializing Otel");
+		tracing::subscriber::with_default(subscriber, || {
+			tracing::info_span!("request", user = "alice").in_scope(|| {
+				tracing::warn!(path = "/a]\"b", "Sent to syslog");
+			});
+			tracing::trace!("Filtered out");
+		});
+	}
+
+	#[test]
+	fn test_syslog_udp() {
+		let server = UdpSocket::bind("127.0.0.1:0").expect("Error binding socket");
+		server.set_read_timeout(Some(Duration::from_secs(5))).expect("Error setting timeout");
+		let address = server.local_addr().expect("Missing address").to_string();
+		log(syslog_config(SyslogTransport::Udp(address)));
+
+		let mut buf = [0; 1024];
+		let len = server.recv(&mut buf).expect("Missing message");
+		let message = std::str::from_utf8(&buf[..len]).expect("Invalid message");
+		let (header, rest) = message.split_once(" - [").expect("Invalid message");
+		let mut header = header.split(' ');
+		assert_eq!(header.next(), Some("<132>1"));
+		assert!(header.next().is_some_and(|timestamp| timestamp.ends_with('Z')));
+		assert_eq!(header.next(), Some("host"));
+		assert_eq!(header.next(), Some("my_app"));
+		assert_eq!(header.next(), Some(std::process::id().to_string().as_str()));
+		assert_eq!(
+			rest,
+			"tracing@32473 target=\"rust_telemetry::syslog::tests\" spans=\"request\" \
+			 user=\"alice\" path=\"/a\\]\\\"b\"] Sent to syslog"
+		);
+		server.set_nonblocking(true).expect("Error setting non-blocking");
+		assert!(server.recv(&mut buf).is_err(), "Unexpected message");
+	}
+
+	#[cfg(unix)]
+	#[test]
+	fn test_syslog_unix() {
+		let directory = tempfile::tempdir().expect("Error creating directory");
+		let path = directory.path().join("log.sock");
+		let server = std::os::unix::net::UnixDatagram::bind(&path).expect("Error binding socket");
+		log(syslog_config(SyslogTransport::Unix(path)));
+
+		let mut buf = [0; 1024];
+		let len = server.recv(&mut buf).expect("Missing message");
+		let message = std::str::from_utf8(&buf[..len]).expect("Invalid message");
+		assert!(message.starts_with("<132>1 "), "{message}");
+		assert!(message.ends_with("] Sent to syslog"), "{message}");
+	}
+
+	#[test]
+	fn test_syslog_missing_socket() {
+		let directory = tempfile::tempdir().expect("Error creating directory");
+		let config = OtelConfig {
+			syslog: Some(syslog_config(SyslogTransport::Unix(directory.path().join("missing")))),
+			..Default::default()
+		};
+		assert!(matches!(build_otel!(&config), Err(crate::OtelInitError::SyslogError(_))));
+	}
+}
@@ -0,0 +1,396 @@
+// SPDX-FileCopyrightText: 2025 Famedly GmbH (info@famedly.com)
+//
+// SPDX-License-Identifier: Apache-2.0
+
+//! Mock OpenTelemetry collector
+//!
+//! This module provides a local OTLP collector for integration tests. It
+//! accepts exports over gRPC and over HTTP (protobuf) on the same port and
+//! records every request it receives, including its headers, so tests can
+//! check end to end what a service really exports.
+use std::{
+	net::SocketAddr,
+	sync::{Arc, Mutex, PoisonError},
+	time::Duration,
+};
+
+use axum::{
+	Router,
+	body::Bytes,
+	extract::State,
+	http::{HeaderMap, HeaderValue, StatusCode, header::CONTENT_TYPE},
+	response::{IntoResponse, Response},
+	routing::post,
+};
+use opentelemetry_proto::tonic::{
+	collector::{
+		logs::v1::{
+			ExportLogsServiceRequest, ExportLogsServiceResponse,
+			logs_service_server::{LogsService, LogsServiceServer},
+		},
+		metrics::v1::{
+			ExportMetricsServiceRequest, ExportMetricsServiceResponse,
+			metrics_service_server::{MetricsService, MetricsServiceServer},
+		},
+		trace::v1::{
+			ExportTraceServiceRequest, ExportTraceServiceResponse,
+			trace_service_server::{TraceService, TraceServiceServer},
+		},
+	},
+	common::v1::{AnyValue, KeyValue, any_value},
+	resource::v1::Resource,
+};
+use prost::Message;
+use tokio::{net::TcpListener, sync::oneshot};
+use url::Url;
+
+/// Content type of OTLP/HTTP protobuf requests
+const PROTOBUF_CONTENT_TYPE: &str = "application/x-protobuf";
+
+/// Transport over which an export request was received
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Transport {
+	/// OTLP/gRPC
+	Grpc,
+	/// OTLP/HTTP with protobuf payload
+	Http,
+}
+
+/// Export request recorded by the [`MockCollector`]
+#[derive(Debug, Clone)]
+pub struct RecordedRequest<T> {
+	/// Transport the request was received over
+	pub transport: Transport,
+	/// Headers (or gRPC metadata) of the request
+	pub headers: HeaderMap,
+	/// Decoded export request
+	pub request: T,
+}
+
+/// All the requests received by the collector, per signal
+#[derive(Debug, Default)]
+struct Recorded {
+	/// Received trace exports
+	traces: Vec<RecordedRequest<ExportTraceServiceRequest>>,
+	/// Received metric exports
+	metrics: Vec<RecordedRequest<ExportMetricsServiceRequest>>,
+	/// Received log exports
+	logs: Vec<RecordedRequest<ExportLogsServiceRequest>>,
+}
+
+/// Shared state of the collector services
+#[derive(Debug, Default)]
+struct Recorder {
+	/// Received requests
+	recorded: Mutex<Recorded>,
+}
+
+impl Recorder {
+	/// Stores a received export request
+	fn record<T: Signal>(&self, transport: Transport, headers: HeaderMap, request: T) {
+		let mut recorded = self.recorded.lock().unwrap_or_else(PoisonError::into_inner);
+		T::requests(&mut recorded).push(RecordedRequest { transport, headers, request });
+	}
+
+	/// Returns a copy of the received requests of a signal
+	fn requests<T: Signal + Clone>(&self) -> Vec<RecordedRequest<T>> {
+		T::requests(&mut self.recorded.lock().unwrap_or_else(PoisonError::into_inner)).clone()
+	}
+}
+
+/// OTLP export request of one of the OpenTelemetry signals
+pub trait ExportRequest {
+	/// Resources the request carries data for
+	fn resources(&self) -> impl Iterator<Item = &Resource>;
+}
+
+impl ExportRequest for ExportTraceServiceRequest {
+	fn resources(&self) -> impl Iterator<Item = &Resource> {
+		self.resource_spans.iter().filter_map(|spans| spans.resource.as_ref())
+	}
+}
+
+impl ExportRequest for ExportMetricsServiceRequest {
+	fn resources(&self) -> impl Iterator<Item = &Resource> {
+		self.resource_metrics.iter().filter_map(|metrics| metrics.resource.as_ref())
+	}
+}
+
+impl ExportRequest for ExportLogsServiceRequest {
+	fn resources(&self) -> impl Iterator<Item = &Resource> {
+		self.resource_logs.iter().filter_map(|logs| logs.resource.as_ref())
+	}
+}
+
+/// Export request the collector knows how to record
+trait Signal: ExportRequest + Message + Default + Sized {
+	/// Response sent back to the exporter
+	type Response: Message + Default;
+
+	/// Selects the list storing the requests of this signal
+	fn requests(recorded: &mut Recorded) -> &mut Vec<RecordedRequest<Self>>;
+}
+
+impl Signal for ExportTraceServiceRequest {
+	type Response = ExportTraceServiceResponse;
+
+	fn requests(recorded: &mut Recorded) -> &mut Vec<RecordedRequest<Self>> {
+		&mut recorded.traces
+	}
+}
+
+impl Signal for ExportMetricsServiceRequest {
+	type Response = ExportMetricsServiceResponse;
+
+	fn requests(recorded: &mut Recorded) -> &mut Vec<RecordedRequest<Self>> {
+		&mut recorded.metrics
+	}
+}
+
+impl Signal for ExportLogsServiceRequest {
+	type Response = ExportLogsServiceResponse;
+
+	fn requests(recorded: &mut Recorded) -> &mut Vec<RecordedRequest<Self>> {
+		&mut recorded.logs
+	}
+}
+
+#[tonic::async_trait]
+impl TraceService for Recorder {
+	async fn export(
+		&self,
+		request: tonic::Request<ExportTraceServiceRequest>,
+	) -> Result<tonic::Response<ExportTraceServiceResponse>, tonic::Status> {
+		let (metadata, _, request) = request.into_parts();
+		self.record(Transport::Grpc, metadata.into_headers(), request);
+		Ok(tonic::Response::new(ExportTraceServiceResponse::default()))
+	}
+}
+
+#[tonic::async_trait]
+impl MetricsService for Recorder {
+	async fn export(
+		&self,
+		request: tonic::Request<ExportMetricsServiceRequest>,
+	) -> Result<tonic::Response<ExportMetricsServiceResponse>, tonic::Status> {
+		let (metadata, _, request) = request.into_parts();
+		self.record(Transport::Grpc, metadata.into_headers(), request);
+		Ok(tonic::Response::new(ExportMetricsServiceResponse::default()))
+	}
+}
+
+#[tonic::async_trait]
+impl LogsService for Recorder {
+	async fn export(
+		&self,
+		request: tonic::Request<ExportLogsServiceRequest>,
+	) -> Result<tonic::Response<ExportLogsServiceResponse>, tonic::Status> {
+		let (metadata, _, request) = request.into_parts();
+		self.record(Transport::Grpc, metadata.into_headers(), request);
+		Ok(tonic::Response::new(ExportLogsServiceResponse::default()))
+	}
+}
+
+/// Handles an OTLP/HTTP export request with protobuf payload
+async fn http_export<T: Signal>(
+	State(recorder): State<Arc<Recorder>>,
+	headers: HeaderMap,
+	body: Bytes,
+) -> Response {
+	if headers.get(CONTENT_TYPE).is_none_or(|content_type| content_type != PROTOBUF_CONTENT_TYPE) {
+		return StatusCode::UNSUPPORTED_MEDIA_TYPE.into_response();
+	}
+	let request = match T::decode(body) {
+		Ok(request) => request,
+		Err(err) => return (StatusCode::BAD_REQUEST, err.to_string()).into_response(),
+	};
+	recorder.record(Transport::Http, headers, request);
+	(
+		[(CONTENT_TYPE, HeaderValue::from_static(PROTOBUF_CONTENT_TYPE))],
+		T::Response::default().encode_to_vec(),
+	)
+		.into_response()
+}
+
+/// Local OTLP collector recording every export request it receives
+///
+/// The collector listens on a random local port and serves OTLP/gRPC and
+/// OTLP/HTTP (`/v1/traces`, `/v1/metrics` and `/v1/logs`) on it. It is stopped
+/// when dropped.
+///
+/// Example
+///
+/// ```rust
+/// use rust_telemetry::{
+/// 	config::{ExporterConfig, OtelConfig, ProviderConfig},
+/// 	testing::MockCollector,
+/// };
+///
+/// #[tokio::main]
+/// async fn main() {
+/// 	let collector = MockCollector::start().await.unwrap();
+/// 	let config = OtelConfig {
+/// 		exporter: Some(ExporterConfig {
+/// 			endpoint: collector.endpoint().into(),
+/// 			traces: Some(ProviderConfig {
+/// 				enabled: true,
+/// 				..Default::default()
+/// 			}),
+/// 			..Default::default()
+/// 		}),
+/// 		..Default::default()
+/// 	};
+///
+/// 	// Initialize the telemetry with `config`, then inspect
+/// 	// `collector.traces()`
+/// }
+/// ```
+#[derive(Debug)]
+pub struct MockCollector {
+	/// Address the collector listens on
+	addr: SocketAddr,
+	/// Received requests
+	recorder: Arc<Recorder>,
+	/// Stops the server when dropped
+	_shutdown: oneshot::Sender<()>,
+}
+
+impl MockCollector {
+	/// Starts a collector listening on a random port of the loopback interface
+	pub async fn start() -> std::io::Result<Self> {
+		let listener = TcpListener::bind((std::net::Ipv4Addr::LOCALHOST, 0)).await?;
+		let addr = listener.local_addr()?;
+		let recorder = Arc::new(Recorder::default());
+
+		let router = tonic::service::Routes::new(TraceServiceServer::from_arc(recorder.clone()))
+			.add_service(MetricsServiceServer::from_arc(recorder.clone()))
+			.add_service(LogsServiceServer::from_arc(recorder.clone()))
+			.into_axum_router()
+			.merge(
+				Router::new()
+					.route("/v1/traces", post(http_export::<ExportTraceServiceRequest>))
+					.route("/v1/metrics", post(http_export::<ExportMetricsServiceRequest>))
+					.route("/v1/logs", post(http_export::<ExportLogsServiceRequest>))
+					.with_state(recorder.clone()),
+			);
+
+		let (shutdown, shutdown_rx) = oneshot::channel::<()>();
+		tokio::spawn(async move {
+			let server = axum::serve(listener, router).with_graceful_shutdown(async {
+				let _ = shutdown_rx.await;
+			});
+			if let Err(err) = server.await {
+				tracing::warn!("Mock collector stopped: {err}");
+			}
+		});
+
+		Ok(Self { addr, recorder, _shutdown: shutdown })
+	}
+
+	/// Endpoint to export to, for both gRPC and HTTP exporters
+	#[allow(clippy::expect_used)]
+	#[must_use]
+	pub fn endpoint(&self) -> Url {
+		Url::parse(&format!("http://{}", self.addr)).expect("Error parsing collector endpoint")
+	}
+
+	/// Received trace export requests
+	#[must_use]
+	pub fn traces(&self) -> Vec<RecordedRequest<ExportTraceServiceRequest>> {
+		self.recorder.requests()
+	}
+
+	/// Received metric export requests
+	#[must_use]
+	pub fn metrics(&self) -> Vec<RecordedRequest<ExportMetricsServiceRequest>> {
+		self.recorder.requests()
+	}
+
+	/// Received log export requests
+	#[must_use]
+	pub fn logs(&self) -> Vec<RecordedRequest<ExportLogsServiceRequest>> {
+		self.recorder.requests()
+	}
+
+	/// Waits until `condition` holds for the collector or the `timeout`
+	/// elapses. Returns whether the condition was met.
+	pub async fn wait_until(
+		&self,
+		timeout: Duration,
+		condition: impl Fn(&Self) -> bool + Sync,
+	) -> bool {
+		tokio::time::timeout(timeout, async {
+			while !condition(self) {
+				tokio::time::sleep(Duration::from_millis(10)).await;
+			}
+		})
+		.await
+		.is_ok()
+	}
+}
+
+impl<T: ExportRequest> RecordedRequest<T> {
+	/// Returns the string value of a resource attribute of the request, if
+	/// present
+	#[must_use]
+	pub fn resource_attribute(&self, key: &str) -> Option<&str> {
+		self.request
+			.resources()
+			.flat_map(|resource| resource.attributes.iter())
+			.find(|attribute| attribute.key == key)
+			.and_then(string_value)
+	}
+}
+
+/// Returns the value of an attribute if it is a string
+fn string_value(attribute: &KeyValue) -> Option<&str> {
+	match attribute.value.as_ref() {
+		Some(AnyValue { value: Some(any_value::Value::StringValue(value)) }) => Some(value),
+		_ => None,
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	#![allow(clippy::expect_used)]
+	use std::time::Duration;
+
+	use opentelemetry::{
+		KeyValue,
+		trace::{Tracer as _, TracerProvider as _},
+	};
+	use opentelemetry_otlp::{SpanExporter, WithExportConfig as _, WithHttpConfig as _};
+	use opentelemetry_sdk::{Resource, trace::SdkTracerProvider};
+
+	use super::{MockCollector, Transport};
+
+	#[tokio::test(flavor = "multi_thread")]
+	async fn test_http_export_is_recorded() {
+		let collector = MockCollector::start().await.expect("Error starting collector");
+		let exporter = SpanExporter::builder()
+			.with_http()
+			.with_endpoint(format!("{}v1/traces", collector.endpoint()))
+			.with_headers([("x-test-header".to_owned(), "test".to_owned())].into())
+			.build()
+			.expect("Error building exporter");
+		let provider = SdkTracerProvider::builder()
+			.with_resource(
+				Resource::builder()
+					.with_attribute(KeyValue::new("service.instance.id", "test-instance"))
+					.build(),
+			)
+			.with_batch_exporter(exporter)
+			.build();
+
+		provider.tracer("test").in_span("test_span", |_| {});
+		provider.force_flush().expect("Error flushing spans");
+		assert!(collector.wait_until(Duration::from_secs(5), |c| !c.traces().is_empty()).await);
+
+		let traces = collector.traces();
+		assert_eq!(traces[0].transport, Transport::Http);
+		assert_eq!(traces[0].headers.get("x-test-header").expect("Missing header"), "test");
+		assert_eq!(traces[0].resource_attribute("service.instance.id"), Some("test-instance"));
+		let span = &traces[0].request.resource_spans[0].scope_spans[0].spans[0];
+		assert_eq!(span.name, "test_span");
+	}
+}